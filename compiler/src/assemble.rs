@@ -0,0 +1,152 @@
+use std::{rc::Rc, str::FromStr};
+
+use anyhow::Error;
+use lexer::Lexer;
+use object::Object;
+use opcode::{Instructions, Opcode};
+use parser::{
+    ast::{
+        BooleanLiteral, CharLiteral, Expression, FloatLiteral, IntegerLiteral, Literal,
+        PrefixExpression, Statement, StringLiteral,
+    },
+    Parser,
+};
+
+use crate::Bytecode;
+
+const CONSTANTS_HEADER: &str = "; constants:";
+
+/// Assembles textual bytecode into a `Bytecode`.
+///
+/// Each line holds one instruction, a mnemonic followed by its operands,
+/// optionally prefixed with the offset printed by `Bytecode::disassemble`.
+/// Lines after a `; constants:` header are constant pool entries, one
+/// integer, float, boolean, string or char literal per line:
+///
+/// ```text
+/// OpConst 0
+/// OpConst 1
+/// OpAdd
+/// OpPop
+/// ; constants:
+/// 1
+/// "two"
+/// ```
+///
+/// Blank lines are ignored. Compiled functions can't be written as
+/// constants.
+pub fn assemble(text: &str) -> Result<Bytecode, Error> {
+    let mut instructions = Instructions::default();
+    let mut constants = Vec::new();
+    let mut in_constants = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if line == CONSTANTS_HEADER {
+            in_constants = true;
+            continue;
+        }
+
+        let result = if in_constants {
+            assemble_constant(line).map(|constant| constants.push(Rc::new(constant)))
+        } else {
+            assemble_instruction(line).map(|instruction| instructions.extend_from(instruction))
+        };
+
+        result.map_err(|e| Error::msg(format!("line {}: {}", line_number, e)))?;
+    }
+
+    Ok(Bytecode {
+        instructions,
+        constants,
+        positions: vec![],
+    })
+}
+
+fn assemble_instruction(line: &str) -> Result<Instructions, Error> {
+    let mut words = line.split_whitespace().peekable();
+
+    // Skip the offset column of disassembler output.
+    if let Some(word) = words.peek() {
+        if word.chars().all(|c| c.is_ascii_digit()) {
+            words.next();
+        }
+    }
+
+    let mnemonic = words
+        .next()
+        .ok_or_else(|| Error::msg("expected an opcode"))?;
+    let op = Opcode::from_str(mnemonic).map_err(Error::msg)?;
+    let widths = &opcode::lookup(op).operand_widths;
+
+    let operands = words
+        .map(|word| {
+            word.parse::<usize>()
+                .map_err(|_| Error::msg(format!("invalid operand `{}`", word)))
+        })
+        .collect::<Result<Vec<usize>, Error>>()?;
+
+    if operands.len() != widths.len() {
+        return Err(Error::msg(format!(
+            "{} expects {} operands, got {}",
+            op,
+            widths.len(),
+            operands.len()
+        )));
+    }
+
+    for (operand, width) in operands.iter().zip(widths) {
+        if *operand >= 1 << (width * 8) {
+            return Err(Error::msg(format!(
+                "operand {} does not fit in {} bytes",
+                operand, width
+            )));
+        }
+    }
+
+    Ok(opcode::make(op, &operands))
+}
+
+fn assemble_constant(line: &str) -> Result<Object, Error> {
+    let mut parser = Parser::new(Lexer::new(line));
+    let program = parser.parse_program()?;
+
+    let expression = match program.statements.as_slice() {
+        [Statement::Expr(expression)] => expression,
+        _ => return Err(Error::msg(format!("invalid constant `{}`", line))),
+    };
+
+    match expression {
+        Expression::Literal(Literal::Integer(IntegerLiteral { value, .. })) => {
+            Ok(Object::Integer(*value))
+        }
+        Expression::Literal(Literal::Float(FloatLiteral { value, .. })) => {
+            Ok(Object::Float(*value))
+        }
+        Expression::Literal(Literal::Boolean(BooleanLiteral { value, .. })) => {
+            Ok(Object::Boolean(*value))
+        }
+        Expression::Literal(Literal::String(StringLiteral { value, .. })) => {
+            Ok(Object::String(value.clone()))
+        }
+        Expression::Literal(Literal::Char(CharLiteral { value, .. })) => Ok(Object::Char(*value)),
+        Expression::Prefix(PrefixExpression {
+            operator, right, ..
+        }) if operator.literal == "-" => match right.as_ref() {
+            Expression::Literal(Literal::Integer(IntegerLiteral { value, .. })) => {
+                Ok(Object::Integer(-value))
+            }
+            Expression::Literal(Literal::Float(FloatLiteral { value, .. })) => {
+                Ok(Object::Float(-value))
+            }
+            _ => Err(Error::msg(format!("invalid constant `{}`", line))),
+        },
+        _ => Err(Error::msg(format!("invalid constant `{}`", line))),
+    }
+}
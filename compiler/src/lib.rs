@@ -1,55 +1,391 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
 use anyhow::Error;
-use lexer::token::{Token, TokenType};
+use lexer::{token::TokenType, Lexer};
 use opcode::{Instructions, Opcode};
 use parser::ast::{
-    BlockStatement, BooleanLiteral, Expression, IntegerLiteral, Literal, Node, Statement,
+    Assignment, BlockStatement, BooleanLiteral, CharLiteral, Expression, FloatLiteral,
+    FunctionLiteral, Identifier, InfixExpression, IntegerLiteral, Literal, Node, Span, Statement,
     StringLiteral,
 };
-use symbol_table::{SymbolScope, SymbolTable};
+use parser::Parser;
+use symbol_table::{Symbol, SymbolScope, SymbolTable};
 
+pub mod assemble;
+pub mod serialize;
 pub mod symbol_table;
 
 #[derive(Clone, PartialEq)]
 pub struct Bytecode {
     pub instructions: opcode::Instructions,
     pub constants: Vec<Rc<object::Object>>,
+    /// The source span of the statement each main-program instruction was
+    /// emitted for, keyed by instruction offset in ascending order.
+    pub positions: Vec<(usize, Span)>,
 }
 
-impl std::fmt::Debug for Bytecode {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut bytecode_string = String::new();
+/// The compiled program borrowed from a `Compiler`, as returned by
+/// `Compiler::compile_in_place`.
+///
+/// Building a `Bytecode` copies the instructions, positions and constant
+/// pool into three new vectors and bumps the reference count of every
+/// constant. A view allocates nothing, which matters for large constant
+/// pools; call `to_bytecode` when an owned copy is needed.
+#[derive(Clone, Copy)]
+pub struct BytecodeView<'a> {
+    pub instructions: &'a Instructions,
+    pub constants: &'a [Rc<object::Object>],
+    pub positions: &'a [(usize, Span)],
+}
+
+impl BytecodeView<'_> {
+    pub fn to_bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.instructions.clone(),
+            constants: self.constants.to_vec(),
+            positions: self.positions.to_vec(),
+        }
+    }
+}
+
+/// Size and opcode frequency figures for a `Bytecode`, as returned by
+/// `Bytecode::stats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BytecodeStats {
+    /// Total bytes of instructions, operands included.
+    pub instruction_bytes: usize,
+    pub instruction_count: usize,
+    pub constant_count: usize,
+    /// How many times each opcode appears.
+    pub opcode_counts: HashMap<Opcode, usize>,
+}
+
+impl Bytecode {
+    /// Returns the span of the statement that emitted the instruction at
+    /// `offset`. Offsets inside an instruction's operands map to that
+    /// instruction.
+    pub fn span_at(&self, offset: usize) -> Option<Span> {
+        let index = self
+            .positions
+            .partition_point(|(position, _)| *position <= offset);
+
+        self.positions[..index].last().map(|(_, span)| *span)
+    }
+
+    /// Renders the main instructions one per line, e.g. `0000 OpConst 1`,
+    /// decoding each opcode's operands using its definition.
+    pub fn disassemble(&self) -> String {
+        self.disassemble_lines(false)
+    }
+
+    /// Like `disassemble`, but annotates each `OpConst` with the constant it
+    /// loads, e.g. `0000 OpConst 0    ; 42`.
+    pub fn disassemble_verbose(&self) -> String {
+        self.disassemble_lines(true)
+    }
+
+    fn disassemble_lines(&self, annotate_constants: bool) -> String {
+        let instructions = &self.instructions;
+        let mut output = String::new();
+        let mut i = 0;
+
+        while i < instructions.len() {
+            let op = Opcode::from(instructions[i]);
+            let definition = opcode::lookup(op);
+            let (operands, read) = opcode::read_operands(definition, &instructions[i + 1..]);
+
+            output.push_str(&format!(
+                "{:04} {}",
+                i,
+                Instructions::format_instructions(definition, &operands)
+            ));
+
+            if annotate_constants && op == Opcode::OpConst {
+                match self.constants.get(operands[0]) {
+                    Some(constant) => output.push_str(&format!("    ; {}", constant)),
+                    None => output.push_str("    ; <invalid constant index>"),
+                }
+            }
+
+            output.push('\n');
+
+            i += 1 + read;
+        }
+
+        output
+    }
+
+    /// Like `disassemble`, followed by the constant pool: one
+    /// `; constant N: value` line per literal, and for compiled functions a
+    /// header with their parameter and local counts and their disassembled
+    /// body.
+    pub fn disassemble_all(&self) -> String {
+        let mut output = self.disassemble();
+        let mut after_function = true;
+
+        for (index, constant) in self.constants.iter().enumerate() {
+            let (value, body) = match constant.as_ref() {
+                object::Object::CompiledFunction(function) => (
+                    format!(
+                        "function ({} parameters, {} locals)",
+                        function.num_parameters, function.num_locals
+                    ),
+                    Some(function.instructions.to_string()),
+                ),
+                object::Object::String(string) => (format!("{:?}", string), None),
+                object::Object::Char(ch) => (format!("{:?}", ch), None),
+                object::Object::Float(float) => (format!("{:?}", float), None),
+                constant => (constant.to_string(), None),
+            };
+
+            // Runs of literals are listed together; each function body is
+            // set apart by blank lines.
+            if after_function || body.is_some() {
+                output.push('\n');
+            }
+
+            output.push_str(&format!("; constant {}: {}\n", index, value));
+
+            after_function = body.is_some();
+            output.push_str(&body.unwrap_or_default());
+        }
+
+        output
+    }
+
+    /// Counts the instructions of the main program and of every compiled
+    /// function constant, decoding each opcode's operands using its
+    /// definition so they aren't mistaken for opcodes.
+    pub fn stats(&self) -> BytecodeStats {
+        let mut stats = BytecodeStats {
+            constant_count: self.constants.len(),
+            ..Default::default()
+        };
+
+        let functions = self
+            .constants
+            .iter()
+            .filter_map(|constant| match constant.as_ref() {
+                object::Object::CompiledFunction(function) => Some(&function.instructions),
+                _ => None,
+            });
+
+        for instructions in std::iter::once(&self.instructions).chain(functions) {
+            let mut i = 0;
+
+            while i < instructions.len() {
+                let op = Opcode::from(instructions[i]);
+                let (_, read) = opcode::read_operands(opcode::lookup(op), &instructions[i + 1..]);
+
+                stats.instruction_count += 1;
+                *stats.opcode_counts.entry(op).or_insert(0) += 1;
+
+                i += 1 + read;
+            }
+
+            stats.instruction_bytes += instructions.len();
+        }
+
+        stats
+    }
+
+    /// Combines separately compiled modules into one program that runs their
+    /// main instructions in order.
+    ///
+    /// Each module's jump targets are shifted past the instructions before
+    /// it, its constant indexes are remapped into a merged pool where equal
+    /// literals are shared, and its global slots are moved past those of
+    /// earlier modules so modules can't overwrite each other's variables.
+    /// Compiled functions get the same constant and global remapping; their
+    /// jumps are relative to their own body and stay as they are.
+    pub fn link(modules: Vec<Bytecode>) -> Result<Bytecode, Error> {
+        let mut linked = Bytecode {
+            instructions: Instructions::default(),
+            constants: Vec::new(),
+            positions: Vec::new(),
+        };
+        let mut global_offset = 0;
+        let mut constant_indexes = HashMap::new();
+
+        for module in modules {
+            let instruction_offset = linked.instructions.len();
+
+            // Functions may refer to constants after them in the pool, so
+            // every index is assigned before any function is rewritten.
+            let constant_map = module
+                .constants
+                .iter()
+                .map(|constant| match constant.as_ref() {
+                    object::Object::CompiledFunction(_) => {
+                        linked.constants.push(Rc::new(object::Object::Null));
+
+                        linked.constants.len() - 1
+                    }
+                    constant => {
+                        let key = constant.hash_key().ok();
+
+                        let existing = match &key {
+                            Some(key) => constant_indexes.get(key).copied(),
+                            None => linked
+                                .constants
+                                .iter()
+                                .position(|existing| same_constant(existing, constant)),
+                        };
+
+                        existing.unwrap_or_else(|| {
+                            linked.constants.push(Rc::new(constant.clone()));
 
-        for (i, instruction) in self.instructions.0.iter().enumerate() {
-            let op = Opcode::from(*instruction);
+                            let index = linked.constants.len() - 1;
 
-            bytecode_string.push_str(&format!("{:04} {}\n", i, op));
+                            if let Some(key) = key {
+                                constant_indexes.insert(key, index);
+                            }
+
+                            index
+                        })
+                    }
+                })
+                .collect::<Vec<usize>>();
+
+            let relocation = Relocation {
+                jump_offset: instruction_offset,
+                constants: &constant_map,
+                global_offset,
+            };
+
+            for (constant, index) in module.constants.iter().zip(constant_map.iter()) {
+                if let object::Object::CompiledFunction(function) = constant.as_ref() {
+                    let instructions = relocate(
+                        &function.instructions,
+                        &Relocation {
+                            jump_offset: 0,
+                            ..relocation
+                        },
+                    )?;
+
+                    linked.constants[*index] = Rc::new(object::Object::CompiledFunction(Rc::new(
+                        object::CompiledFunction::new(
+                            instructions,
+                            function.num_locals,
+                            function.num_parameters,
+                        ),
+                    )));
+                }
+            }
+
+            linked
+                .instructions
+                .extend_from(relocate(&module.instructions, &relocation)?);
+            linked.positions.extend(
+                module
+                    .positions
+                    .iter()
+                    .map(|(offset, span)| (offset + instruction_offset, *span)),
+            );
+
+            global_offset += global_count(&module);
         }
 
-        write!(f, "{}", bytecode_string)
+        Ok(linked)
+    }
+
+    /// Removes `OpJump`s that target the instruction directly after them,
+    /// both in the main program and in every compiled function constant.
+    pub fn optimize_jumps(&mut self) {
+        self.instructions = remove_redundant_jumps(&self.instructions, &mut self.positions);
+
+        for constant in self.constants.iter_mut() {
+            if let object::Object::CompiledFunction(function) = constant.as_ref() {
+                let instructions = remove_redundant_jumps(&function.instructions, &mut vec![]);
+
+                *constant = Rc::new(object::Object::CompiledFunction(Rc::new(
+                    object::CompiledFunction::new(
+                        instructions,
+                        function.num_locals,
+                        function.num_parameters,
+                    ),
+                )));
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Bytecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.disassemble())
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct CompilationScope {
     pub instructions: opcode::Instructions,
+    pub positions: Vec<(usize, Span)>,
     pub last_instruction: EmittedInstruction,
     pub previous_instruction: EmittedInstruction,
+
+    /// Loops enclosing the code being compiled, innermost last. Kept per
+    /// scope so a `break` inside a function can't escape a loop around it.
+    loops: Vec<LoopJumps>,
+}
+
+/// Positions of the `OpJump` placeholders emitted for `break` and
+/// `continue` inside one loop, patched once the loop is compiled.
+#[derive(Clone, Debug, Default)]
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// A non-fatal diagnostic produced while compiling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompileWarning {
+    pub message: String,
+}
+
+impl std::fmt::Display for CompileWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "warning: {}", self.message)
+    }
+}
+
+/// Lexes, parses and compiles `source` with a fresh compiler. Syntax errors
+/// are reported the same way as compile errors.
+pub fn compile_str(source: &str) -> Result<Bytecode, Error> {
+    let mut parser = Parser::new(Lexer::new(source));
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let mut compiler = Compiler::new();
+    compiler.compile_in_place(&Node::Program(program))?;
+
+    Ok(compiler.into_bytecode())
 }
 
 pub struct Compiler {
     pub constants: Vec<Rc<object::Object>>,
     pub symbol_table: SymbolTable,
 
+    /// Index of each integer, boolean and string constant in `constants`.
+    constant_indexes: HashMap<object::HashKey, usize>,
+
     scopes: Vec<CompilationScope>,
     scope_index: usize,
+
+    optimize: bool,
+    wrapping_mode: object::WrappingMode,
+    keep_last_value: bool,
+    warnings: Vec<CompileWarning>,
+
+    /// Span of the statement currently being compiled.
+    span: Span,
 }
 
 impl Compiler {
     pub fn new() -> Self {
         let main_scope = CompilationScope {
             instructions: opcode::Instructions::default(),
+            positions: Vec::new(),
             last_instruction: EmittedInstruction {
                 opcode: opcode::Opcode::OpNull,
                 position: 0,
@@ -58,29 +394,86 @@ impl Compiler {
                 opcode: opcode::Opcode::OpNull,
                 position: 0,
             },
+            loops: Vec::new(),
         };
 
+        let mut symbol_table = SymbolTable::new();
+
+        for (index, builtin) in object::builtins::BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(index, builtin.name);
+        }
+
         Self {
             constants: Vec::new(),
-            symbol_table: SymbolTable::new(),
+            symbol_table,
+            constant_indexes: HashMap::new(),
             scopes: vec![main_scope],
             scope_index: 0,
+            optimize: false,
+            wrapping_mode: object::WrappingMode::default(),
+            keep_last_value: false,
+            warnings: Vec::new(),
+            span: Span::default(),
         }
     }
 
-    pub fn new_with_state(constants: Vec<Rc<object::Object>>, symbol_table: SymbolTable) -> Self {
+    pub fn new_with_state(
+        constants: Vec<Rc<object::Object>>,
+        mut symbol_table: SymbolTable,
+    ) -> Self {
         let compiler = Self::new();
 
+        for (index, builtin) in object::builtins::BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(index, builtin.name);
+        }
+
+        let mut constant_indexes = HashMap::new();
+
+        for (index, constant) in constants.iter().enumerate() {
+            if let Ok(key) = constant.hash_key() {
+                constant_indexes.entry(key).or_insert(index);
+            }
+        }
+
         Self {
             constants,
             symbol_table,
+            constant_indexes,
             ..compiler
         }
     }
 
+    /// Enables compile-time optimisations such as folding constant integer
+    /// arithmetic into a single constant.
+    pub fn with_optimizations(self) -> Self {
+        Self {
+            optimize: true,
+            ..self
+        }
+    }
+
+    /// Sets the overflow behaviour constant folding assumes. It should match
+    /// the VM's, so folded and unfolded arithmetic give the same result.
+    pub fn with_wrapping_mode(self, wrapping_mode: object::WrappingMode) -> Self {
+        Self {
+            wrapping_mode,
+            ..self
+        }
+    }
+
+    /// Leaves the value of a program's final expression statement on the
+    /// stack instead of popping it, so a REPL can read it with `Vm::stack_top`.
+    pub fn with_keep_last_value(self) -> Self {
+        Self {
+            keep_last_value: true,
+            ..self
+        }
+    }
+
     pub fn enter_scope(&mut self) {
         let scope = CompilationScope {
             instructions: opcode::Instructions::default(),
+            positions: Vec::new(),
             last_instruction: EmittedInstruction {
                 opcode: Opcode::OpNull,
                 position: 0,
@@ -89,6 +482,7 @@ impl Compiler {
                 opcode: Opcode::OpNull,
                 position: 0,
             },
+            loops: Vec::new(),
         };
 
         self.scopes.push(scope);
@@ -117,14 +511,156 @@ impl Compiler {
         self.scope_index
     }
 
-    fn add_constant(&mut self, obj: object::Object) -> usize {
+    pub fn warnings(&self) -> &Vec<CompileWarning> {
+        &self.warnings
+    }
+
+    /// Adds `obj` to the constant pool, reusing the index of an identical
+    /// literal already in the pool.
+    fn add_constant(&mut self, obj: object::Object) -> Result<usize, Error> {
+        let key = obj.hash_key().ok();
+
+        let existing = match &key {
+            Some(key) => self.constant_indexes.get(key).copied(),
+            None => self
+                .constants
+                .iter()
+                .position(|constant| same_constant(constant, &obj)),
+        };
+
+        if let Some(index) = existing {
+            return Ok(index);
+        }
+
+        if self.constants.len() >= operand_limit(Opcode::OpConst) {
+            return Err(Error::msg("compiler: constant pool overflow"));
+        }
+
         self.constants.push(obj.into());
 
-        (self.constants.len() - 1) as usize
+        let index = self.constants.len() - 1;
+
+        if let Some(key) = key {
+            self.constant_indexes.insert(key, index);
+        }
+
+        Ok(index)
+    }
+
+    /// Defines `name` in the current scope, failing without defining it if
+    /// its slot wouldn't fit in the operand of the instruction that stores it.
+    fn define_symbol(&mut self, name: &str) -> Result<Rc<Symbol>, Error> {
+        let redefined =
+            self.symbol_table.store.get(name).is_some_and(|symbol| {
+                matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local)
+            });
+
+        let (op, kind) = match self.symbol_table.outer {
+            None => (Opcode::OpSetGlobal, "global"),
+            Some(_) => (Opcode::OpSetLocal, "local"),
+        };
+
+        // Redefining a name reuses its slot, so only a new one can overflow.
+        if !redefined && self.symbol_table.num_definitions >= operand_limit(op) {
+            return Err(Error::msg(format!(
+                "compiler: too many {} variables to define '{}'",
+                kind, name
+            )));
+        }
+
+        Ok(self.symbol_table.define(name))
+    }
+
+    /// Compiles `assignment`, leaving nothing on the stack, and returns the
+    /// symbol it stored into.
+    fn compile_assignment(&mut self, assignment: &Assignment) -> Result<Rc<Symbol>, Error> {
+        // A function is defined before its body is compiled so it can call
+        // itself. Any other value is compiled first, so `$x = $x + 1` still
+        // needs an existing `$x`.
+        let symbol = if let Expression::Function(function_literal) = &assignment.value {
+            let symbol = self.define_symbol(&assignment.name.value)?;
+
+            // A local slot is only set once the closure exists, so the body
+            // refers to itself through the current closure instead of
+            // capturing the slot.
+            let name = match symbol.scope {
+                SymbolScope::Global => None,
+                _ => Some(assignment.name.value.as_str()),
+            };
+
+            self.compile_function(function_literal, name)?;
+
+            symbol
+        } else {
+            self.compile_expression(&assignment.value)?;
+
+            self.define_symbol(&assignment.name.value)?
+        };
+
+        self.emit(
+            if symbol.scope == SymbolScope::Global {
+                Opcode::OpSetGlobal
+            } else {
+                Opcode::OpSetLocal
+            },
+            vec![symbol.index],
+        );
+
+        Ok(symbol)
+    }
+
+    /// Compiles `function_literal` into a closure. `name` is what the body
+    /// calls itself by when it can't load its own variable.
+    fn compile_function(
+        &mut self,
+        function_literal: &FunctionLiteral,
+        name: Option<&str>,
+    ) -> Result<(), Error> {
+        self.enter_scope();
+
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+
+        for parameter in function_literal.parameters.iter() {
+            self.define_symbol(&parameter.value)?;
+        }
+
+        self.compile_block_statement(&function_literal.body)?;
+
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.replace_last_pop_with_return();
+        }
+
+        if !self.last_instruction_is(Opcode::OpReturnValue) {
+            self.emit(Opcode::OpReturn, vec![]);
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions;
+        let instructions = self.exit_scope();
+
+        // Push the captured values in the enclosing scope so OpClosure can
+        // collect them off the stack.
+        for symbol in free_symbols.iter() {
+            self.load_symbol(symbol)?;
+        }
+
+        let compiled_function = Rc::from(object::CompiledFunction::new(
+            instructions,
+            num_locals,
+            function_literal.parameters.len(),
+        ));
+
+        let constant = self.add_constant(object::Object::CompiledFunction(compiled_function))?;
+
+        self.emit(Opcode::OpClosure, vec![constant, free_symbols.len()]);
+
+        Ok(())
     }
 
     fn change_operand(&mut self, position: usize, operand: usize) {
-        let op = Opcode::from(self.current_instructions().0[position]);
+        let op = Opcode::from(self.current_instructions()[position]);
 
         let new_instruction = opcode::make(op, &vec![operand]);
 
@@ -132,13 +668,12 @@ impl Compiler {
     }
 
     pub fn add_instructions(&mut self, instructions: &Instructions) -> usize {
-        let position = self.current_instructions().0.len();
+        let position = self.current_instructions().len();
 
-        let new_instruction = self.scopes[self.scope_index]
+        self.scopes[self.scope_index]
             .instructions
-            .merge_instructions(instructions);
+            .extend_from(instructions.clone());
 
-        self.scopes[self.scope_index].instructions = new_instruction;
         position
     }
 
@@ -149,8 +684,8 @@ impl Compiler {
     fn replace_instruction(&mut self, position: usize, new_instruction: opcode::Instructions) {
         let instructions = &mut self.scopes[self.scope_index].instructions;
 
-        for (i, instruction) in new_instruction.0.iter().enumerate() {
-            instructions.0[position + i] = *instruction;
+        for i in 0..new_instruction.len() {
+            instructions[position + i] = new_instruction[i];
         }
     }
 
@@ -174,10 +709,30 @@ impl Compiler {
         self.scopes[self.scope_index].last_instruction.opcode = Opcode::OpReturnValue;
     }
 
+    /// Copies the compiled program out of the compiler, which stays usable
+    /// for further input. See `bytecode_view` and `into_bytecode` for ways
+    /// to avoid the copy.
     pub fn bytecode(&self) -> Bytecode {
+        self.bytecode_view().to_bytecode()
+    }
+
+    /// Borrows the compiled program without copying it.
+    pub fn bytecode_view(&self) -> BytecodeView<'_> {
+        BytecodeView {
+            instructions: self.current_instructions(),
+            constants: &self.constants,
+            positions: &self.scopes[self.scope_index].positions,
+        }
+    }
+
+    /// Moves the compiled program out of the compiler without copying it.
+    pub fn into_bytecode(mut self) -> Bytecode {
+        let scope = self.scopes.swap_remove(self.scope_index);
+
         Bytecode {
-            instructions: self.current_instructions().clone(),
-            constants: self.constants.clone(),
+            instructions: scope.instructions,
+            constants: self.constants,
+            positions: scope.positions,
         }
     }
 
@@ -186,17 +741,37 @@ impl Compiler {
 
         let index = self.add_instructions(&instructions);
 
+        self.scopes[self.scope_index]
+            .positions
+            .push((index, self.span));
+
         _ = self.set_last_instruction(op, index);
 
         index
     }
 
+    /// Compiles `node` and returns a copy of the program compiled so far.
     pub fn compile(&mut self, node: &Node) -> Result<Bytecode, Error> {
+        self.compile_in_place(node)?;
+
+        Ok(self.bytecode())
+    }
+
+    /// Compiles `node` like `compile`, but borrows the result instead of
+    /// copying it.
+    pub fn compile_in_place(&mut self, node: &Node) -> Result<BytecodeView<'_>, Error> {
         match node {
             Node::Program(p) => {
                 for statement in &p.statements {
                     self.compile_statement(statement)?;
                 }
+
+                if self.keep_last_value
+                    && matches!(p.statements.last(), Some(Statement::Expr(_)))
+                    && self.last_instruction_is(Opcode::OpPop)
+                {
+                    self.remove_last_pop();
+                }
             }
             Node::Statement(s) => {
                 self.compile_statement(s)?;
@@ -206,32 +781,42 @@ impl Compiler {
             }
         }
 
-        Ok(self.bytecode())
+        Ok(self.bytecode_view())
     }
 
     fn compile_block_statement(&mut self, block: &BlockStatement) -> Result<(), Error> {
+        let mut returned = false;
+
         for statement in block.statements.iter() {
+            // Nothing after a return in the same block can run, so skip it.
+            if returned {
+                self.warnings.push(CompileWarning {
+                    message: format!("unreachable code after return: {}", statement),
+                });
+
+                continue;
+            }
+
             self.compile_statement(statement)?;
+
+            returned = matches!(statement, Statement::Return(_));
         }
 
         Ok(())
     }
 
     fn compile_statement(&mut self, s: &Statement) -> Result<(), Error> {
-        match s {
-            Statement::Assign(assignment) => {
-                self.compile_expression(&assignment.value)?;
+        let previous = std::mem::replace(&mut self.span, s.span());
+        let result = self.compile_statement_node(s);
+        self.span = previous;
 
-                let symbol = self.symbol_table.define(&assignment.name.value);
+        result
+    }
 
-                self.emit(
-                    if symbol.scope == SymbolScope::Global {
-                        Opcode::OpSetGlobal
-                    } else {
-                        Opcode::OpSetLocal
-                    },
-                    vec![symbol.index],
-                );
+    fn compile_statement_node(&mut self, s: &Statement) -> Result<(), Error> {
+        match s {
+            Statement::Assign(assignment) => {
+                self.compile_assignment(assignment)?;
 
                 Ok(())
             }
@@ -249,26 +834,64 @@ impl Compiler {
 
                 Ok(())
             }
-            _ => Err(Error::msg("compile_statement: unimplemented")),
+            Statement::IndexAssign(_) => Err(Error::msg(format!(
+                "compiler: unsupported statement: {}",
+                s
+            ))),
+            Statement::Break(_) | Statement::Continue(_) => {
+                if self.scopes[self.scope_index].loops.is_empty() {
+                    return Err(Error::msg(format!("compiler: '{}' outside of a loop", s)));
+                }
+
+                // dummy value that will be overwritten later
+                let position = self.emit(Opcode::OpJump, vec![9999]);
+                let jumps = self.scopes[self.scope_index].loops.last_mut().unwrap();
+
+                if matches!(s, Statement::Break(_)) {
+                    jumps.breaks.push(position);
+                } else {
+                    jumps.continues.push(position);
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn enter_loop(&mut self) {
+        self.scopes[self.scope_index]
+            .loops
+            .push(LoopJumps::default());
+    }
+
+    /// Points the pending `continue` jumps of the innermost loop at
+    /// `continue_target` and its `break` jumps at `break_target`.
+    fn leave_loop(&mut self, continue_target: usize, break_target: usize) {
+        let jumps = self.scopes[self.scope_index].loops.pop().unwrap();
+
+        for position in jumps.continues {
+            self.change_operand(position, continue_target);
+        }
+
+        for position in jumps.breaks {
+            self.change_operand(position, break_target);
         }
     }
 
     fn compile_operands(
         &mut self,
-        left: &Box<Expression>,
-        right: &Box<Expression>,
-        operator: Token,
+        left: &Expression,
+        right: &Expression,
+        swap_operands: bool,
     ) -> Result<(), Error> {
-        match operator.token_type {
-            TokenType::Lt => {
-                self.compile_expression(right)?;
-                self.compile_expression(left)?;
-            }
-            _ => {
-                self.compile_expression(left)?;
-                self.compile_expression(right)?;
-            }
+        if swap_operands {
+            self.compile_expression(right)?;
+            self.compile_expression(left)?;
+        } else {
+            self.compile_expression(left)?;
+            self.compile_expression(right)?;
         }
+
         Ok(())
     }
 
@@ -278,55 +901,17 @@ impl Compiler {
                 let symbol = self.symbol_table.resolve(&identifier.value);
 
                 match symbol {
-                    Some(symbol) => {
-                        self.emit(
-                            if symbol.scope == SymbolScope::Global {
-                                Opcode::OpGetGlobal
-                            } else {
-                                Opcode::OpGetLocal
-                            },
-                            vec![symbol.index],
-                        );
-                    }
-                    None => {
-                        return Err(Error::msg(format!(
-                            "undefined variable: {}",
-                            identifier.value
-                        )));
-                    }
+                    Some(symbol) => self.load_symbol(&symbol),
+                    None => Err(undefined_variable(identifier)),
                 }
-
-                Ok(())
             }
-            Expression::Function(function_literal) => {
-                self.enter_scope();
-                
-                for parameter in function_literal.parameters.iter() {
-                    self.symbol_table.define(&parameter.value);
-                }
-
-                self.compile_block_statement(&function_literal.body)?;
-
-                if self.last_instruction_is(Opcode::OpPop) {
-                    self.replace_last_pop_with_return();
-                }
-
-                if !self.last_instruction_is(Opcode::OpReturnValue) {
-                    self.emit(Opcode::OpReturn, vec![]);
-                }
-
-                let num_locals = self.symbol_table.num_definitions;
-                let instructions = self.exit_scope();
-
-                let compiled_function = Rc::from(object::CompiledFunction::new(instructions, num_locals));
-
-                let operands =
-                    vec![self.add_constant(object::Object::CompiledFunction(compiled_function))];
-
-                self.emit(Opcode::OpConst, operands);
+            Expression::Assign(assignment) => {
+                // The store pops the value, so load it back as the result.
+                let symbol = self.compile_assignment(assignment)?;
 
-                Ok(())
+                self.load_symbol(&symbol)
             }
+            Expression::Function(function_literal) => self.compile_function(function_literal, None),
             Expression::Call(call_expression) => {
                 self.compile_expression(&call_expression.function)?;
 
@@ -345,27 +930,105 @@ impl Compiler {
                 let jnt_position = self.emit(Opcode::OpJumpNotTruthy, vec![9999]);
 
                 self.compile_block_statement(&if_expression.consequence)?;
-
-                if self.last_instruction_is(Opcode::OpPop) {
-                    self.remove_last_pop();
-                }
+                self.leave_block_value();
 
                 let j_position = self.emit(Opcode::OpJump, vec![9999]);
-                let after_consequence_position = self.current_instructions().0.len();
+                let after_consequence_position = self.current_instructions().len();
                 self.change_operand(jnt_position, after_consequence_position);
 
                 if if_expression.alternative.is_none() {
                     self.emit(opcode::Opcode::OpNull, vec![]);
                 } else {
                     self.compile_block_statement(if_expression.alternative.as_ref().unwrap())?;
+                    self.leave_block_value();
+                }
+
+                let after_alternative_position = self.current_instructions().len();
+                self.change_operand(j_position, after_alternative_position);
+
+                Ok(())
+            }
+            Expression::Ternary(ternary_expression) => {
+                self.compile_expression(&ternary_expression.condition)?;
+
+                // dummy value that will be overwritten later
+                let jnt_position = self.emit(Opcode::OpJumpNotTruthy, vec![9999]);
+
+                self.compile_expression(&ternary_expression.consequence)?;
+
+                let j_position = self.emit(Opcode::OpJump, vec![9999]);
+                let after_consequence_position = self.current_instructions().len();
+                self.change_operand(jnt_position, after_consequence_position);
+
+                self.compile_expression(&ternary_expression.alternative)?;
 
-                    if self.last_instruction_is(Opcode::OpPop) {
-                        self.remove_last_pop();
+                let after_alternative_position = self.current_instructions().len();
+                self.change_operand(j_position, after_alternative_position);
+
+                Ok(())
+            }
+            Expression::While(while_expression) => {
+                let loop_start = self.current_instructions().len();
+
+                self.compile_expression(&while_expression.condition)?;
+
+                // dummy value that will be overwritten later
+                let exit_position = self.emit(Opcode::OpJumpNotTruthy, vec![9999]);
+
+                self.enter_loop();
+                self.compile_block_statement(&while_expression.body)?;
+
+                self.emit(Opcode::OpJump, vec![loop_start]);
+
+                let after_body_position = self.current_instructions().len();
+                self.change_operand(exit_position, after_body_position);
+                self.leave_loop(loop_start, after_body_position);
+
+                // A loop has no value, but it's still an expression whose
+                // result gets popped.
+                self.emit(Opcode::OpNull, vec![]);
+
+                Ok(())
+            }
+            Expression::For(for_expression) => {
+                // Lowered the same way as a while loop, with the initialiser
+                // run once up front and the update run at the end of the body.
+                if let Some(init) = &for_expression.init {
+                    self.compile_statement(init)?;
+                }
+
+                let loop_start = self.current_instructions().len();
+
+                let exit_position = match &for_expression.condition {
+                    Some(condition) => {
+                        self.compile_expression(condition)?;
+
+                        // dummy value that will be overwritten later
+                        Some(self.emit(Opcode::OpJumpNotTruthy, vec![9999]))
                     }
+                    None => None,
+                };
+
+                self.enter_loop();
+                self.compile_block_statement(&for_expression.body)?;
+
+                let update_position = self.current_instructions().len();
+
+                if let Some(update) = &for_expression.update {
+                    self.compile_statement(update)?;
                 }
 
-                let after_alternative_position = self.current_instructions().0.len();
-                self.change_operand(j_position, after_alternative_position);
+                self.emit(Opcode::OpJump, vec![loop_start]);
+
+                let after_body_position = self.current_instructions().len();
+
+                if let Some(exit_position) = exit_position {
+                    self.change_operand(exit_position, after_body_position);
+                }
+
+                self.leave_loop(update_position, after_body_position);
+
+                self.emit(Opcode::OpNull, vec![]);
 
                 Ok(())
             }
@@ -377,29 +1040,64 @@ impl Compiler {
 
                 Ok(())
             }
+            Expression::Range(range_expression) => {
+                self.compile_expression(&range_expression.start)?;
+                self.compile_expression(&range_expression.end)?;
+
+                self.emit(Opcode::OpRange, vec![range_expression.inclusive as usize]);
+
+                Ok(())
+            }
+            Expression::Slice(slice_expression) => {
+                self.compile_expression(&slice_expression.left)?;
+
+                // A missing bound is passed as null.
+                for bound in [&slice_expression.start, &slice_expression.end] {
+                    match bound {
+                        Some(bound) => self.compile_expression(bound)?,
+                        None => {
+                            self.emit(Opcode::OpNull, vec![]);
+                        }
+                    }
+                }
+
+                self.emit(Opcode::OpSlice, vec![]);
+
+                Ok(())
+            }
+            Expression::Infix(infix_expression)
+                if matches!(
+                    infix_expression.operator.token_type,
+                    TokenType::And | TokenType::Or
+                ) =>
+            {
+                self.compile_logical_expression(infix_expression)
+            }
             Expression::Infix(infix_expression) => {
+                if self.optimize {
+                    if let Some(value) = fold_integer_expression(e, self.wrapping_mode) {
+                        let constant = self.add_constant(object::Object::Integer(value))?;
+
+                        self.emit(opcode::Opcode::OpConst, vec![constant]);
+
+                        return Ok(());
+                    }
+                }
+
+                let (swap_operands, op) = infix_opcode(&infix_expression.operator.token_type)
+                    .ok_or_else(|| {
+                        Error::msg(format!(
+                            "compiler: unsupported infix operator '{}' in {}",
+                            infix_expression.operator, e
+                        ))
+                    })?;
+
                 self.compile_operands(
                     &infix_expression.left,
                     &infix_expression.right,
-                    infix_expression.operator.clone(),
+                    swap_operands,
                 )?;
-
-                match infix_expression.operator.token_type {
-                    TokenType::Plus => self.emit(opcode::Opcode::OpAdd, vec![]),
-                    TokenType::Minus => self.emit(opcode::Opcode::OpSub, vec![]),
-                    TokenType::Asterisk => self.emit(opcode::Opcode::OpMul, vec![]),
-                    TokenType::Slash => self.emit(opcode::Opcode::OpDiv, vec![]),
-                    TokenType::Gt | TokenType::Lt => {
-                        self.emit(opcode::Opcode::OpGreaterThan, vec![])
-                    }
-                    TokenType::Eq => self.emit(opcode::Opcode::OpEqual, vec![]),
-                    TokenType::NotEq => self.emit(opcode::Opcode::OpNotEqual, vec![]),
-                    _ => {
-                        return Err(Error::msg(
-                            "compile_expression: unimplemented infix operator",
-                        ))
-                    }
-                };
+                self.emit(op, vec![]);
 
                 Ok(())
             }
@@ -409,15 +1107,56 @@ impl Compiler {
                 match prefix_expression.operator.token_type {
                     TokenType::Bang => self.emit(opcode::Opcode::OpBang, vec![]),
                     TokenType::Minus => self.emit(opcode::Opcode::OpMinus, vec![]),
+                    TokenType::Tilde => self.emit(opcode::Opcode::OpBitNot, vec![]),
                     _ => {
-                        return Err(Error::msg(
-                            "compile_expression: unimplemented prefix operator",
-                        ))
+                        return Err(Error::msg(format!(
+                            "compiler: unsupported prefix operator '{}' in {}",
+                            prefix_expression.operator, e
+                        )))
                     }
                 };
 
                 Ok(())
             }
+            Expression::Postfix(postfix_expression) => {
+                let invalid_operand = || {
+                    Error::msg(format!(
+                        "compiler: invalid operand for '{}' in {}",
+                        postfix_expression.operator, e
+                    ))
+                };
+
+                // Only variables the current scope can write back to work.
+                let symbol = match postfix_expression.left.as_ref() {
+                    Expression::Identifier(identifier) => self
+                        .symbol_table
+                        .resolve(&identifier.value)
+                        .ok_or_else(|| undefined_variable(identifier))?,
+                    _ => return Err(invalid_operand()),
+                };
+
+                let set = match symbol.scope {
+                    SymbolScope::Global => Opcode::OpSetGlobal,
+                    SymbolScope::Local => Opcode::OpSetLocal,
+                    _ => return Err(invalid_operand()),
+                };
+
+                // The expression's value is the variable before the update.
+                self.load_symbol(&symbol)?;
+                self.load_symbol(&symbol)?;
+
+                let one = self.add_constant(object::Object::Integer(1))?;
+                self.emit(Opcode::OpConst, vec![one]);
+
+                match postfix_expression.operator.token_type {
+                    TokenType::Increment => self.emit(Opcode::OpAdd, vec![]),
+                    _ => self.emit(Opcode::OpSub, vec![]),
+                };
+
+                self.emit(set, vec![symbol.index]);
+
+                Ok(())
+            }
             Expression::Literal(literal_expression) => match literal_expression {
                 Literal::Array(array) => {
                     for element in array.elements.iter() {
@@ -428,6 +1167,29 @@ impl Compiler {
 
                     Ok(())
                 }
+                Literal::Tuple(tuple) => {
+                    for element in tuple.elements.iter() {
+                        self.compile_expression(element)?;
+                    }
+
+                    self.emit(opcode::Opcode::OpTuple, vec![tuple.elements.len()]);
+
+                    Ok(())
+                }
+                Literal::Hash(hash) => {
+                    // Sort the pairs so the same literal always compiles to the same bytecode.
+                    let mut pairs = hash.pairs.iter().collect::<Vec<_>>();
+                    pairs.sort_by_key(|(key, _)| key.to_string());
+
+                    for (key, value) in pairs {
+                        self.compile_expression(key)?;
+                        self.compile_expression(value)?;
+                    }
+
+                    self.emit(opcode::Opcode::OpHash, vec![hash.pairs.len() * 2]);
+
+                    Ok(())
+                }
                 Literal::Boolean(boolean) => match boolean {
                     BooleanLiteral { value: true, .. } => {
                         self.emit(opcode::Opcode::OpTrue, vec![]);
@@ -440,10 +1202,24 @@ impl Compiler {
                         Ok(())
                     }
                 },
+                Literal::Null(_) => {
+                    self.emit(opcode::Opcode::OpNull, vec![]);
+
+                    Ok(())
+                }
                 Literal::Integer(IntegerLiteral { value, .. }) => {
                     let integer = object::Object::Integer(*value);
 
-                    let constant = self.add_constant(integer);
+                    let constant = self.add_constant(integer)?;
+
+                    self.emit(opcode::Opcode::OpConst, vec![constant]);
+
+                    Ok(())
+                }
+                Literal::Float(FloatLiteral { value, .. }) => {
+                    let float = object::Object::Float(*value);
+
+                    let constant = self.add_constant(float)?;
 
                     self.emit(opcode::Opcode::OpConst, vec![constant]);
 
@@ -452,44 +1228,358 @@ impl Compiler {
                 Literal::String(StringLiteral { value, .. }) => {
                     let string = object::Object::String(value.clone());
 
-                    let constant = self.add_constant(string);
+                    let constant = self.add_constant(string)?;
 
                     self.emit(opcode::Opcode::OpConst, vec![constant]);
 
                     Ok(())
                 }
-                _ => {
-                    return Err(Error::msg("compile_expression: unimplemented"));
+                Literal::Char(CharLiteral { value, .. }) => {
+                    let ch = object::Object::Char(*value);
+
+                    let constant = self.add_constant(ch)?;
+
+                    self.emit(opcode::Opcode::OpConst, vec![constant]);
+
+                    Ok(())
                 }
             },
-            _ => {
-                return Err(Error::msg("compile_expression: unimplemented"));
-            }
         }
     }
 
+    /// Compiles `&&` and `||` so the right-hand side only runs when it decides
+    /// the result. Both operators leave a boolean on the stack.
+    ///
+    /// `a && b` becomes:
+    ///
+    /// ```text
+    /// a; OpJumpNotTruthy false; b; OpJumpNotTruthy false; OpTrue; OpJump end
+    /// false: OpFalse
+    /// end:
+    /// ```
+    ///
+    /// and `a || b` is the mirror image using `OpJumpTruthy`.
+    fn compile_logical_expression(&mut self, infix: &InfixExpression) -> Result<(), Error> {
+        let (jump, on_jump, fall_through) = match infix.operator.token_type {
+            TokenType::And => (Opcode::OpJumpNotTruthy, Opcode::OpFalse, Opcode::OpTrue),
+            _ => (Opcode::OpJumpTruthy, Opcode::OpTrue, Opcode::OpFalse),
+        };
+
+        self.compile_expression(&infix.left)?;
+        let left_jump = self.emit(jump, vec![9999]);
+
+        self.compile_expression(&infix.right)?;
+        let right_jump = self.emit(jump, vec![9999]);
+
+        self.emit(fall_through, vec![]);
+        let end_jump = self.emit(Opcode::OpJump, vec![9999]);
+
+        let short_circuit_position = self.current_instructions().len();
+        self.change_operand(left_jump, short_circuit_position);
+        self.change_operand(right_jump, short_circuit_position);
+
+        self.emit(on_jump, vec![]);
+
+        let end_position = self.current_instructions().len();
+        self.change_operand(end_jump, end_position);
+
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<(), Error> {
+        let op = match symbol.scope {
+            SymbolScope::Global => Opcode::OpGetGlobal,
+            SymbolScope::Local => Opcode::OpGetLocal,
+            SymbolScope::Free => Opcode::OpGetFree,
+            SymbolScope::Builtin => Opcode::OpGetBuiltin,
+            SymbolScope::Function => {
+                self.emit(Opcode::OpCurrentClosure, vec![]);
+
+                return Ok(());
+            }
+        };
+
+        self.emit(op, vec![symbol.index]);
+
+        Ok(())
+    }
+
     fn last_instruction_is(&self, op: Opcode) -> bool {
-        if self.current_instructions().0.is_empty() {
+        if self.current_instructions().is_empty() {
             return false;
         }
 
         &self.scopes[self.scope_index].last_instruction.opcode == &op
     }
 
+    /// Makes the block just compiled leave its value on the stack: the value
+    /// of its final expression statement, or null when it's empty or ends in
+    /// some other statement.
+    fn leave_block_value(&mut self) {
+        if self.last_instruction_is(Opcode::OpPop) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::OpNull, vec![]);
+        }
+    }
+
     fn remove_last_pop(&mut self) {
         let last = self.scopes[self.scope_index].last_instruction.clone();
         let previous = self.scopes[self.scope_index].previous_instruction.clone();
 
-        let old_instructions = self.current_instructions().0.clone();
-        let new_instructions = old_instructions[..last.position].to_vec();
+        let new_instructions = Instructions(self.current_instructions()[..last.position].to_vec());
 
-        self.scopes[self.scope_index].instructions.0 = new_instructions;
+        self.scopes[self.scope_index].instructions = new_instructions;
+        self.scopes[self.scope_index]
+            .positions
+            .retain(|(position, _)| *position < last.position);
         self.scopes[self.scope_index].last_instruction = previous;
     }
 }
 
+/// How many distinct values fit in the first operand of `op`.
+fn operand_limit(op: Opcode) -> usize {
+    1 << (8 * opcode::lookup(op).operand_widths[0])
+}
+
+fn undefined_variable(identifier: &Identifier) -> Error {
+    Error::msg(format!(
+        "compiler: undefined variable '{}' at {}:{}",
+        identifier.value, identifier.token.line, identifier.token.column
+    ))
+}
+
+fn is_jump(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::OpJump | Opcode::OpJumpNotTruthy | Opcode::OpJumpTruthy
+    )
+}
+
+/// The opcode an infix operator compiles to, and whether its operands are
+/// pushed in reverse. The VM only has greater-than comparisons, so `a < b`
+/// and `a <= b` are compiled as `b > a` and `b >= a`. Returns `None` for
+/// operators without an opcode, including the short-circuiting `&&` and
+/// `||`.
+pub fn infix_opcode(operator: &TokenType) -> Option<(bool, Opcode)> {
+    let operation = match operator {
+        TokenType::Plus => (false, Opcode::OpAdd),
+        TokenType::Minus => (false, Opcode::OpSub),
+        TokenType::Asterisk => (false, Opcode::OpMul),
+        TokenType::Slash => (false, Opcode::OpDiv),
+        TokenType::Percent => (false, Opcode::OpMod),
+        TokenType::Gt => (false, Opcode::OpGreaterThan),
+        TokenType::GtEq => (false, Opcode::OpGreaterThanOrEqual),
+        TokenType::Lt => (true, Opcode::OpGreaterThan),
+        TokenType::LtEq => (true, Opcode::OpGreaterThanOrEqual),
+        TokenType::Eq => (false, Opcode::OpEqual),
+        TokenType::NotEq => (false, Opcode::OpNotEqual),
+        TokenType::Ampersand => (false, Opcode::OpBitAnd),
+        TokenType::Pipe => (false, Opcode::OpBitOr),
+        TokenType::Caret => (false, Opcode::OpBitXor),
+        TokenType::ShiftLeft => (false, Opcode::OpShiftLeft),
+        TokenType::ShiftRight => (false, Opcode::OpShiftRight),
+        _ => return None,
+    };
+
+    Some(operation)
+}
+
+/// How `Bytecode::link` rewrites one module's operands.
+#[derive(Clone, Copy)]
+struct Relocation<'a> {
+    /// Added to every jump target.
+    jump_offset: usize,
+    /// The linked constant index for each of the module's constants.
+    constants: &'a [usize],
+    /// Added to every global slot.
+    global_offset: usize,
+}
+
+/// Rewrites the jump, constant and global operands in `instructions`,
+/// failing if a rebased operand no longer fits its width.
+fn relocate(instructions: &Instructions, relocation: &Relocation) -> Result<Instructions, Error> {
+    let mut relocated = Instructions::default();
+    let mut i = 0;
+
+    while i < instructions.len() {
+        let op = Opcode::from(instructions[i]);
+        let definition = opcode::lookup(op);
+        let (mut operands, read) = opcode::read_operands(definition, &instructions[i + 1..]);
+
+        match op {
+            op if is_jump(op) => operands[0] += relocation.jump_offset,
+            Opcode::OpConst | Opcode::OpClosure => {
+                operands[0] = *relocation.constants.get(operands[0]).ok_or_else(|| {
+                    Error::msg(format!("link: constant {} does not exist", operands[0]))
+                })?;
+            }
+            Opcode::OpGetGlobal | Opcode::OpSetGlobal => operands[0] += relocation.global_offset,
+            _ => {}
+        }
+
+        for (operand, width) in operands.iter().zip(definition.operand_widths.iter()) {
+            if *operand >= 1 << (width * 8) {
+                return Err(Error::msg(format!(
+                    "link: operand {} of {} does not fit in {} bytes",
+                    operand, op, width
+                )));
+            }
+        }
+
+        relocated.extend_from(opcode::make(op, &operands));
+
+        i += 1 + read;
+    }
+
+    Ok(relocated)
+}
+
+/// The number of global slots `bytecode` uses, one past the highest slot
+/// read or written by its main instructions or any of its functions.
+fn global_count(bytecode: &Bytecode) -> usize {
+    let functions = bytecode
+        .constants
+        .iter()
+        .filter_map(|constant| match constant.as_ref() {
+            object::Object::CompiledFunction(function) => Some(&function.instructions),
+            _ => None,
+        });
+
+    let mut count = 0;
+
+    for instructions in std::iter::once(&bytecode.instructions).chain(functions) {
+        let mut i = 0;
+
+        while i < instructions.len() {
+            let op = Opcode::from(instructions[i]);
+            let (operands, read) =
+                opcode::read_operands(opcode::lookup(op), &instructions[i + 1..]);
+
+            if matches!(op, Opcode::OpGetGlobal | Opcode::OpSetGlobal) {
+                count = count.max(operands[0] + 1);
+            }
+
+            i += 1 + read;
+        }
+    }
+
+    count
+}
+
+/// Drops jumps to the next instruction, relocating the remaining jump
+/// targets and the offsets in `positions` to match the new layout.
+fn remove_redundant_jumps(
+    instructions: &Instructions,
+    positions: &mut Vec<(usize, Span)>,
+) -> Instructions {
+    let mut instructions = instructions.clone();
+
+    loop {
+        let mut removed = Vec::new();
+        let mut i = 0;
+
+        while i < instructions.len() {
+            let op = Opcode::from(instructions[i]);
+            let (operands, read) =
+                opcode::read_operands(opcode::lookup(op), &instructions[i + 1..]);
+
+            if op == Opcode::OpJump && operands[0] == i + 1 + read {
+                removed.push((i, 1 + read));
+            }
+
+            i += 1 + read;
+        }
+
+        if removed.is_empty() {
+            return instructions;
+        }
+
+        // Every removed byte before an old offset shifts it down by one.
+        let relocate = |target: usize| -> usize {
+            target
+                - removed
+                    .iter()
+                    .filter(|(position, _)| *position < target)
+                    .map(|(_, width)| width)
+                    .sum::<usize>()
+        };
+
+        let mut optimized = Instructions::default();
+        let mut i = 0;
+
+        while i < instructions.len() {
+            let op = Opcode::from(instructions[i]);
+            let (operands, read) =
+                opcode::read_operands(opcode::lookup(op), &instructions[i + 1..]);
+
+            if !removed.iter().any(|(position, _)| *position == i) {
+                let instruction = if is_jump(op) {
+                    opcode::make(op, &vec![relocate(operands[0])])
+                } else {
+                    Instructions(instructions[i..i + 1 + read].to_vec())
+                };
+
+                optimized = optimized.merge_instructions(&instruction);
+            }
+
+            i += 1 + read;
+        }
+
+        positions.retain(|(offset, _)| !removed.iter().any(|(position, _)| position == offset));
+
+        for (offset, _) in positions.iter_mut() {
+            *offset = relocate(*offset);
+        }
+
+        instructions = optimized;
+    }
+}
+
+/// Evaluates an expression made up only of integer literals and `+ - * /`.
+///
+/// Returns `None` when the expression can't be folded, including on checked
+/// overflow and division by zero, so those cases are left for the VM to
+/// report.
+fn fold_integer_expression(
+    expression: &Expression,
+    wrapping_mode: object::WrappingMode,
+) -> Option<i64> {
+    match expression {
+        Expression::Literal(Literal::Integer(IntegerLiteral { value, .. })) => Some(*value),
+        Expression::Infix(infix_expression) => {
+            let left = fold_integer_expression(&infix_expression.left, wrapping_mode)?;
+            let right = fold_integer_expression(&infix_expression.right, wrapping_mode)?;
+
+            let op = match infix_expression.operator.token_type {
+                TokenType::Plus => object::ArithOp::Add,
+                TokenType::Minus => object::ArithOp::Sub,
+                TokenType::Asterisk => object::ArithOp::Mul,
+                TokenType::Slash => object::ArithOp::Div,
+                _ => return None,
+            };
+
+            wrapping_mode.apply(op, left, right).ok()
+        }
+        _ => None,
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct EmittedInstruction {
     pub opcode: opcode::Opcode,
     pub position: usize,
 }
+
+/// Whether two constants that have no `HashKey` are interchangeable. Only
+/// literal values are shared; floats compare by bit pattern so `0.0` and
+/// `-0.0` stay distinct.
+fn same_constant(a: &object::Object, b: &object::Object) -> bool {
+    use object::Object;
+
+    match (a, b) {
+        (Object::Float(a), Object::Float(b)) => a.to_bits() == b.to_bits(),
+        (Object::Char(a), Object::Char(b)) => a == b,
+        _ => false,
+    }
+}
@@ -0,0 +1,306 @@
+use std::{io::Read, rc::Rc};
+
+use anyhow::Error;
+use byteorder::{BigEndian, ReadBytesExt};
+use object::{builtins, CompiledFunction, Object};
+use opcode::{Instructions, Opcode};
+use parser::ast::{Position, Span};
+
+use crate::Bytecode;
+
+pub const MAGIC: &[u8; 4] = b"PINE";
+pub const VERSION: u8 = 2;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_BOOLEAN: u8 = 1;
+const TAG_STRING: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_FUNCTION: u8 = 4;
+const TAG_CHAR: u8 = 5;
+
+/// Binary layout (all integers big-endian):
+///
+/// ```text
+/// "PINE" version:u8
+/// instructions_len:u32 instructions
+/// constants_len:u32 (tag:u8 payload)*
+/// positions_len:u32 (offset:u32 start_line:u32 start_column:u32 end_line:u32 end_column:u32)*
+/// ```
+impl Bytecode {
+    /// Encodes the bytecode in the binary format described above. Fails if
+    /// a constant isn't one the compiler emits, such as a closure or a hash,
+    /// since those only exist at runtime.
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+
+        write_instructions(&mut bytes, &self.instructions);
+
+        bytes.extend_from_slice(&(self.constants.len() as u32).to_be_bytes());
+
+        for constant in self.constants.iter() {
+            write_constant(&mut bytes, constant)?;
+        }
+
+        bytes.extend_from_slice(&(self.positions.len() as u32).to_be_bytes());
+
+        for (offset, span) in self.positions.iter() {
+            bytes.extend_from_slice(&(*offset as u32).to_be_bytes());
+
+            for position in [span.start, span.end] {
+                bytes.extend_from_slice(&(position.line as u32).to_be_bytes());
+                bytes.extend_from_slice(&(position.column as u32).to_be_bytes());
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Bytecode, Error> {
+        let mut reader = bytes;
+
+        let mut magic = [0; 4];
+        read_exact(&mut reader, &mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(Error::msg("not a pine bytecode file: bad magic number"));
+        }
+
+        let version = reader.read_u8().map_err(|_| unexpected_end())?;
+
+        if version != VERSION {
+            return Err(Error::msg(format!(
+                "unsupported bytecode version: {}",
+                version
+            )));
+        }
+
+        let instructions = read_instructions(&mut reader)?;
+
+        let num_constants = read_u32(&mut reader)?;
+        let mut constants = Vec::new();
+
+        for _ in 0..num_constants {
+            constants.push(Rc::new(read_constant(&mut reader)?));
+        }
+
+        check_operands(&instructions, constants.len())?;
+
+        for constant in constants.iter() {
+            if let Object::CompiledFunction(function) = &**constant {
+                check_operands(&function.instructions, constants.len())?;
+            }
+        }
+
+        let num_positions = read_u32(&mut reader)?;
+        let mut positions = Vec::new();
+
+        for _ in 0..num_positions {
+            let offset = read_u32(&mut reader)? as usize;
+            let start = read_position(&mut reader)?;
+            let end = read_position(&mut reader)?;
+
+            positions.push((offset, Span { start, end }));
+        }
+
+        if !reader.is_empty() {
+            return Err(Error::msg(format!(
+                "{} unexpected trailing bytes in bytecode",
+                reader.len()
+            )));
+        }
+
+        Ok(Bytecode {
+            instructions,
+            constants,
+            positions,
+        })
+    }
+}
+
+fn write_instructions(bytes: &mut Vec<u8>, instructions: &Instructions) {
+    bytes.extend_from_slice(&(instructions.0.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&instructions.0);
+}
+
+fn write_constant(bytes: &mut Vec<u8>, constant: &Object) -> Result<(), Error> {
+    match constant {
+        Object::Integer(integer) => {
+            bytes.push(TAG_INTEGER);
+            bytes.extend_from_slice(&integer.to_be_bytes());
+        }
+        Object::Boolean(boolean) => {
+            bytes.push(TAG_BOOLEAN);
+            bytes.push(*boolean as u8);
+        }
+        Object::String(string) => {
+            bytes.push(TAG_STRING);
+            bytes.extend_from_slice(&(string.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(string.as_bytes());
+        }
+        Object::Float(float) => {
+            bytes.push(TAG_FLOAT);
+            bytes.extend_from_slice(&float.to_be_bytes());
+        }
+        Object::Char(ch) => {
+            bytes.push(TAG_CHAR);
+            bytes.extend_from_slice(&(*ch as u32).to_be_bytes());
+        }
+        Object::CompiledFunction(function) => {
+            bytes.push(TAG_FUNCTION);
+            write_instructions(bytes, &function.instructions);
+            bytes.extend_from_slice(&(function.num_locals as u32).to_be_bytes());
+            bytes.extend_from_slice(&(function.num_parameters as u32).to_be_bytes());
+        }
+        _ => {
+            return Err(Error::msg(format!(
+                "cannot serialize constant: {}",
+                constant
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a length-prefixed instruction stream, checking that every opcode is
+/// known and has all of its operands.
+fn read_instructions(reader: &mut &[u8]) -> Result<Instructions, Error> {
+    let length = read_u32(reader)? as usize;
+
+    let instructions = read_bytes(reader, length)?;
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let byte = instructions[offset];
+
+        let op = Opcode::from_byte(byte)
+            .ok_or_else(|| Error::msg(format!("unknown opcode {} at offset {}", byte, offset)))?;
+
+        let width: usize = opcode::lookup(op).operand_widths.iter().sum();
+
+        if offset + 1 + width > instructions.len() {
+            return Err(Error::msg(format!(
+                "truncated operands for {} at offset {}",
+                op, offset
+            )));
+        }
+
+        offset += 1 + width;
+    }
+
+    Ok(Instructions(instructions))
+}
+
+/// Checks that the operands of `instructions`, which `read_instructions` has
+/// already walked, point at constants in a pool of `num_constants`, at
+/// builtins that exist and at jump targets inside the instructions.
+fn check_operands(instructions: &Instructions, num_constants: usize) -> Result<(), Error> {
+    let mut offset = 0;
+
+    while offset < instructions.len() {
+        let op = Opcode::from(instructions[offset]);
+        let (operands, read) =
+            opcode::read_operands(opcode::lookup(op), &instructions.0[offset + 1..]);
+
+        let (kind, limit) = match op {
+            Opcode::OpConst | Opcode::OpClosure => ("constant index", num_constants),
+            Opcode::OpGetBuiltin => ("builtin index", builtins::BUILTINS.len()),
+            // Jumping to the very end finishes the instructions.
+            Opcode::OpJump | Opcode::OpJumpNotTruthy | Opcode::OpJumpTruthy => {
+                ("jump target", instructions.len() + 1)
+            }
+            _ => ("", usize::MAX),
+        };
+
+        if operands.first().is_some_and(|operand| *operand >= limit) {
+            return Err(Error::msg(format!(
+                "{} {} out of range for {} at offset {}",
+                kind, operands[0], op, offset
+            )));
+        }
+
+        offset += 1 + read;
+    }
+
+    Ok(())
+}
+
+fn read_constant(reader: &mut &[u8]) -> Result<Object, Error> {
+    let tag = reader.read_u8().map_err(|_| unexpected_end())?;
+
+    let constant = match tag {
+        TAG_INTEGER => Object::Integer(
+            reader
+                .read_i64::<BigEndian>()
+                .map_err(|_| unexpected_end())?,
+        ),
+        TAG_BOOLEAN => Object::Boolean(reader.read_u8().map_err(|_| unexpected_end())? != 0),
+        TAG_STRING => {
+            let length = read_u32(reader)? as usize;
+
+            Object::String(String::from_utf8(read_bytes(reader, length)?)?)
+        }
+        TAG_FLOAT => Object::Float(
+            reader
+                .read_f64::<BigEndian>()
+                .map_err(|_| unexpected_end())?,
+        ),
+        TAG_CHAR => {
+            let code = read_u32(reader)?;
+
+            Object::Char(
+                char::from_u32(code)
+                    .ok_or_else(|| Error::msg(format!("invalid char constant: {}", code)))?,
+            )
+        }
+        TAG_FUNCTION => {
+            let instructions = read_instructions(reader)?;
+            let num_locals = read_u32(reader)? as usize;
+            let num_parameters = read_u32(reader)? as usize;
+
+            Object::CompiledFunction(Rc::new(CompiledFunction::new(
+                instructions,
+                num_locals,
+                num_parameters,
+            )))
+        }
+        _ => return Err(Error::msg(format!("unknown constant tag: {}", tag))),
+    };
+
+    Ok(constant)
+}
+
+fn read_position(reader: &mut &[u8]) -> Result<Position, Error> {
+    Ok(Position {
+        line: read_u32(reader)? as usize,
+        column: read_u32(reader)? as usize,
+    })
+}
+
+fn read_u32(reader: &mut &[u8]) -> Result<u32, Error> {
+    reader.read_u32::<BigEndian>().map_err(|_| unexpected_end())
+}
+
+/// Reads `length` bytes, checking they're there before allocating so a
+/// corrupt length can't request a huge buffer.
+fn read_bytes(reader: &mut &[u8], length: usize) -> Result<Vec<u8>, Error> {
+    if length > reader.len() {
+        return Err(unexpected_end());
+    }
+
+    let (bytes, rest) = reader.split_at(length);
+    *reader = rest;
+
+    Ok(bytes.to_vec())
+}
+
+fn read_exact(reader: &mut &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+    reader.read_exact(buffer).map_err(|_| unexpected_end())
+}
+
+fn unexpected_end() -> Error {
+    Error::msg("unexpected end of bytecode")
+}
@@ -21,6 +21,7 @@ pub struct SymbolTable {
     pub outer: Option<Rc<Self>>,
     pub store: HashMap<String, Rc<Symbol>>,
     pub num_definitions: usize,
+    pub free_symbols: Vec<Rc<Symbol>>,
 }
 
 impl SymbolTable {
@@ -29,6 +30,7 @@ impl SymbolTable {
             outer: None,
             store: HashMap::new(),
             num_definitions: 0,
+            free_symbols: Vec::new(),
         }
     }
 
@@ -36,11 +38,20 @@ impl SymbolTable {
         Self {
             store: HashMap::new(),
             num_definitions: 0,
+            free_symbols: Vec::new(),
             outer: Some(Rc::new(outer)),
         }
     }
 
+    /// Defines `name` in this scope, or returns the existing symbol if the
+    /// name was already defined here so reassignment writes the same slot.
     pub fn define(&mut self, name: &str) -> Rc<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) {
+                return Rc::clone(symbol);
+            }
+        }
+
         let symbol = Rc::new(Symbol {
             name: name.to_string(),
             scope: if self.outer.is_none() {
@@ -57,13 +68,58 @@ impl SymbolTable {
         symbol
     }
 
-    pub fn resolve(&self, name: &str) -> Option<Rc<Symbol>> {
-        let symbol = self.store.get(name);
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Rc<Symbol> {
+        let symbol = Rc::new(Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Builtin,
+            index,
+        });
+
+        self.store.insert(name.to_string(), Rc::clone(&symbol));
+
+        symbol
+    }
+
+    /// Defines `name` as the function whose body this scope belongs to, so
+    /// it can refer to itself without capturing a slot that isn't set yet.
+    pub fn define_function_name(&mut self, name: &str) -> Rc<Symbol> {
+        let symbol = Rc::new(Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        });
+
+        self.store.insert(name.to_string(), Rc::clone(&symbol));
+
+        symbol
+    }
+
+    /// Records `original` as a variable captured from an enclosing function
+    /// and returns the free symbol that refers to it within this scope.
+    pub fn define_free(&mut self, original: Rc<Symbol>) -> Rc<Symbol> {
+        let symbol = Rc::new(Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        });
+
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), Rc::clone(&symbol));
 
-        if symbol.is_none() && self.outer.is_some() {
-            return self.outer.as_ref().unwrap().resolve(name);
+        symbol
+    }
+
+    pub fn resolve(&mut self, name: &str) -> Option<Rc<Symbol>> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(Rc::clone(symbol));
         }
 
-        symbol.cloned()
+        let outer = Rc::make_mut(self.outer.as_mut()?);
+        let symbol = outer.resolve(name)?;
+
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            _ => Some(self.define_free(symbol)),
+        }
     }
 }
@@ -0,0 +1,128 @@
+use anyhow::Error;
+use compiler::{assemble::assemble, Bytecode, Compiler};
+use lexer::Lexer;
+use object::Object;
+use opcode::{concat_instructions, make, Opcode};
+use parser::{ast::Node, Parser};
+
+fn compile(input: &str) -> Result<Bytecode, Error> {
+    let mut parser = Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    let mut compiler = Compiler::new();
+
+    compiler.compile(&Node::Program(program))
+}
+
+#[test]
+fn test_assemble() -> Result<(), Error> {
+    let text = "
+OpConst 0
+OpConst 1
+OpAdd
+OpSetGlobal 0
+OpGetGlobal 0
+OpJumpNotTruthy 20
+OpConst 2
+OpPop
+
+; constants:
+1
+-2.5
+\"two\\n\"
+'c'
+true
+";
+
+    let bytecode = assemble(text)?;
+
+    assert_eq!(
+        bytecode.instructions,
+        concat_instructions(&vec![
+            make(Opcode::OpConst, &vec![0]),
+            make(Opcode::OpConst, &vec![1]),
+            make(Opcode::OpAdd, &vec![]),
+            make(Opcode::OpSetGlobal, &vec![0]),
+            make(Opcode::OpGetGlobal, &vec![0]),
+            make(Opcode::OpJumpNotTruthy, &vec![20]),
+            make(Opcode::OpConst, &vec![2]),
+            make(Opcode::OpPop, &vec![]),
+        ])
+    );
+
+    let expected_constants = [
+        Object::Integer(1),
+        Object::Float(-2.5),
+        Object::String("two\n".to_string()),
+        Object::Char('c'),
+        Object::Boolean(true),
+    ];
+
+    assert_eq!(bytecode.constants.len(), expected_constants.len());
+
+    for (expected, actual) in expected_constants.iter().zip(bytecode.constants.iter()) {
+        assert_eq!(expected, actual.as_ref());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assemble_matches_compiler_output() -> Result<(), Error> {
+    let inputs = [
+        "1 + 2 * 3",
+        "$a = \"hello\"; $a == \"world\"",
+        "if (1 < 2) { 10 } else { 20 }; 3333;",
+        "[1, 2.5, 'x'][0]",
+    ];
+
+    for input in inputs {
+        let bytecode = compile(input)?;
+
+        let mut text = bytecode.disassemble();
+        text.push_str("; constants:\n");
+
+        for constant in bytecode.constants.iter() {
+            match constant.as_ref() {
+                Object::String(string) => text.push_str(&format!("{:?}\n", string)),
+                Object::Char(ch) => text.push_str(&format!("{:?}\n", ch)),
+                Object::Float(float) => text.push_str(&format!("{:?}\n", float)),
+                constant => text.push_str(&format!("{}\n", constant)),
+            }
+        }
+
+        let assembled = assemble(&text)?;
+
+        assert_eq!(assembled.instructions, bytecode.instructions, "{}", input);
+        assert_eq!(assembled.constants, bytecode.constants, "{}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assemble_errors() -> Result<(), Error> {
+    let tests = [
+        ("OpConst 0\nOpNope", "line 2: unknown opcode: OpNope"),
+        ("OpAdd 1", "line 1: OpAdd expects 0 operands, got 1"),
+        ("OpConst", "line 1: OpConst expects 1 operands, got 0"),
+        ("OpConst x", "line 1: invalid operand `x`"),
+        (
+            "OpGetLocal 256",
+            "line 1: operand 256 does not fit in 1 bytes",
+        ),
+        (
+            "OpPop\n; constants:\n\n[1]",
+            "line 4: invalid constant `[1]`",
+        ),
+    ];
+
+    for (text, expected) in tests {
+        match assemble(text) {
+            Ok(bytecode) => panic!("expected an error for {:?}, got {:?}", text, bytecode),
+            Err(e) => assert_eq!(e.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
@@ -1,11 +1,14 @@
-use std::{borrow::Borrow, rc::Rc};
+use std::{borrow::Borrow, collections::HashMap, rc::Rc};
 
 use anyhow::Error;
-use compiler::Compiler;
+use compiler::{compile_str, infix_opcode, symbol_table::SymbolTable, Bytecode, Compiler};
+use lexer::token::{Token, TokenType};
 use lexer::Lexer;
-use object::Object;
-use opcode::concat_instructions;
-use parser::ast::Node;
+use object::{Object, WrappingMode};
+use opcode::{concat_instructions, Opcode};
+use parser::ast::{
+    Expression, InfixExpression, IntegerLiteral, Literal, Node, PrefixExpression, Statement,
+};
 
 struct CompilerTestCase {
     input: String,
@@ -59,6 +62,24 @@ fn test_array_expressions() -> Result<(), Error> {
                 opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
+        CompilerTestCase {
+            input: "[1, 2 + 3, 4]".to_string(),
+            expected_constants: vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+                Object::Integer(4),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                opcode::make(opcode::Opcode::OpArray, &vec![3]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
     ];
 
     run_compiler_tests(tests)?;
@@ -97,6 +118,24 @@ fn test_boolean_expressions() -> Result<(), Error> {
                 opcode::make(opcode::Opcode::OpGreaterThan, &vec![]),
             ],
         },
+        CompilerTestCase {
+            input: "1 >= 2".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpGreaterThanOrEqual, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "1 <= 2".to_string(),
+            expected_constants: vec![Object::Integer(2), Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpGreaterThanOrEqual, &vec![]),
+            ],
+        },
         CompilerTestCase {
             input: "1 == 2".to_string(),
             expected_constants: vec![Object::Integer(1), Object::Integer(2)],
@@ -141,6 +180,140 @@ fn test_boolean_expressions() -> Result<(), Error> {
                 opcode::make(opcode::Opcode::OpBang, &vec![]),
             ],
         },
+        CompilerTestCase {
+            input: "null == null".to_string(),
+            expected_constants: vec![],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                opcode::make(opcode::Opcode::OpEqual, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_builtins() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "len([1, 2]); push([], 1);".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpGetBuiltin, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpArray, &vec![2]),
+                opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpGetBuiltin, &vec![4]),
+                opcode::make(opcode::Opcode::OpArray, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpCall, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "function () { len([]); }".to_string(),
+            expected_constants: vec![Object::CompiledFunction(Rc::new(
+                object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetBuiltin, &vec![0]),
+                        opcode::make(opcode::Opcode::OpArray, &vec![0]),
+                        opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    0,
+                    0,
+                ),
+            ))],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![0, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_closures() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "function ($a) { function ($b) { $a + $b; }; }".to_string(),
+            expected_constants: vec![
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetFree, &vec![0]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    1,
+                ))),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpClosure, &vec![0, 1]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    1,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![1, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "function ($a) { function ($b) { function ($c) { $a + $b + $c; }; }; }"
+                .to_string(),
+            expected_constants: vec![
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetFree, &vec![0]),
+                        opcode::make(opcode::Opcode::OpGetFree, &vec![1]),
+                        opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    1,
+                ))),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetFree, &vec![0]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpClosure, &vec![0, 2]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    1,
+                ))),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpClosure, &vec![1, 1]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    1,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![2, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
     ];
 
     run_compiler_tests(tests)?;
@@ -203,6 +376,34 @@ fn test_compilation_scopes() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_compilation_scopes_restore_outer_state() -> Result<(), Error> {
+    let mut compiler = Compiler::new();
+
+    compiler.symbol_table.define("$a");
+    compiler.emit(opcode::Opcode::OpTrue, vec![]);
+    compiler.emit(opcode::Opcode::OpPop, vec![]);
+
+    let outer_instructions = compiler.current_instructions().clone();
+
+    compiler.enter_scope();
+    compiler.symbol_table.define("$b");
+    compiler.emit(opcode::Opcode::OpFalse, vec![]);
+
+    let inner_instructions = compiler.exit_scope();
+
+    assert_eq!(
+        inner_instructions,
+        opcode::make(opcode::Opcode::OpFalse, &vec![])
+    );
+    assert_eq!(compiler.current_instructions(), &outer_instructions);
+    assert!(compiler.symbol_table.outer.is_none());
+    assert!(compiler.symbol_table.resolve("$a").is_some());
+    assert!(compiler.symbol_table.resolve("$b").is_none());
+
+    Ok(())
+}
+
 #[test]
 fn test_conditionals() -> Result<(), Error> {
     let tests = vec![
@@ -238,6 +439,24 @@ fn test_conditionals() -> Result<(), Error> {
                 opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
+        CompilerTestCase {
+            input: "true ? 10 : 20; 3333;".to_string(),
+            expected_constants: vec![
+                Object::Integer(10),
+                Object::Integer(20),
+                Object::Integer(3333),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![10]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpJump, &vec![13]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
     ];
 
     run_compiler_tests(tests)?;
@@ -246,51 +465,86 @@ fn test_conditionals() -> Result<(), Error> {
 }
 
 #[test]
-fn test_functions() -> Result<(), Error> {
-    let tests = vec![CompilerTestCase {
-        input: "function () { return 5 + 10; }".to_string(),
-        expected_constants: vec![
-            Object::Integer(5),
-            Object::Integer(10),
-            Object::CompiledFunction(
-                Rc::new(
-                    object::CompiledFunction::new(
-                        concat_instructions(
-                            &vec![
-                                opcode::make(opcode::Opcode::OpConst, &vec![0]),
-                                opcode::make(opcode::Opcode::OpConst, &vec![1]),
-                                opcode::make(opcode::Opcode::OpAdd, &vec![]),
-                                opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
-                            ],
-                        ),
-                        0
-                    )
-                )
-            ),
-        ],
-        expected_instructions: vec![
-            opcode::make(opcode::Opcode::OpConst, &vec![2]),
-            opcode::make(opcode::Opcode::OpPop, &vec![]),
-        ],
-    }];
+fn test_empty_programs_and_blocks() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "".to_string(),
+            expected_constants: vec![],
+            expected_instructions: vec![],
+        },
+        CompilerTestCase {
+            input: "if (true) { }".to_string(),
+            expected_constants: vec![],
+            expected_instructions: vec![
+                // 0000
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                // 0001
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![8]),
+                // 0004: the empty consequence still produces a value
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                // 0005
+                opcode::make(opcode::Opcode::OpJump, &vec![9]),
+                // 0008
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                // 0009
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "if (true) { 1 } else { }".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                // 0000
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                // 0001
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![10]),
+                // 0004
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                // 0007
+                opcode::make(opcode::Opcode::OpJump, &vec![11]),
+                // 0010
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                // 0011
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
 
     run_compiler_tests(tests)?;
 
+    let mut parser = parser::Parser::new(Lexer::new(""));
+    let program = parser.parse_program()?;
+    let bytecode = Compiler::new()
+        .with_keep_last_value()
+        .compile(&Node::Program(program))?;
+
+    assert!(bytecode.instructions.is_empty());
+
     Ok(())
 }
 
 #[test]
-fn test_functions_with_no_return_value() -> Result<(), Error> {
+fn test_while_loops() -> Result<(), Error> {
     let tests = vec![CompilerTestCase {
-        input: "function() { }".to_string(),
-        expected_constants: vec![Object::CompiledFunction(Rc::new(
-            object::CompiledFunction::new(concat_instructions(&vec![opcode::make(
-                opcode::Opcode::OpReturn,
-                &vec![],
-            )]), 0),
-        ))],
+        input: "$x = 0; while ($x < 3) { $x = $x + 1; }; $x;".to_string(),
+        expected_constants: vec![Object::Integer(0), Object::Integer(3), Object::Integer(1)],
         expected_instructions: vec![
             opcode::make(opcode::Opcode::OpConst, &vec![0]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+            // 0006: loop start
+            opcode::make(opcode::Opcode::OpConst, &vec![1]),
+            opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpGreaterThan, &vec![]),
+            opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![29]),
+            opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpConst, &vec![2]),
+            opcode::make(opcode::Opcode::OpAdd, &vec![]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpJump, &vec![6]),
+            // 0029: loop exit
+            opcode::make(opcode::Opcode::OpNull, &vec![]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+            opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
             opcode::make(opcode::Opcode::OpPop, &vec![]),
         ],
     }];
@@ -301,44 +555,41 @@ fn test_functions_with_no_return_value() -> Result<(), Error> {
 }
 
 #[test]
-fn test_function_calls() -> Result<(), Error> {
+fn test_for_loops() -> Result<(), Error> {
     let tests = vec![
         CompilerTestCase {
-            input: "$noArg = function () { return 24; }; $noArg();".to_string(),
-            expected_constants: vec![
-                Object::Integer(24),
-                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(concat_instructions(
-                    &vec![
-                        opcode::make(opcode::Opcode::OpConst, &vec![0]),
-                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
-                    ],
-                ), 0))),
-            ],
+            input: "for ($i = 0; $i < 3; $i = $i + 1) { $i; }".to_string(),
+            expected_constants: vec![Object::Integer(0), Object::Integer(3), Object::Integer(1)],
             expected_instructions: vec![
-                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
                 opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                // 0006: loop start
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
                 opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
-                opcode::make(opcode::Opcode::OpCall, &vec![0]),
+                opcode::make(opcode::Opcode::OpGreaterThan, &vec![]),
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![33]),
+                // body
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                // update, after the body and before the back edge
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpJump, &vec![6]),
+                // 0033: loop exit
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
                 opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
         CompilerTestCase {
-            input: "$oneArg = function ($a) { return $a; }; $oneArg(24);".to_string(),
-            expected_constants: vec![
-                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(concat_instructions(
-                    &vec![
-                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
-                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
-                    ],
-                ), 1))),
-                Object::Integer(24),
-            ],
+            input: "for (;;) { 1; }".to_string(),
+            expected_constants: vec![Object::Integer(1)],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
-                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
-                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
-                opcode::make(opcode::Opcode::OpConst, &vec![1]),
-                opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpJump, &vec![0]),
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
                 opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
@@ -350,47 +601,1196 @@ fn test_function_calls() -> Result<(), Error> {
 }
 
 #[test]
-fn test_index_expressions() -> Result<(), Error> {
+fn test_break_and_continue() -> Result<(), Error> {
     let tests = vec![
         CompilerTestCase {
-            input: "[1, 2, 3][1 + 1]".to_string(),
-            expected_constants: vec![
-                Object::Integer(1),
-                Object::Integer(2),
-                Object::Integer(3),
-                Object::Integer(1),
-                Object::Integer(1),
+            input: "while (true) { break; continue; }".to_string(),
+            expected_constants: vec![],
+            expected_instructions: vec![
+                // 0000: loop start
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![13]),
+                // break
+                opcode::make(opcode::Opcode::OpJump, &vec![13]),
+                // continue
+                opcode::make(opcode::Opcode::OpJump, &vec![0]),
+                opcode::make(opcode::Opcode::OpJump, &vec![0]),
+                // 0013: loop exit
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
+        },
+        CompilerTestCase {
+            input: "for ($i = 0; $i < 3; $i = $i + 1) { continue; }".to_string(),
+            expected_constants: vec![Object::Integer(0), Object::Integer(3), Object::Integer(1)],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                // 0006: loop start
                 opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGreaterThan, &vec![]),
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![32]),
+                // continue jumps to the update
+                opcode::make(opcode::Opcode::OpJump, &vec![19]),
+                // 0019: update
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
                 opcode::make(opcode::Opcode::OpConst, &vec![2]),
-                opcode::make(opcode::Opcode::OpArray, &vec![3]),
-                opcode::make(opcode::Opcode::OpConst, &vec![3]),
-                opcode::make(opcode::Opcode::OpConst, &vec![4]),
-                opcode::make(opcode::Opcode::OpAdd, &vec![4]),
-                opcode::make(opcode::Opcode::OpIndex, &vec![]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpJump, &vec![6]),
+                // 0032: loop exit
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
                 opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
-        CompilerTestCase {
-            input: "[1, 2, 3][2 - 1]".to_string(),
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_break_outside_loop() -> Result<(), Error> {
+    let tests = [
+        ("break;", "compiler: 'break' outside of a loop"),
+        (
+            "while (true) { function () { continue; }; }",
+            "compiler: 'continue' outside of a loop",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        match Compiler::new().compile(&Node::Program(program)) {
+            Ok(_) => panic!("expected a compile error for {}", input),
+            Err(e) => assert_eq!(e.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_conditional_jump_targets() -> Result<(), Error> {
+    let inputs = vec![
+        "if (true) { 10 }",
+        "if (false) { 10 } else { 20 }",
+        "if (1 > 2) { 10; 11 } else { 20; 21 }; 3333;",
+    ];
+
+    for input in inputs {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        let bytecode = Compiler::new().compile(&Node::Program(program))?;
+        let instructions = &bytecode.instructions.0;
+
+        let mut jumps = vec![];
+        let mut i = 0;
+
+        while i < instructions.len() {
+            let op = opcode::Opcode::from(instructions[i]);
+            let (operands, read) =
+                opcode::read_operands(opcode::lookup(op), &instructions[i + 1..]);
+
+            if op == opcode::Opcode::OpJumpNotTruthy || op == opcode::Opcode::OpJump {
+                jumps.push((op, i, operands[0]));
+            }
+
+            i += 1 + read;
+        }
+
+        let (_, _, jnt_target) = jumps[0];
+        let (jump_op, jump_position, jump_target) = jumps[1];
+
+        assert_eq!(jump_op, opcode::Opcode::OpJump, "input: {}", input);
+
+        // The false branch starts right after the consequence's trailing OpJump...
+        assert_eq!(jnt_target, jump_position + 3, "input: {}", input);
+
+        // ...and the true branch skips over the whole alternative.
+        assert!(jump_target > jnt_target, "input: {}", input);
+        assert!(jump_target <= instructions.len(), "input: {}", input);
+        assert_ne!(
+            opcode::Opcode::from(instructions[jnt_target]),
+            opcode::Opcode::OpJump,
+            "input: {}",
+            input
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_logical_expressions() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "true && false".to_string(),
+            expected_constants: vec![],
+            expected_instructions: vec![
+                // 0000
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                // 0001
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![12]),
+                // 0004
+                opcode::make(opcode::Opcode::OpFalse, &vec![]),
+                // 0005
+                opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![12]),
+                // 0008
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                // 0009
+                opcode::make(opcode::Opcode::OpJump, &vec![13]),
+                // 0012
+                opcode::make(opcode::Opcode::OpFalse, &vec![]),
+                // 0013
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "false || 1 > 2".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            expected_instructions: vec![
+                // 0000
+                opcode::make(opcode::Opcode::OpFalse, &vec![]),
+                // 0001
+                opcode::make(opcode::Opcode::OpJumpTruthy, &vec![18]),
+                // 0004
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                // 0007
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                // 0010
+                opcode::make(opcode::Opcode::OpGreaterThan, &vec![]),
+                // 0011
+                opcode::make(opcode::Opcode::OpJumpTruthy, &vec![18]),
+                // 0014
+                opcode::make(opcode::Opcode::OpFalse, &vec![]),
+                // 0015
+                opcode::make(opcode::Opcode::OpJump, &vec![19]),
+                // 0018
+                opcode::make(opcode::Opcode::OpTrue, &vec![]),
+                // 0019
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_optimize_jumps() -> Result<(), Error> {
+    let input = "for (; true;) { continue; }; 30;";
+
+    let mut parser = parser::Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    let mut compiler = Compiler::new();
+
+    let mut bytecode = compiler.compile(&Node::Program(program))?;
+
+    // With no update clause, the trailing continue leaves an OpJump 7 at
+    // offset 4.
+    assert_eq!(
+        &bytecode.instructions.0[4..7],
+        &opcode::make(opcode::Opcode::OpJump, &vec![7]).0[..]
+    );
+
+    bytecode.optimize_jumps();
+
+    let expected = concat_instructions(&vec![
+        // 0000
+        opcode::make(opcode::Opcode::OpTrue, &vec![]),
+        // 0001
+        opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![7]),
+        // 0004
+        opcode::make(opcode::Opcode::OpJump, &vec![0]),
+        // 0007
+        opcode::make(opcode::Opcode::OpNull, &vec![]),
+        // 0008
+        opcode::make(opcode::Opcode::OpPop, &vec![]),
+        // 0009
+        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+        // 0012
+        opcode::make(opcode::Opcode::OpPop, &vec![]),
+    ]);
+
+    assert_eq!(bytecode.instructions, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_source_positions() -> Result<(), Error> {
+    let input = "1;\n2;";
+
+    let mut parser = parser::Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+    // OpConst 0, OpPop, OpConst 1, OpPop
+    assert_eq!(bytecode.span_at(0).map(|span| span.start.line), Some(1));
+    assert_eq!(bytecode.span_at(3).map(|span| span.start.line), Some(1));
+    assert_eq!(bytecode.span_at(4).map(|span| span.start.line), Some(2));
+    assert_eq!(bytecode.span_at(5).map(|span| span.start.line), Some(2));
+
+    Ok(())
+}
+
+#[test]
+fn test_tuple_literals() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "(1,)".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpTuple, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "(1, 2 + 3)".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpTuple, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_keep_last_value() -> Result<(), Error> {
+    let compile = |mut compiler: Compiler, input: &str| -> Result<opcode::Instructions, Error> {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        Ok(compiler.compile(&Node::Program(program))?.instructions)
+    };
+
+    let input = "1; 2";
+
+    let popped = concat_instructions(&vec![
+        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+        opcode::make(opcode::Opcode::OpPop, &vec![]),
+        opcode::make(opcode::Opcode::OpConst, &vec![1]),
+        opcode::make(opcode::Opcode::OpPop, &vec![]),
+    ]);
+
+    let kept = concat_instructions(&vec![
+        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+        opcode::make(opcode::Opcode::OpPop, &vec![]),
+        opcode::make(opcode::Opcode::OpConst, &vec![1]),
+    ]);
+
+    assert_eq!(compile(Compiler::new(), input)?, popped);
+    assert_eq!(
+        compile(Compiler::new().with_keep_last_value(), input)?,
+        kept
+    );
+
+    // A trailing assignment has no value to keep.
+    let expected = concat_instructions(&vec![
+        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+        opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+    ]);
+
+    assert_eq!(
+        compile(Compiler::new().with_keep_last_value(), "$a = 1;")?,
+        expected
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_construct_errors() -> Result<(), Error> {
+    let mut parser = parser::Parser::new(Lexer::new("$a = [1]; $a[0] = 2;"));
+    let program = parser.parse_program()?;
+
+    match Compiler::new().compile(&Node::Program(program)) {
+        Ok(_) => panic!("expected a compile error"),
+        Err(e) => assert_eq!(e.to_string(), "compiler: unsupported statement: $a[0] = 2"),
+    }
+
+    // The parser never produces these operators, so build the nodes by hand.
+    let integer = |value: i64| {
+        Box::new(Expression::Literal(Literal::Integer(IntegerLiteral {
+            token: Token::new(TokenType::Int, &value.to_string()),
+            value,
+        })))
+    };
+
+    let infix = Expression::Infix(InfixExpression {
+        token: Token::new(TokenType::Int, "1"),
+        left: integer(1),
+        operator: Token::new(TokenType::Colon, ":"),
+        right: integer(2),
+    });
+
+    let prefix = Expression::Prefix(PrefixExpression {
+        token: Token::new(TokenType::Question, "?"),
+        operator: Token::new(TokenType::Question, "?"),
+        right: integer(3),
+    });
+
+    let tests = [
+        (infix, "compiler: unsupported infix operator ':' in (1 : 2)"),
+        (prefix, "compiler: unsupported prefix operator '?' in (?3)"),
+    ];
+
+    for (expression, expected) in tests {
+        let node = Node::Statement(Statement::Expr(expression));
+
+        match Compiler::new().compile(&node) {
+            Ok(_) => panic!("expected a compile error for {}", node),
+            Err(e) => assert_eq!(e.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_postfix_expressions() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "$x = 1; $x++;".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                // the old value stays on the stack
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                // then the new one is stored
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "function () { $i = 5; $i--; }".to_string(),
+            expected_constants: vec![
+                Object::Integer(5),
+                Object::Integer(1),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                        opcode::make(opcode::Opcode::OpSetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                        opcode::make(opcode::Opcode::OpSub, &vec![]),
+                        opcode::make(opcode::Opcode::OpSetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    0,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![2, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    let errors = [
+        ("1++", "compiler: invalid operand for '++' in (1++)"),
+        (
+            "$a = [1]; $a[0]--",
+            "compiler: invalid operand for '--' in (($a[0])--)",
+        ),
+        ("$y++", "compiler: undefined variable '$y' at 1:1"),
+        (
+            "function () { $n = 1; function () { $n++ } }",
+            "compiler: invalid operand for '++' in ($n++)",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        match Compiler::new().compile(&Node::Program(program)) {
+            Ok(_) => panic!("expected a compile error for {}", input),
+            Err(e) => assert_eq!(e.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_float_expressions() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "2.75".to_string(),
+            expected_constants: vec![Object::Float(2.75)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "[1, 2.5]".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Float(2.5)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpArray, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_functions() -> Result<(), Error> {
+    let tests = vec![CompilerTestCase {
+        input: "function () { return 5 + 10; }".to_string(),
+        expected_constants: vec![
+            Object::Integer(5),
+            Object::Integer(10),
+            Object::CompiledFunction(
+                Rc::new(
+                    object::CompiledFunction::new(
+                        concat_instructions(
+                            &vec![
+                                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                                opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                            ],
+                        ),
+                        0,
+                        0,
+                    )
+                )
+            ),
+        ],
+        expected_instructions: vec![
+            opcode::make(opcode::Opcode::OpClosure, &vec![2, 0]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+        ],
+    }];
+
+    run_compiler_tests(tests)?;
+
+    // Without a `return`, the last expression statement is returned.
+    let tests = vec![
+        CompilerTestCase {
+            input: "function () { 5 + 10; }".to_string(),
+            expected_constants: vec![
+                Object::Integer(5),
+                Object::Integer(10),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                        opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                        opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    0,
+                    0,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![2, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "function () { 1; 2; }".to_string(),
+            expected_constants: vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                        opcode::make(opcode::Opcode::OpPop, &vec![]),
+                        opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    0,
+                    0,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![2, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_functions_with_no_return_value() -> Result<(), Error> {
+    let tests = vec![CompilerTestCase {
+        input: "function() { }".to_string(),
+        expected_constants: vec![Object::CompiledFunction(Rc::new(
+            object::CompiledFunction::new(concat_instructions(&vec![opcode::make(
+                opcode::Opcode::OpReturn,
+                &vec![],
+            )]), 0, 0),
+        ))],
+        expected_instructions: vec![
+            opcode::make(opcode::Opcode::OpClosure, &vec![0, 0]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+        ],
+    }];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_unreachable_code_after_return() -> Result<(), Error> {
+    let input = "function () { return 5; 10; }";
+
+    let mut parser = parser::Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    let mut compiler = Compiler::new();
+
+    let bytecode = compiler.compile(&Node::Program(program))?;
+
+    assert_constants(
+        &vec![
+            Object::Integer(5),
+            Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                concat_instructions(&vec![
+                    opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                    opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                ]),
+                0,
+                0,
+            ))),
+        ],
+        &bytecode.constants,
+    );
+
+    assert_eq!(compiler.warnings().len(), 1);
+    assert_eq!(
+        compiler.warnings()[0].message,
+        "unreachable code after return: 10"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_function_calls() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "$noArg = function () { return 24; }; $noArg();".to_string(),
+            expected_constants: vec![
+                Object::Integer(24),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(concat_instructions(
+                    &vec![
+                        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ],
+                ), 0, 0))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![1, 0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpCall, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "$oneArg = function ($a) { return $a; }; $oneArg(24);".to_string(),
+            expected_constants: vec![
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(concat_instructions(
+                    &vec![
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ],
+                ), 1, 1))),
+                Object::Integer(24),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![0, 0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "function ($a) { $a; }(24);".to_string(),
+            expected_constants: vec![
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    1,
+                    1,
+                ))),
+                Object::Integer(24),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![0, 0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_global_assignment_statements() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "$one = 1; $two = 2;".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![1]),
+            ],
+        },
+        CompilerTestCase {
+            input: "$one = 1; $one;".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "$one = 1; $two = $one; $two;".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![1]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "$y = ($x = 3);".to_string(),
+            expected_constants: vec![Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![1]),
+            ],
+        },
+        CompilerTestCase {
+            input: "($x = 3);".to_string(),
+            expected_constants: vec![Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_literals() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "{}".to_string(),
+            expected_constants: vec![],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpHash, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "{1: 2, 3: 4}".to_string(),
+            expected_constants: vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+                Object::Integer(4),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                opcode::make(opcode::Opcode::OpHash, &vec![4]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "{\"b\": 2 * 3, \"a\": 1}".to_string(),
+            expected_constants: vec![
+                Object::String("a".to_string()),
+                Object::Integer(1),
+                Object::String("b".to_string()),
+                Object::Integer(2),
+                Object::Integer(3),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                opcode::make(opcode::Opcode::OpConst, &vec![4]),
+                opcode::make(opcode::Opcode::OpMul, &vec![]),
+                opcode::make(opcode::Opcode::OpHash, &vec![4]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_index_expressions() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "[1, 2, 3][1 + 1]".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpArray, &vec![3]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![4]),
+                opcode::make(opcode::Opcode::OpIndex, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "[1, 2, 3][2 - 1]".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpArray, &vec![3]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSub, &vec![4]),
+                opcode::make(opcode::Opcode::OpIndex, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "\"hello\"[1:]".to_string(),
+            expected_constants: vec![Object::String("hello".to_string()), Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                // the missing end bound
+                opcode::make(opcode::Opcode::OpNull, &vec![]),
+                opcode::make(opcode::Opcode::OpSlice, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "0..5; 0..=5".to_string(),
+            expected_constants: vec![Object::Integer(0), Object::Integer(5)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpRange, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpRange, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_grouped_expressions() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "(1 + 2) * 3".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpMul, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "1 * (2 + 3)".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpMul, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "((1 + 2) * (3 - 4)) / 5".to_string(),
             expected_constants: vec![
                 Object::Integer(1),
                 Object::Integer(2),
                 Object::Integer(3),
+                Object::Integer(4),
+                Object::Integer(5),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                opcode::make(opcode::Opcode::OpSub, &vec![]),
+                opcode::make(opcode::Opcode::OpMul, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![4]),
+                opcode::make(opcode::Opcode::OpDiv, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "-(((1 - 2)))".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpSub, &vec![]),
+                opcode::make(opcode::Opcode::OpMinus, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_integer_arithmetic() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "1 + 2".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+            ],
+        },
+        CompilerTestCase {
+            input: "2 - 1".to_string(),
+            expected_constants: vec![Object::Integer(2), Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpSub, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+            ],
+        },
+        CompilerTestCase {
+            input: "2 * 4".to_string(),
+            expected_constants: vec![Object::Integer(2), Object::Integer(4)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpMul, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+            ],
+        },
+        CompilerTestCase {
+            input: "4 / 2".to_string(),
+            expected_constants: vec![Object::Integer(4), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpDiv, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+            ],
+        },
+        CompilerTestCase {
+            input: "10 % 3".to_string(),
+            expected_constants: vec![Object::Integer(10), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpMod, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "-1".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpMinus, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_bitwise_operators() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "5 & 3".to_string(),
+            expected_constants: vec![Object::Integer(5), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpBitAnd, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "5 | 3".to_string(),
+            expected_constants: vec![Object::Integer(5), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpBitOr, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "5 ^ 3".to_string(),
+            expected_constants: vec![Object::Integer(5), Object::Integer(3)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpBitXor, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "1 << 4".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(4)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpShiftLeft, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "16 >> 2".to_string(),
+            expected_constants: vec![Object::Integer(16), Object::Integer(2)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpShiftRight, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_bitwise_not() -> Result<(), Error> {
+    let tests = vec![CompilerTestCase {
+        input: "~5".to_string(),
+        expected_constants: vec![Object::Integer(5)],
+        expected_instructions: vec![
+            opcode::make(opcode::Opcode::OpConst, &vec![0]),
+            opcode::make(opcode::Opcode::OpBitNot, &vec![]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+        ],
+    }];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_local_assignment_statements() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "$num = 55; function () { $num; }".to_string(),
+            expected_constants: vec![
+                Object::Integer(55),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    0,
+                    0,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpClosure, &vec![1, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "function () { $a = 1; $b = 2; $a + $b; }".to_string(),
+            expected_constants: vec![
+                Object::Integer(1),
                 Object::Integer(2),
+                Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                    concat_instructions(&vec![
+                        opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                        opcode::make(opcode::Opcode::OpSetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                        opcode::make(opcode::Opcode::OpSetLocal, &vec![1]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                        opcode::make(opcode::Opcode::OpGetLocal, &vec![1]),
+                        opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                        opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                    ]),
+                    2,
+                    0,
+                ))),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpClosure, &vec![2, 0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+    ];
+
+    run_compiler_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_constant_deduplication() -> Result<(), Error> {
+    let tests = vec![
+        CompilerTestCase {
+            input: "1 + 1 + 1".to_string(),
+            expected_constants: vec![Object::Integer(1)],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "\"a\"; 1; 1.0; \"a\"; 0.0; -0.0; 1.0".to_string(),
+            expected_constants: vec![
+                Object::String("a".to_string()),
+                Object::Integer(1),
+                Object::Float(1.0),
+                Object::Float(0.0),
+            ],
+            expected_instructions: vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                opcode::make(opcode::Opcode::OpMinus, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        },
+        CompilerTestCase {
+            input: "1; \"1\"; 'a'; \"a\"; 'a'; 1; \"1\"".to_string(),
+            expected_constants: vec![
                 Object::Integer(1),
+                Object::String("1".to_string()),
+                Object::Char('a'),
+                Object::String("a".to_string()),
             ],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
                 opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
                 opcode::make(opcode::Opcode::OpConst, &vec![2]),
-                opcode::make(opcode::Opcode::OpArray, &vec![3]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
                 opcode::make(opcode::Opcode::OpConst, &vec![3]),
-                opcode::make(opcode::Opcode::OpConst, &vec![4]),
-                opcode::make(opcode::Opcode::OpSub, &vec![4]),
-                opcode::make(opcode::Opcode::OpIndex, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![2]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
                 opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
@@ -402,59 +1802,124 @@ fn test_index_expressions() -> Result<(), Error> {
 }
 
 #[test]
-fn test_integer_arithmetic() -> Result<(), Error> {
+fn test_constant_folding() -> Result<(), Error> {
     let tests = vec![
         CompilerTestCase {
-            input: "1 + 2".to_string(),
-            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
+            input: "2 + 3".to_string(),
+            expected_constants: vec![Object::Integer(5)],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
-                opcode::make(opcode::Opcode::OpConst, &vec![1]),
-                opcode::make(opcode::Opcode::OpAdd, &vec![1]),
-                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
         CompilerTestCase {
-            input: "2 - 1".to_string(),
-            expected_constants: vec![Object::Integer(2), Object::Integer(1)],
+            input: "2 * 3 - 8 / 2".to_string(),
+            expected_constants: vec![Object::Integer(2)],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
-                opcode::make(opcode::Opcode::OpConst, &vec![1]),
-                opcode::make(opcode::Opcode::OpSub, &vec![1]),
-                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
         CompilerTestCase {
-            input: "2 * 4".to_string(),
-            expected_constants: vec![Object::Integer(2), Object::Integer(4)],
+            input: "$a = 1; $a + 2".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(2)],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+                opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
                 opcode::make(opcode::Opcode::OpConst, &vec![1]),
-                opcode::make(opcode::Opcode::OpMul, &vec![1]),
-                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
         CompilerTestCase {
-            input: "4 / 2".to_string(),
-            expected_constants: vec![Object::Integer(4), Object::Integer(2)],
+            input: "1 / 0".to_string(),
+            expected_constants: vec![Object::Integer(1), Object::Integer(0)],
             expected_instructions: vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
                 opcode::make(opcode::Opcode::OpConst, &vec![1]),
-                opcode::make(opcode::Opcode::OpDiv, &vec![1]),
-                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+                opcode::make(opcode::Opcode::OpDiv, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
         },
-        CompilerTestCase {
-            input: "-1".to_string(),
-            expected_constants: vec![Object::Integer(1)],
-            expected_instructions: vec![
+    ];
+
+    for test in tests {
+        let mut parser = parser::Parser::new(Lexer::new(&test.input));
+
+        let program = parser.parse_program()?;
+        let mut compiler = Compiler::new().with_optimizations();
+
+        let bytecode = compiler.compile(&Node::Program(program))?;
+
+        assert_constants(&test.expected_constants, &bytecode.constants);
+        assert_eq!(
+            bytecode.instructions,
+            concat_instructions(&test.expected_instructions)
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_constant_folding_overflow() -> Result<(), Error> {
+    let input = "9223372036854775807 + 1";
+
+    let tests = [
+        (
+            WrappingMode::Checked,
+            vec![Object::Integer(i64::MAX), Object::Integer(1)],
+            vec![
                 opcode::make(opcode::Opcode::OpConst, &vec![0]),
-                opcode::make(opcode::Opcode::OpMinus, &vec![]),
-                opcode::make(opcode::Opcode::OpPop, &vec![0]),
+                opcode::make(opcode::Opcode::OpConst, &vec![1]),
+                opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
             ],
-        },
+        ),
+        (
+            WrappingMode::Wrapping,
+            vec![Object::Integer(i64::MIN)],
+            vec![
+                opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                opcode::make(opcode::Opcode::OpPop, &vec![]),
+            ],
+        ),
     ];
 
+    for (wrapping_mode, expected_constants, expected_instructions) in tests {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+        let mut compiler = Compiler::new()
+            .with_optimizations()
+            .with_wrapping_mode(wrapping_mode);
+
+        let bytecode = compiler.compile(&Node::Program(program))?;
+
+        assert_constants(&expected_constants, &bytecode.constants);
+        assert_eq!(
+            bytecode.instructions,
+            concat_instructions(&expected_instructions)
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_constant_folding_disabled_by_default() -> Result<(), Error> {
+    let tests = vec![CompilerTestCase {
+        input: "2 + 3".to_string(),
+        expected_constants: vec![Object::Integer(2), Object::Integer(3)],
+        expected_instructions: vec![
+            opcode::make(opcode::Opcode::OpConst, &vec![0]),
+            opcode::make(opcode::Opcode::OpConst, &vec![1]),
+            opcode::make(opcode::Opcode::OpAdd, &vec![]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+        ],
+    }];
+
     run_compiler_tests(tests)?;
 
     Ok(())
@@ -510,6 +1975,325 @@ fn run_compiler_tests(tests: Vec<CompilerTestCase>) -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_recursive_functions() -> Result<(), Error> {
+    let tests = vec![CompilerTestCase {
+        input: "$countDown = function ($x) { $countDown($x - 1); }; $countDown(1);".to_string(),
+        expected_constants: vec![
+            Object::Integer(1),
+            Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                concat_instructions(&vec![
+                    opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+                    opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                    opcode::make(opcode::Opcode::OpConst, &vec![0]),
+                    opcode::make(opcode::Opcode::OpSub, &vec![]),
+                    opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                    opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                ]),
+                1,
+                1,
+            ))),
+        ],
+        expected_instructions: vec![
+            opcode::make(opcode::Opcode::OpClosure, &vec![1, 0]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpConst, &vec![0]),
+            opcode::make(opcode::Opcode::OpCall, &vec![1]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+        ],
+    }];
+
+    run_compiler_tests(tests)?;
+
+    // A local function can't capture its own slot before it's set, so it
+    // loads itself as the current closure.
+    let tests = vec![CompilerTestCase {
+        input: "$f = function () { $g = function ($n) { $g($n); }; };".to_string(),
+        expected_constants: vec![
+            Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                concat_instructions(&vec![
+                    opcode::make(opcode::Opcode::OpCurrentClosure, &vec![]),
+                    opcode::make(opcode::Opcode::OpGetLocal, &vec![0]),
+                    opcode::make(opcode::Opcode::OpCall, &vec![1]),
+                    opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                ]),
+                1,
+                1,
+            ))),
+            Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                concat_instructions(&vec![
+                    opcode::make(opcode::Opcode::OpClosure, &vec![0, 0]),
+                    opcode::make(opcode::Opcode::OpSetLocal, &vec![0]),
+                    opcode::make(opcode::Opcode::OpReturn, &vec![]),
+                ]),
+                1,
+                0,
+            ))),
+        ],
+        expected_instructions: vec![
+            opcode::make(opcode::Opcode::OpClosure, &vec![1, 0]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+        ],
+    }];
+
+    run_compiler_tests(tests)?;
+
+    // Only functions are defined ahead of their value.
+    let mut parser = parser::Parser::new(Lexer::new("$x = $x + 1;"));
+    let program = parser.parse_program()?;
+
+    match Compiler::new().compile(&Node::Program(program)) {
+        Ok(bytecode) => panic!("expected an error, got {:?}", bytecode),
+        Err(e) => assert_eq!(e.to_string(), "compiler: undefined variable '$x' at 1:6"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_link() -> Result<(), Error> {
+    let compile = |input: &str| -> Result<Bytecode, Error> {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        Compiler::new().compile(&Node::Program(program))
+    };
+
+    let first = compile("$a = 1; if ($a) { 2 } else { 3 };")?;
+    let second = compile("$b = 3; if (true) { 4 } else { $b };")?;
+    let third = compile("$f = function () { $f; 4 + 5 };")?;
+
+    let linked = Bytecode::link(vec![first, second, third])?;
+
+    assert_constants(
+        &vec![
+            Object::Integer(1),
+            Object::Integer(2),
+            Object::Integer(3),
+            Object::Integer(4),
+            Object::Integer(5),
+            Object::CompiledFunction(Rc::new(object::CompiledFunction::new(
+                concat_instructions(&vec![
+                    opcode::make(opcode::Opcode::OpGetGlobal, &vec![2]),
+                    opcode::make(opcode::Opcode::OpPop, &vec![]),
+                    opcode::make(opcode::Opcode::OpConst, &vec![3]),
+                    opcode::make(opcode::Opcode::OpConst, &vec![4]),
+                    opcode::make(opcode::Opcode::OpAdd, &vec![]),
+                    opcode::make(opcode::Opcode::OpReturnValue, &vec![]),
+                ]),
+                0,
+                0,
+            ))),
+        ],
+        &linked.constants,
+    );
+
+    assert_instructions(
+        &vec![
+            // The first module is unchanged.
+            opcode::make(opcode::Opcode::OpConst, &vec![0]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpGetGlobal, &vec![0]),
+            opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![18]),
+            opcode::make(opcode::Opcode::OpConst, &vec![1]),
+            opcode::make(opcode::Opcode::OpJump, &vec![21]),
+            opcode::make(opcode::Opcode::OpConst, &vec![2]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+            // 0022: the second module, with `3` shared with the first and
+            // its jumps and global slot moved along.
+            opcode::make(opcode::Opcode::OpConst, &vec![2]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![1]),
+            opcode::make(opcode::Opcode::OpTrue, &vec![]),
+            opcode::make(opcode::Opcode::OpJumpNotTruthy, &vec![38]),
+            opcode::make(opcode::Opcode::OpConst, &vec![3]),
+            opcode::make(opcode::Opcode::OpJump, &vec![41]),
+            opcode::make(opcode::Opcode::OpGetGlobal, &vec![1]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+            // 0042: the third module.
+            opcode::make(opcode::Opcode::OpClosure, &vec![5, 0]),
+            opcode::make(opcode::Opcode::OpSetGlobal, &vec![2]),
+        ],
+        &linked.instructions,
+    );
+    assert_eq!(linked.instructions.len(), 49);
+
+    Ok(())
+}
+
+#[test]
+fn test_compile_str() -> Result<(), Error> {
+    let bytecode = compile_str("1 + 2")?;
+
+    assert_constants(
+        &vec![Object::Integer(1), Object::Integer(2)],
+        &bytecode.constants,
+    );
+    assert_eq!(
+        bytecode.instructions,
+        concat_instructions(&vec![
+            opcode::make(opcode::Opcode::OpConst, &vec![0]),
+            opcode::make(opcode::Opcode::OpConst, &vec![1]),
+            opcode::make(opcode::Opcode::OpAdd, &vec![]),
+            opcode::make(opcode::Opcode::OpPop, &vec![]),
+        ])
+    );
+
+    let stats = bytecode.stats();
+    assert_eq!(stats.opcode_counts.get(&Opcode::OpConst), Some(&2));
+    assert_eq!(stats.opcode_counts.get(&Opcode::OpAdd), Some(&1));
+
+    // Syntax errors come back through the same error type.
+    assert!(compile_str("$x = ;").is_err());
+
+    match compile_str("$x + 1") {
+        Ok(bytecode) => panic!("expected an error, got {:?}", bytecode),
+        Err(e) => assert_eq!(e.to_string(), "compiler: undefined variable '$x' at 1:1"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_undefined_variable() -> Result<(), Error> {
+    let tests = [
+        ("$foo", "compiler: undefined variable '$foo' at 1:1"),
+        (
+            "$x = 1;\n$x + $y",
+            "compiler: undefined variable '$y' at 2:6",
+        ),
+        (
+            "function ($a) {\n    return $a + $b;\n}",
+            "compiler: undefined variable '$b' at 2:17",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        match compile_str(input) {
+            Ok(bytecode) => panic!("expected an error, got {:?}", bytecode),
+            Err(e) => assert_eq!(e.to_string(), expected, "{}", input),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_comparison_opcodes() -> Result<(), Error> {
+    let tests = [
+        (TokenType::Gt, Some((false, Opcode::OpGreaterThan))),
+        (TokenType::GtEq, Some((false, Opcode::OpGreaterThanOrEqual))),
+        (TokenType::Lt, Some((true, Opcode::OpGreaterThan))),
+        (TokenType::LtEq, Some((true, Opcode::OpGreaterThanOrEqual))),
+        (TokenType::Eq, Some((false, Opcode::OpEqual))),
+        (TokenType::NotEq, Some((false, Opcode::OpNotEqual))),
+        (TokenType::And, None),
+        (TokenType::Or, None),
+    ];
+
+    for (operator, expected) in tests {
+        assert_eq!(infix_opcode(&operator), expected, "{}", operator);
+    }
+
+    // Swapped operands are pushed, and so added to the pool, right first.
+    for (input, pushed, op) in [
+        ("1 > 2", [1, 2], Opcode::OpGreaterThan),
+        ("1 >= 2", [1, 2], Opcode::OpGreaterThanOrEqual),
+        ("1 < 2", [2, 1], Opcode::OpGreaterThan),
+        ("1 <= 2", [2, 1], Opcode::OpGreaterThanOrEqual),
+    ] {
+        let bytecode = compile_str(input)?;
+
+        assert_constants(
+            &pushed.iter().map(|value| Object::Integer(*value)).collect(),
+            &bytecode.constants,
+        );
+        assert_eq!(
+            bytecode.instructions,
+            concat_instructions(&vec![
+                opcode::make(Opcode::OpConst, &vec![0]),
+                opcode::make(Opcode::OpConst, &vec![1]),
+                opcode::make(op, &vec![]),
+                opcode::make(Opcode::OpPop, &vec![]),
+            ]),
+            "{}",
+            input
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_bytecode_view_borrows_constants() -> Result<(), Error> {
+    let elements = (0..10_000).map(|i| i.to_string()).collect::<Vec<String>>();
+    let input = format!("[{}]", elements.join(", "));
+
+    let mut parser = parser::Parser::new(Lexer::new(&input));
+    let program = Node::Program(parser.parse_program()?);
+
+    let mut compiler = Compiler::new();
+    let view = compiler.compile_in_place(&program)?;
+
+    assert_eq!(view.constants.len(), 10_000);
+    assert!(view
+        .constants
+        .iter()
+        .all(|constant| Rc::strong_count(constant) == 1));
+
+    let view = compiler.bytecode_view();
+    assert!(std::ptr::eq(view.constants, compiler.constants.as_slice()));
+    assert!(std::ptr::eq(
+        view.instructions,
+        compiler.current_instructions()
+    ));
+
+    // Copying on request still shares the constants themselves.
+    let bytecode = view.to_bytecode();
+    assert_eq!(bytecode, compiler.bytecode());
+    assert!(Rc::ptr_eq(&bytecode.constants[0], &compiler.constants[0]));
+
+    let constants = compiler.constants.as_ptr();
+    let moved = compiler.into_bytecode();
+    assert_eq!(moved, bytecode);
+    assert_eq!(moved.constants.as_ptr(), constants);
+
+    Ok(())
+}
+
+#[test]
+fn test_disassemble_verbose() -> Result<(), Error> {
+    let bytecode = compile_str("$a = 42; $a + 1.5; \"hi\"")?;
+
+    assert_eq!(
+        bytecode.disassemble_verbose(),
+        "0000 OpConst 0    ; 42
+0003 OpSetGlobal 0
+0006 OpGetGlobal 0
+0009 OpConst 1    ; 1.5
+0012 OpAdd
+0013 OpPop
+0014 OpConst 2    ; hi
+0017 OpPop
+"
+    );
+
+    // A dangling index is flagged rather than panicking.
+    let bytecode = Bytecode {
+        instructions: opcode::make(Opcode::OpConst, &vec![3]),
+        constants: vec![Rc::new(Object::Integer(1))],
+        positions: vec![],
+    };
+
+    assert_eq!(
+        bytecode.disassemble_verbose(),
+        "0000 OpConst 3    ; <invalid constant index>\n"
+    );
+    assert_eq!(bytecode.disassemble(), "0000 OpConst 3\n");
+
+    Ok(())
+}
+
 pub fn assert_constants(expected: &Vec<Object>, actual: &Vec<Rc<Object>>) {
     assert_eq!(expected.len(), actual.len());
     for (exp, b_got) in expected.iter().zip(actual) {
@@ -531,3 +2315,178 @@ fn assert_instructions(expected: &Vec<opcode::Instructions>, actual: &opcode::In
         );
     }
 }
+
+#[test]
+fn test_stats() -> Result<(), Error> {
+    let tests = [
+        (
+            "$a = 1; $a + $a * 2;",
+            18,
+            2,
+            vec![
+                (Opcode::OpConst, 2),
+                (Opcode::OpSetGlobal, 1),
+                (Opcode::OpGetGlobal, 2),
+                (Opcode::OpMul, 1),
+                (Opcode::OpAdd, 1),
+                (Opcode::OpPop, 1),
+            ],
+        ),
+        (
+            // Function bodies are counted too.
+            "function () { 1 }; 1",
+            13,
+            2,
+            vec![
+                (Opcode::OpConst, 2),
+                (Opcode::OpReturnValue, 1),
+                (Opcode::OpClosure, 1),
+                (Opcode::OpPop, 2),
+            ],
+        ),
+    ];
+
+    for (input, expected_bytes, expected_constants, expected_counts) in tests {
+        let mut parser = parser::Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        let stats = Compiler::new().compile(&Node::Program(program))?.stats();
+        let expected_counts = expected_counts.into_iter().collect::<HashMap<_, _>>();
+
+        assert_eq!(stats.instruction_bytes, expected_bytes, "{}", input);
+        assert_eq!(
+            stats.instruction_count,
+            expected_counts.values().sum::<usize>(),
+            "{}",
+            input
+        );
+        assert_eq!(stats.constant_count, expected_constants, "{}", input);
+        assert_eq!(stats.opcode_counts, expected_counts, "{}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_disassemble() -> Result<(), Error> {
+    let bytecode = Bytecode {
+        instructions: concat_instructions(&vec![
+            opcode::make(opcode::Opcode::OpAdd, &vec![]),
+            opcode::make(opcode::Opcode::OpConst, &vec![2]),
+            opcode::make(opcode::Opcode::OpConst, &vec![65534]),
+            opcode::make(opcode::Opcode::OpClosure, &vec![65535, 255]),
+            opcode::make(opcode::Opcode::OpGetLocal, &vec![1]),
+        ]),
+        constants: vec![],
+        positions: vec![],
+    };
+
+    let expected = "0000 OpAdd
+0001 OpConst 2
+0004 OpConst 65534
+0007 OpClosure 65535 255
+0011 OpGetLocal 1
+";
+
+    assert_eq!(bytecode.disassemble(), expected);
+    assert_eq!(format!("{:?}", bytecode), expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_constant_pool_overflow() -> Result<(), Error> {
+    let compile = |constants: &Vec<Rc<Object>>, input: &str| {
+        let program = parser::Parser::new(Lexer::new(input)).parse_program()?;
+
+        Compiler::new_with_state(constants.clone(), SymbolTable::new())
+            .compile(&Node::Program(program))
+    };
+
+    // Indexes up to 65535 fit in OpConst's two-byte operand.
+    let mut constants = (0..65535)
+        .map(|value| Rc::new(Object::Integer(value)))
+        .collect::<Vec<_>>();
+
+    let bytecode = compile(&constants, "65535")?;
+    assert_eq!(
+        bytecode.instructions,
+        concat_instructions(&vec![
+            opcode::make(Opcode::OpConst, &vec![65535]),
+            opcode::make(Opcode::OpPop, &vec![]),
+        ])
+    );
+
+    constants.push(Rc::new(Object::Integer(65535)));
+
+    // A full pool still reuses existing constants...
+    assert!(compile(&constants, "65535").is_ok());
+
+    // ...but has no room for a new one.
+    for input in ["65536", "\"s\"", "function () {}"] {
+        match compile(&constants, input) {
+            Ok(bytecode) => panic!("expected an error, got {:?}", bytecode),
+            Err(e) => assert_eq!(
+                e.to_string(),
+                "compiler: constant pool overflow",
+                "{}",
+                input
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_variable_slot_overflow() -> Result<(), Error> {
+    // Identifiers can't contain digits, so spell the index out in letters.
+    let name = |mut index: usize| {
+        let mut name = "$v".to_string();
+
+        loop {
+            name.push((b'a' + (index % 26) as u8) as char);
+            index /= 26;
+
+            if index == 0 {
+                return name;
+            }
+        }
+    };
+
+    let mut symbol_table = SymbolTable::new();
+
+    for index in 0..65536 {
+        symbol_table.define(&name(index));
+    }
+
+    // Reassigning an existing global is still fine.
+    let input = format!("{} = 1; $x = 1", name(65535));
+    let program = parser::Parser::new(Lexer::new(&input)).parse_program()?;
+    let mut compiler = Compiler::new_with_state(vec![], symbol_table);
+
+    match compiler.compile(&Node::Program(program)) {
+        Ok(bytecode) => panic!("expected an error, got {:?}", bytecode),
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "compiler: too many global variables to define '$x'"
+        ),
+    }
+
+    // The failed definition leaves the table as it was.
+    assert!(!compiler.symbol_table.store.contains_key("$x"));
+    assert_eq!(compiler.symbol_table.num_definitions, 65536);
+
+    // OpSetLocal only has one byte for the slot.
+    let parameters = (0..257).map(name).collect::<Vec<String>>();
+
+    match compile_str(&format!("function ({}) {{}}", parameters.join(", "))) {
+        Ok(bytecode) => panic!("expected an error, got {:?}", bytecode),
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "compiler: too many local variables to define '$vwj'"
+        ),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::rc::Rc;
+
+use anyhow::Error;
+use compiler::{
+    serialize::{MAGIC, VERSION},
+    Bytecode, Compiler,
+};
+use lexer::Lexer;
+use object::Object;
+use parser::{ast::Node, Parser};
+
+fn compile(input: &str) -> Result<Bytecode, Error> {
+    let mut parser = Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    let mut compiler = Compiler::new();
+
+    compiler.compile(&Node::Program(program))
+}
+
+#[test]
+fn test_round_trip() -> Result<(), Error> {
+    let inputs = [
+        "1 + 2",
+        "['a', '\\n']",
+        "$a = true; $b = \"hello\"; if ($a) { $b } else { 2.5 }",
+        "$add = function ($x, $y) { $z = $x + $y; $z; }; $add(1, 2);",
+        "$adder = function ($a) { function ($b) { $a + $b; }; }; $adder(1)(2);",
+    ];
+
+    for input in inputs {
+        let bytecode = compile(input)?;
+
+        let bytes = bytecode.serialize()?;
+
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(Bytecode::deserialize(&bytes)?, bytecode, "input: {}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_deserialize_errors() -> Result<(), Error> {
+    let bytes = compile("[1, \"two\"]")?.serialize()?;
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+
+    let mut bad_version = bytes.clone();
+    bad_version[4] = 99;
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+
+    // A header followed by the given instruction stream and body bytes.
+    let file = |instructions: &[u8], rest: &[u8]| {
+        let mut file = MAGIC.to_vec();
+        file.push(VERSION);
+        file.extend_from_slice(&(instructions.len() as u32).to_be_bytes());
+        file.extend_from_slice(instructions);
+        file.extend_from_slice(rest);
+        file
+    };
+
+    let mut huge_instructions = MAGIC.to_vec();
+    huge_instructions.push(VERSION);
+    huge_instructions.extend_from_slice(&u32::MAX.to_be_bytes());
+
+    let huge_string = file(&[], &[0, 0, 0, 1, 2, 0xff, 0xff, 0xff, 0xff]);
+    let unknown_opcode = file(&[0xff], &[0; 8]);
+    let truncated_operands = file(&[0x00, 0x00], &[0; 8]);
+    let bad_constant = file(&[0x00, 0x00, 0x05], &[0; 8]);
+    let bad_closure = file(&[0x1A, 0x00, 0x00, 0x00], &[0; 8]);
+    let bad_builtin = file(&[0x20, 0xff], &[0; 8]);
+    let bad_jump = file(&[0x0E, 0x00, 0x04], &[0; 8]);
+
+    let tests = [
+        (&bad_magic[..], "not a pine bytecode file: bad magic number"),
+        (&bad_version[..], "unsupported bytecode version: 99"),
+        (&bytes[..bytes.len() - 1], "unexpected end of bytecode"),
+        (&trailing[..], "1 unexpected trailing bytes in bytecode"),
+        (&huge_instructions[..], "unexpected end of bytecode"),
+        (&huge_string[..], "unexpected end of bytecode"),
+        (&unknown_opcode[..], "unknown opcode 255 at offset 0"),
+        (
+            &truncated_operands[..],
+            "truncated operands for OpConst at offset 0",
+        ),
+        (
+            &bad_constant[..],
+            "constant index 5 out of range for OpConst at offset 0",
+        ),
+        (
+            &bad_closure[..],
+            "constant index 0 out of range for OpClosure at offset 0",
+        ),
+        (
+            &bad_builtin[..],
+            "builtin index 255 out of range for OpGetBuiltin at offset 0",
+        ),
+        (
+            &bad_jump[..],
+            "jump target 4 out of range for OpJump at offset 0",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        match Bytecode::deserialize(input) {
+            Ok(_) => panic!("expected an error: {}", expected),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_serialize_errors() {
+    let bytecode = Bytecode {
+        instructions: Default::default(),
+        constants: vec![Rc::new(Object::Null)],
+        positions: vec![],
+    };
+
+    match bytecode.serialize() {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => assert_eq!(err.to_string(), "cannot serialize constant: null"),
+    }
+}
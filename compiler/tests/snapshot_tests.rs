@@ -0,0 +1,98 @@
+use std::{env, fs, path::PathBuf};
+
+use anyhow::Error;
+use compiler::compile_str;
+
+/// Compiles `source` and compares its full disassembly with the golden file
+/// `tests/snapshots/<name>.snap`.
+///
+/// Run with `UPDATE_SNAPSHOTS=1` to write the current output instead, e.g.
+/// after an intended codegen change, and review the diff before committing.
+fn assert_snapshot(name: &str, source: &str) -> Result<(), Error> {
+    let actual = format!(
+        "; source: {}\n\n{}",
+        source,
+        compile_str(source)?.disassemble_all()
+    );
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{}.snap", name));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, &actual)?;
+
+        return Ok(());
+    }
+
+    let expected = fs::read_to_string(&path).map_err(|e| {
+        Error::msg(format!(
+            "can't read snapshot {}: {} (run with UPDATE_SNAPSHOTS=1 to create it)",
+            path.display(),
+            e
+        ))
+    })?;
+
+    assert_eq!(
+        actual, expected,
+        "snapshot {} changed; rerun with UPDATE_SNAPSHOTS=1 to accept it",
+        name
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_arithmetic() -> Result<(), Error> {
+    assert_snapshot("arithmetic", "$a = 1 + 2 * 3; ($a - 4) / 2 % 3; -$a;")
+}
+
+#[test]
+fn test_snapshot_if_else() -> Result<(), Error> {
+    assert_snapshot(
+        "if_else",
+        "$x = 5; if ($x > 3 && $x != 4) { \"big\" } else { \"small\" }; $x < 1 ? 1.5 : 'c';",
+    )
+}
+
+#[test]
+fn test_snapshot_loops() -> Result<(), Error> {
+    assert_snapshot(
+        "loops",
+        "$i = 0; while ($i < 10) { $i = $i + 1; if ($i == 5) { break; }; }; for ($j = 0; $j < 3; $j++) { continue; };",
+    )
+}
+
+#[test]
+fn test_snapshot_arrays_and_hashes() -> Result<(), Error> {
+    assert_snapshot(
+        "arrays_and_hashes",
+        "$a = [1, 2, 3]; $a[0] + $a[-1]; $h = {\"one\": 1, 2: [true]}; $h[\"one\"]; $a[1:]; (1, \"x\");",
+    )
+}
+
+#[test]
+fn test_snapshot_functions() -> Result<(), Error> {
+    assert_snapshot(
+        "functions",
+        "$add = function ($a, $b) { return $a + $b; }; $noop = function () { }; $add(1, 2); len([$noop()]);",
+    )
+}
+
+#[test]
+fn test_snapshot_closures() -> Result<(), Error> {
+    assert_snapshot(
+        "closures",
+        "$adder = function ($a) { function ($b) { $a + $b } }; $addTwo = $adder(2); $addTwo(3);",
+    )
+}
+
+#[test]
+fn test_snapshot_recursion() -> Result<(), Error> {
+    assert_snapshot(
+        "recursion",
+        "$fib = function ($n) { if ($n < 2) { return $n; }; $fib($n - 1) + $fib($n - 2); }; $fib(10);",
+    )
+}
@@ -146,3 +146,86 @@ fn test_resolve_global() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_redefine_reuses_symbol() -> Result<(), Error> {
+    let mut global = SymbolTable::new();
+
+    let a = global.define("a");
+    global.define("b");
+
+    assert_eq!(global.define("a").index, a.index);
+    assert_eq!(global.num_definitions, 2);
+
+    let mut local = SymbolTable::new_enclosed(global);
+
+    // Defining a global's name inside a function shadows it with a local.
+    let shadow = local.define("a");
+
+    assert_eq!(shadow.scope, SymbolScope::Local);
+    assert_eq!(shadow.index, 0);
+    assert_eq!(local.define("a").index, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_resolve_free() -> Result<(), Error> {
+    let mut global = SymbolTable::new();
+    global.define("a");
+
+    let mut first_local = SymbolTable::new_enclosed(global);
+    first_local.define("c");
+
+    let mut second_local = SymbolTable::new_enclosed(first_local);
+    second_local.define("e");
+
+    let expected = vec![
+        ("a", SymbolScope::Global, 0),
+        ("c", SymbolScope::Free, 0),
+        ("e", SymbolScope::Local, 0),
+    ];
+
+    for (name, scope, index) in expected {
+        let symbol = second_local
+            .resolve(name)
+            .unwrap_or_else(|| panic!("symbol for {} is None", name));
+
+        assert_eq!(symbol.scope, scope, "wrong scope for {}", name);
+        assert_eq!(symbol.index, index, "wrong index for {}", name);
+    }
+
+    assert_eq!(second_local.free_symbols.len(), 1);
+    assert_eq!(second_local.free_symbols[0].name, "c");
+    assert_eq!(second_local.free_symbols[0].scope, SymbolScope::Local);
+
+    assert!(second_local.resolve("unknown").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_define_resolve_builtins() -> Result<(), Error> {
+    let mut global = SymbolTable::new();
+
+    for (index, name) in ["a", "c", "e", "f"].iter().enumerate() {
+        global.define_builtin(index, name);
+    }
+
+    let first_local = SymbolTable::new_enclosed(global.clone());
+    let mut second_local = SymbolTable::new_enclosed(first_local.clone());
+
+    for (index, name) in ["a", "c", "e", "f"].iter().enumerate() {
+        let symbol = second_local
+            .resolve(name)
+            .unwrap_or_else(|| panic!("symbol for {} is None", name));
+
+        assert_eq!(symbol.scope, SymbolScope::Builtin);
+        assert_eq!(symbol.index, index);
+    }
+
+    assert!(second_local.free_symbols.is_empty());
+    assert_eq!(global.num_definitions, 0);
+
+    Ok(())
+}
@@ -1,17 +1,65 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
 
 use anyhow::{Error, Ok, Result};
 
+use lexer::token::TokenType;
+
 use parser::ast::{
-    ArrayLiteral, BooleanLiteral, CallExpression, Expression, FunctionLiteral, IfExpression,
-    Literal, Node, Statement,
+    ArrayLiteral, Assignment, BooleanLiteral, CallExpression, Expression, FunctionLiteral,
+    HashLiteral, IfExpression, Literal, Node, RangeExpression, SliceExpression, Statement,
+    TernaryExpression, TupleLiteral,
 };
 
 use object::{
+    builtins,
     environment::{Env, Environment},
-    Object,
+    ArithOp, Object, WrappingMode,
 };
 
+/// How many function calls may be nested before evaluation stops with a
+/// stack overflow error, unless changed with `set_max_call_depth`.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+thread_local! {
+    static MAX_CALL_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_CALL_DEPTH) };
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Sets how deeply function calls may nest on the current thread. Each call
+/// uses several Rust stack frames, so a limit that is too high for the
+/// thread's stack still crashes instead of returning an error.
+pub fn set_max_call_depth(depth: usize) {
+    MAX_CALL_DEPTH.with(|max| max.set(depth));
+}
+
+/// Counts one level of call nesting for as long as it is alive, so the depth
+/// unwinds correctly when a call returns an error.
+struct CallDepthGuard;
+
+impl CallDepthGuard {
+    fn enter() -> Result<Self> {
+        let depth = CALL_DEPTH.with(|depth| depth.get());
+
+        if depth >= MAX_CALL_DEPTH.with(|max| max.get()) {
+            return Err(Error::msg("stack overflow: max call depth exceeded"));
+        }
+
+        CALL_DEPTH.with(|d| d.set(depth + 1));
+
+        Ok(CallDepthGuard)
+    }
+}
+
+impl Drop for CallDepthGuard {
+    fn drop(&mut self) {
+        CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
 pub fn eval(node: Node, env: &Env) -> Result<Rc<Object>> {
     match node {
         Node::Expression(expression) => eval_expression(&expression, env),
@@ -39,6 +87,16 @@ pub fn eval_statements(statements: &Vec<Statement>, env: &Env) -> Result<Rc<Obje
 fn apply_function(function: &Rc<Object>, args: &Vec<Rc<Object>>) -> Result<Rc<Object>> {
     match &**function {
         Object::Function(params, body, env) => {
+            if params.len() != args.len() {
+                return Err(Error::msg(format!(
+                    "wrong number of arguments: want={}, got={}",
+                    params.len(),
+                    args.len()
+                )));
+            }
+
+            let _depth = CallDepthGuard::enter()?;
+
             let mut env = Environment::new_enclosed_environment(&env);
 
             params.iter().enumerate().for_each(|(i, param)| {
@@ -48,18 +106,11 @@ fn apply_function(function: &Rc<Object>, args: &Vec<Rc<Object>>) -> Result<Rc<Ob
             let evaluated = eval_statements(&body.statements, &Rc::new(RefCell::new(env)))?;
             return unwrap_return_value(evaluated);
         }
+        Object::Builtin(builtin) => (builtin.func)(args.clone()),
         f => Err(Error::msg(format!("expected {} to be a function", f))),
     }
 }
 
-fn is_truthy(object: &Object) -> bool {
-    match object {
-        Object::Null => false,
-        Object::Boolean(boolean) => *boolean,
-        _ => true,
-    }
-}
-
 fn unwrap_return_value(object: Rc<Object>) -> Result<Rc<Object>> {
     match &*object {
         Object::Return(value) => Ok(Rc::clone(value)),
@@ -72,6 +123,7 @@ fn eval_expression(expression: &Expression, env: &Env) -> Result<Rc<Object>> {
         Expression::Identifier(identifier_expression) => {
             eval_identifier(identifier_expression.to_string(), env)
         }
+        Expression::Assign(assignment) => eval_assignment(assignment, env),
         Expression::Literal(literal) => eval_literal(&literal, env),
         Expression::Index(index_expression) => {
             let left = eval_expression(&index_expression.left, &Rc::clone(env))?;
@@ -79,6 +131,50 @@ fn eval_expression(expression: &Expression, env: &Env) -> Result<Rc<Object>> {
 
             eval_index_expression(left, index)
         }
+        Expression::Slice(SliceExpression {
+            left, start, end, ..
+        }) => {
+            let left = eval_expression(left, env)?;
+            let bound = |bound: &Option<Box<Expression>>| match bound {
+                Some(bound) => eval_expression(bound, env),
+                None => Ok(Rc::new(Object::Null)),
+            };
+            let (start, end) = (bound(start)?, bound(end)?);
+
+            Ok(Rc::new(left.slice(&start, &end)?))
+        }
+        Expression::Range(RangeExpression {
+            start,
+            end,
+            inclusive,
+            ..
+        }) => {
+            let start = eval_expression(start, env)?;
+            let end = eval_expression(end, env)?;
+
+            Ok(Rc::new(Object::range(&start, &end, *inclusive)?))
+        }
+        Expression::Infix(infix_expression)
+            if matches!(
+                infix_expression.operator.token_type,
+                TokenType::And | TokenType::Or
+            ) =>
+        {
+            let left = eval_expression(&infix_expression.left, env)?;
+
+            // Only evaluate the right-hand side when it decides the result.
+            let result = match infix_expression.operator.token_type {
+                TokenType::And if !left.is_truthy() => false,
+                TokenType::Or if left.is_truthy() => true,
+                _ => {
+                    let right = eval_expression(&infix_expression.right, env)?;
+
+                    right.is_truthy()
+                }
+            };
+
+            Ok(native_bool_to_bool_object(result).into())
+        }
         Expression::Infix(infix_expression) => {
             let left = eval_expression(&infix_expression.left, &Rc::clone(env))?;
             let right = eval_expression(&infix_expression.right, &Rc::clone(env))?;
@@ -91,6 +187,20 @@ fn eval_expression(expression: &Expression, env: &Env) -> Result<Rc<Object>> {
             eval_prefix_expression(prefix_expression.operator.to_string(), &right, env)
         }
         Expression::If(_if_expression) => eval_if_expression(expression, env),
+        Expression::Ternary(TernaryExpression {
+            condition,
+            consequence,
+            alternative,
+            ..
+        }) => {
+            let condition = eval_expression(condition, env)?;
+
+            if condition.is_truthy() {
+                eval_expression(consequence, env)
+            } else {
+                eval_expression(alternative, env)
+            }
+        }
         Expression::Call(CallExpression {
             function,
             token: _,
@@ -131,7 +241,7 @@ fn eval_if_expression(expression: &Expression, env: &Env) -> Result<Rc<Object>>
     {
         let condition = eval_expression(condition, env)?;
 
-        if is_truthy(&condition) {
+        if condition.is_truthy() {
             eval_statements(&consequence.statements, env)
         } else {
             match alternative {
@@ -150,14 +260,28 @@ fn eval_if_expression(expression: &Expression, env: &Env) -> Result<Rc<Object>>
 fn eval_index_expression(left: Rc<Object>, index: Rc<Object>) -> Result<Rc<Object>> {
     match (&*left, &*index) {
         (Object::Array(elements), Object::Integer(index)) => {
-            let max = elements.len() - 1;
-
-            if *index < 0 || *index > max as i64 {
-                return Ok(Object::Null.into());
+            match object::array_index(elements.len(), *index) {
+                Some(index) => Ok(Rc::clone(&elements[index])),
+                None => Ok(Object::Null.into()),
             }
-
-            return Ok(Rc::clone(&elements[*index as usize]));
         }
+        (Object::String(string), Object::Integer(index)) => {
+            Ok(Rc::new(Object::string_index(string, *index)))
+        }
+        (
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            },
+            Object::Integer(index),
+        ) => Ok(Rc::new(Object::range_index(
+            *start, *end, *inclusive, *index,
+        ))),
+        (Object::Hash(pairs), _) => match pairs.get(&index.hash_key()?) {
+            Some(value) => Ok(Rc::clone(value)),
+            None => Ok(Object::Null.into()),
+        },
         _ => Err(Error::msg(format!(
             "Unknown index expression: {}[{}]",
             left, index
@@ -178,22 +302,48 @@ fn eval_infix_expression(
         (Object::Boolean(left), Object::Boolean(right)) => {
             eval_boolean_infix_expression(operator, *left, *right)
         }
+        (left, right)
+            if matches!(operator.as_str(), "==" | "!=")
+                && std::mem::discriminant(left) == std::mem::discriminant(right) =>
+        {
+            let equal = left.equals(right);
+
+            Ok(native_bool_to_bool_object(if operator == "==" { equal } else { !equal }).into())
+        }
         (Object::String(left), Object::String(right)) => {
             eval_string_infix_expression(operator, left.to_string(), right.to_string())
         }
-        _ => Err(Error::msg(format!("Unknown operator: {}", operator))),
+        _ if std::mem::discriminant(left) != std::mem::discriminant(right) => {
+            Err(Error::msg(format!(
+                "type mismatch: {} {} {}",
+                left.type_name(),
+                operator,
+                right.type_name()
+            )))
+        }
+        _ => Err(unknown_infix_operator(&operator, left, right)),
     }
 }
 
+fn unknown_infix_operator(operator: &str, left: &Object, right: &Object) -> Error {
+    Error::msg(format!(
+        "unknown operator: {} {} {}",
+        left.type_name(),
+        operator,
+        right.type_name()
+    ))
+}
+
 fn eval_boolean_infix_expression(operator: String, left: bool, right: bool) -> Result<Rc<Object>> {
     let result = match operator.as_str() {
         "==" => native_bool_to_bool_object(left == right),
         "!=" => native_bool_to_bool_object(left != right),
         _ => {
-            return Err(Error::msg(format!(
-                "Unknown operator: {} {} {}",
-                left, operator, right
-            )))
+            return Err(unknown_infix_operator(
+                &operator,
+                &Object::Boolean(left),
+                &Object::Boolean(right),
+            ))
         }
     };
 
@@ -201,26 +351,57 @@ fn eval_boolean_infix_expression(operator: String, left: bool, right: bool) -> R
 }
 
 fn eval_integer_infix_expression(operator: String, left: i64, right: i64) -> Result<Rc<Object>> {
+    let arithmetic = |op| {
+        WrappingMode::Checked
+            .apply(op, left, right)
+            .map(Object::Integer)
+    };
+
     let result = match operator.as_str() {
-        "+" => Object::Integer(left + right),
-        "-" => Object::Integer(left - right),
-        "*" => Object::Integer(left * right),
-        "/" => Object::Integer(left / right),
+        "+" => arithmetic(ArithOp::Add)?,
+        "-" => arithmetic(ArithOp::Sub)?,
+        "*" => arithmetic(ArithOp::Mul)?,
+        "/" => arithmetic(ArithOp::Div)?,
+        "%" => arithmetic(ArithOp::Rem)?,
+        "&" => Object::Integer(left & right),
+        "|" => Object::Integer(left | right),
+        "^" => Object::Integer(left ^ right),
+        "<<" => Object::Integer(
+            shift_amount(right)
+                .and_then(|s| left.checked_shl(s))
+                .ok_or_else(|| shift_out_of_range(right))?,
+        ),
+        ">>" => Object::Integer(
+            shift_amount(right)
+                .and_then(|s| left.checked_shr(s))
+                .ok_or_else(|| shift_out_of_range(right))?,
+        ),
         "<" => native_bool_to_bool_object(left < right),
         ">" => native_bool_to_bool_object(left > right),
+        "<=" => native_bool_to_bool_object(left <= right),
+        ">=" => native_bool_to_bool_object(left >= right),
         "==" => native_bool_to_bool_object(left == right),
         "!=" => native_bool_to_bool_object(left != right),
         _ => {
-            return Err(Error::msg(format!(
-                "Unknown operator: {} {} {}",
-                left, operator, right
-            )))
+            return Err(unknown_infix_operator(
+                &operator,
+                &Object::Integer(left),
+                &Object::Integer(right),
+            ))
         }
     };
 
     Ok(result.into())
 }
 
+fn shift_amount(amount: i64) -> Option<u32> {
+    u32::try_from(amount).ok()
+}
+
+fn shift_out_of_range(amount: i64) -> Error {
+    Error::msg(format!("shift amount out of range: {}", amount))
+}
+
 fn eval_string_infix_expression(
     operator: String,
     left: String,
@@ -229,10 +410,11 @@ fn eval_string_infix_expression(
     let result = match operator.as_str() {
         "+" => Object::String(format!("{}{}", left, right)),
         _ => {
-            return Err(Error::msg(format!(
-                "Unknown operator: {} {} {}",
-                left, operator, right
-            )))
+            return Err(unknown_infix_operator(
+                &operator,
+                &Object::String(left),
+                &Object::String(right),
+            ))
         }
     };
 
@@ -243,32 +425,36 @@ fn eval_prefix_expression(operator: String, right: &Object, _env: &Env) -> Resul
     match operator.as_str() {
         "-" => eval_minus_prefix_operator_expression(right),
         "!" => eval_bang_operator_expression(right),
+        "~" => match *right {
+            Object::Integer(integer) => Ok(Rc::new(Object::Integer(!integer))),
+            _ => Err(Error::msg(format!(
+                "unknown operator: ~{}",
+                right.type_name()
+            ))),
+        },
         _ => {
             return Err(Error::msg(format!(
-                "Unknown operator: {}{}",
-                operator, right
+                "unknown operator: {}{}",
+                operator,
+                right.type_name()
             )))
         }
     }
 }
 
 fn eval_bang_operator_expression(right: &Object) -> Result<Rc<Object>> {
-    let result = match *right {
-        Object::Null => Rc::new(Object::Boolean(true)),
-        Object::Boolean(boolean) => Rc::new(Object::Boolean(!boolean)),
-        _ => Rc::new(Object::Boolean(false)),
-    };
-
-    Ok(result.into())
+    Ok(Rc::new(Object::Boolean(!right.is_truthy())))
 }
 
 fn eval_minus_prefix_operator_expression(right: &Object) -> Result<Rc<Object>> {
     let result = match *right {
-        Object::Integer(integer) => Rc::from(Object::Integer(-integer)),
+        Object::Integer(integer) => {
+            Rc::from(Object::Integer(WrappingMode::Checked.negate(integer)?))
+        }
         _ => {
             return Err(Error::msg(format!(
-                "Invalid use of minus operator: -{}",
-                right
+                "unknown operator: -{}",
+                right.type_name()
             )))
         }
     };
@@ -279,14 +465,7 @@ fn eval_minus_prefix_operator_expression(right: &Object) -> Result<Rc<Object>> {
 fn eval_statement(statement: &Statement, env: &Env) -> Result<Rc<Object>> {
     match statement {
         Statement::Expr(expression) => eval_expression(expression, env),
-        Statement::Assign(assignment) => {
-            let value = eval_expression(&assignment.value, env)?;
-            let object = Rc::clone(&value);
-
-            env.borrow_mut().set(assignment.name.to_string(), object);
-
-            Ok(value)
-        }
+        Statement::Assign(assignment) => eval_assignment(assignment, env),
         Statement::Return(return_statement) => {
             let value = eval_expression(&return_statement.return_value, env)?;
 
@@ -296,9 +475,23 @@ fn eval_statement(statement: &Statement, env: &Env) -> Result<Rc<Object>> {
     }
 }
 
+fn eval_assignment(assignment: &Assignment, env: &Env) -> Result<Rc<Object>> {
+    let value = eval_expression(&assignment.value, env)?;
+    let object = Rc::clone(&value);
+
+    env.borrow_mut().set(assignment.name.to_string(), object);
+
+    Ok(value)
+}
+
 fn eval_identifier(identifier: String, env: &Env) -> Result<Rc<Object>> {
-    match env.borrow().get(&identifier) {
-        Some(value) => Ok(value),
+    if let Some(value) = env.borrow().get(&identifier) {
+        return Ok(value);
+    }
+
+    // Builtins are checked last so user bindings can shadow them.
+    match builtins::lookup(&identifier) {
+        Some(builtin) => Ok(Rc::new(Object::Builtin(builtin.clone()))),
         None => Err(Error::msg(format!("Identifier not found: {}", identifier))),
     }
 }
@@ -306,13 +499,33 @@ fn eval_identifier(identifier: String, env: &Env) -> Result<Rc<Object>> {
 fn eval_literal(literal: &Literal, env: &Env) -> Result<Rc<Object>> {
     let result = match literal {
         Literal::Integer(integer) => Object::Integer(integer.value),
+        Literal::Float(float) => Object::Float(float.value),
         Literal::Boolean(BooleanLiteral { value, .. }) => Object::Boolean(*value),
+        Literal::Null(_) => Object::Null,
         Literal::String(string) => Object::String(string.value.clone()),
+        Literal::Char(ch) => Object::Char(ch.value),
         Literal::Array(ArrayLiteral { elements, .. }) => {
             let elements = eval_expressions(elements, env)?;
 
             return Ok(Rc::from(Object::Array(elements)));
         }
+        Literal::Tuple(TupleLiteral { elements, .. }) => {
+            let elements = eval_expressions(elements, env)?;
+
+            return Ok(Rc::from(Object::Tuple(elements)));
+        }
+        Literal::Hash(HashLiteral { pairs, .. }) => {
+            let mut hash = HashMap::new();
+
+            for (key, value) in pairs {
+                let key = eval_expression(key, env)?.hash_key()?;
+                let value = eval_expression(value, env)?;
+
+                hash.insert(key, value);
+            }
+
+            Object::Hash(hash)
+        }
         _ => return Err(Error::msg(format!("Unknown literal type: {}", literal))),
     };
 
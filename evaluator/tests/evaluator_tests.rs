@@ -2,7 +2,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use anyhow::Error;
 
-use evaluator::eval;
+use evaluator::{eval, set_max_call_depth, DEFAULT_MAX_CALL_DEPTH};
 use lexer::Lexer;
 use object::{environment::Environment, Object};
 use parser::{ast::Node, Parser};
@@ -28,6 +28,10 @@ fn test_eval_boolean_expression() -> Result<(), Error> {
         ("1 > 2", false),
         ("1 < 1", false),
         ("1 > 1", false),
+        ("1 <= 1", true),
+        ("2 <= 1", false),
+        ("1 >= 1", true),
+        ("1 >= 2", false),
         ("1 == 1", true),
         ("1 != 1", false),
         ("1 == 2", false),
@@ -41,6 +45,22 @@ fn test_eval_boolean_expression() -> Result<(), Error> {
         ("(1 < 2) == false", false),
         ("(1 > 2) == true", false),
         ("(1 > 2) == false", true),
+        ("true && false", false),
+        ("true && true", true),
+        ("false || true", true),
+        ("false || false", false),
+        ("false && 1 / 0 == 1", false),
+        ("true || 1 / 0 == 1", true),
+        ("[1, [2, 3]] == [1, [2, 3]]", true),
+        ("[1, [2, 3]] != [1, [2, 4]]", true),
+        ("[1, 2] == [1, 2, 3]", false),
+        ("[] == []", true),
+        ("{\"a\": 1, \"b\": [2]} == {\"b\": [2], \"a\": 1}", true),
+        ("{\"a\": 1} != {\"a\": 2}", true),
+        ("\"a\" == \"a\"", true),
+        ("null == null", true),
+        ("null != null", false),
+        ("!null", true),
     ];
 
     for (input, expected) in tests {
@@ -63,12 +83,20 @@ fn test_eval_integer_expression() -> Result<(), Error> {
         ("-50 + 100 + -50", 0),
         ("5 * 2 + 10", 20),
         ("5 + 2 * 10", 25),
+        ("1 + 2 * 3", 7),
         ("20 + 2 * -10", 0),
         ("50 / 2 * 2 + 10", 60),
+        ("10 % 3", 1),
+        ("2 + 10 % 4 * 3", 8),
         ("2 * (5 + 10)", 30),
         ("3 * 3 * 3 + 10", 37),
         ("3 * (3 * 3) + 10", 37),
         ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ("6 & 3 | 8 ^ 1", 11),
+        ("1 << 4 >> 2", 4),
+        ("-16 >> 2", -4),
+        ("~5", -6),
+        ("~-1 | ~~3", 3),
     ];
 
     for (input, expected) in tests {
@@ -88,6 +116,63 @@ fn test_eval_string_expressions() -> Result<(), Error> {
         assert_string_literal_object(evaluated, expected)?;
     }
 
+    let errors = vec![
+        ("\"Hello\" + 1", "type mismatch: STRING + INTEGER"),
+        ("1 + \"Hello\"", "type mismatch: INTEGER + STRING"),
+    ];
+
+    for (input, expected) in errors {
+        match assert_eval(input) {
+            Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_operator_errors() -> Result<(), Error> {
+    let errors = vec![
+        ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+        ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+        ("\"a\" - \"b\"", "unknown operator: STRING - STRING"),
+        ("-true", "unknown operator: -BOOLEAN"),
+        ("~true", "unknown operator: ~BOOLEAN"),
+        ("1 << 64", "shift amount out of range: 64"),
+        ("1 >> -1", "shift amount out of range: -1"),
+        (
+            "9223372036854775807 + 1",
+            "integer overflow: 9223372036854775807 + 1",
+        ),
+        (
+            "-(-9223372036854775807 - 1)",
+            "integer overflow: -(-9223372036854775808)",
+        ),
+        (
+            "(-9223372036854775807 - 1) / -1",
+            "integer overflow: -9223372036854775808 / -1",
+        ),
+        ("1 / 0", "division by zero"),
+        ("1 % 0", "division by zero"),
+        (
+            "(-9223372036854775807 - 1) % -1",
+            "integer overflow: -9223372036854775808 % -1",
+        ),
+        ("true & false", "unknown operator: BOOLEAN & BOOLEAN"),
+        (
+            "if (10 > 1) { true + false; }",
+            "unknown operator: BOOLEAN + BOOLEAN",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        match assert_eval(input) {
+            Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+            Err(err) => assert_eq!(err.to_string(), expected, "input: {}", input),
+        }
+    }
+
     Ok(())
 }
 
@@ -157,6 +242,12 @@ fn test_eval_if_else_expressions() -> Result<(), Error> {
         ("if (1 > 2) { 10 }", None),
         ("if (1 > 2) { 10 } else { 20 }", Some(20)),
         ("if (1 < 2) { 10 } else { 20 }", Some(10)),
+        ("if (true) { }", None),
+        ("if (false) { 10 } else { }", None),
+        ("if (0) { 10 } else { 20 }", Some(10)),
+        ("if (\"\") { 10 } else { 20 }", Some(10)),
+        ("if ([]) { 10 } else { 20 }", Some(10)),
+        ("if (if (false) { 1 }) { 10 } else { 20 }", Some(20)),
     ];
 
     for (input, expected) in tests {
@@ -172,6 +263,24 @@ fn test_eval_if_else_expressions() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_eval_ternary_expressions() -> Result<(), Error> {
+    let tests = vec![
+        ("true ? 10 : 20", 10),
+        ("false ? 10 : 20", 20),
+        ("1 > 2 ? 10 : 2 > 1 ? 20 : 30", 20),
+        ("(1 < 2 ? 3 : 4) * 5", 15),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = assert_eval(input)?;
+
+        assert_integer_literal_object(evaluated, expected)?;
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_eval_index_expressions() -> Result<(), Error> {
     let tests = vec![
@@ -180,6 +289,8 @@ fn test_eval_index_expressions() -> Result<(), Error> {
         ("[1, 2, 3][2]", 3),
         ("$i = 0; [1][$i];", 1),
         ("[1, 2, 3][1 + 1];", 3),
+        ("[1, 2, 3][-1]", 3),
+        ("[1, 2, 3][-3]", 1),
         ("$myArray = [1, 2, 3]; $myArray[2];", 3),
         (
             "$myArray = [1, 2, 3]; $myArray[0] + $myArray[1] + $myArray[2];",
@@ -193,6 +304,87 @@ fn test_eval_index_expressions() -> Result<(), Error> {
         assert_integer_literal_object(evaluated, expected)?;
     }
 
+    for input in ["[][0]", "[][-1]", "[1, 2, 3][3]", "[1, 2, 3][-4]"] {
+        assert_eq!(*assert_eval(input)?, Object::Null, "{}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_string_indexing_and_slicing() -> Result<(), Error> {
+    let tests = vec![
+        ("\"hello\"[1]", "e"),
+        ("\"hello\"[-1]", "o"),
+        ("\"héllo\"[1]", "é"),
+        ("\"hello\"[5]", "null"),
+        ("\"hello\"[1:3]", "el"),
+        ("\"hello\"[:2]", "he"),
+        ("\"hello\"[3:]", "lo"),
+        ("\"hello\"[-3:-1]", "ll"),
+        // Out-of-range bounds are clamped rather than reported.
+        ("\"hello\"[2:100]", "llo"),
+        ("\"hello\"[-100:2]", "he"),
+        ("\"hello\"[4:1]", ""),
+        ("[1, 2, 3, 4][1:3]", "[2, 3]"),
+        ("[1, 2, 3][:]", "[1, 2, 3]"),
+        ("[][1:2]", "[]"),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = assert_eval(input)?;
+
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+    }
+
+    assert_eq!(*assert_eval("\"hello\"[0]")?, Object::Char('h'));
+
+    let errors = vec![
+        (
+            "1[0:1]",
+            "unsupported types for slice: INTEGER[INTEGER:INTEGER]",
+        ),
+        (
+            "\"hello\"[true:]",
+            "unsupported types for slice: STRING[BOOLEAN:NULL]",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        match assert_eval(input) {
+            Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_hash_index_expressions() -> Result<(), Error> {
+    let tests = vec![
+        (r#"{"foo": 5}["foo"]"#, Some(5)),
+        (r#"{"foo": 5}["bar"]"#, None),
+        (r#"$key = "foo"; {"foo": 5}[$key]"#, Some(5)),
+        (r#"{}["foo"]"#, None),
+        ("{5: 5}[5]", Some(5)),
+        ("{true: 5}[true]", Some(5)),
+        ("{false: 5}[false]", Some(5)),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = assert_eval(input)?;
+
+        if let Some(expected) = expected {
+            assert_integer_literal_object(evaluated, expected)?;
+        } else {
+            assert_eq!(*evaluated, Object::Null);
+        }
+    }
+
+    assert!(assert_eval("{[1]: 2}").is_err());
+    assert!(assert_eval(r#"{"a": 1}[[1]]"#).is_err());
+
     Ok(())
 }
 
@@ -202,6 +394,9 @@ fn test_eval_assertions() -> Result<(), Error> {
         ("$a = 5; $a;", 5),
         ("$a = 5 * 5; $a;", 25),
         ("$a = 5; $a;", 5),
+        ("$a = $b = 5; $a + $b;", 10),
+        ("($a = 2) * 3;", 6),
+        ("$a = 1; $b = ($a = $a + 1) + $a; $b;", 4),
     ];
 
     for (input, expected) in tests {
@@ -215,7 +410,17 @@ fn test_eval_assertions() -> Result<(), Error> {
 
 #[test]
 fn test_eval_functions() -> Result<(), Error> {
-    let tests = vec![("function ($x) { $x + 2; }(2);", 4)];
+    let tests = vec![
+        ("function ($x) { $x + 2; }(2);", 4),
+        (
+            "$factorial = function ($n) { if ($n < 2) { return 1; } $n * $factorial($n - 1); }; $factorial(5);",
+            120,
+        ),
+        (
+            "$adder = function ($a) { function ($b) { $a + $b; }; }; $addTwo = $adder(2); $addTwo(3);",
+            5,
+        ),
+    ];
 
     for (input, expected) in tests {
         let evaluated = assert_eval(input)?;
@@ -226,6 +431,183 @@ fn test_eval_functions() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_eval_function_arity() -> Result<(), Error> {
+    let tests = vec![
+        (
+            "function ($x) { $x; }();",
+            "wrong number of arguments: want=1, got=0",
+        ),
+        (
+            "function ($x, $y) { $x + $y; }(1, 2, 3);",
+            "wrong number of arguments: want=2, got=3",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        match assert_eval(input) {
+            Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_builtin_functions() -> Result<(), Error> {
+    let tests = vec![
+        ("len(\"\")", Object::Integer(0)),
+        ("len(\"four\")", Object::Integer(4)),
+        ("len(\"héllo\")", Object::Integer(5)),
+        ("len([1, 2, 3])", Object::Integer(3)),
+        ("first([1, 2, 3])", Object::Integer(1)),
+        ("first([])", Object::Null),
+        ("last([1, 2, 3])", Object::Integer(3)),
+        ("last([])", Object::Null),
+        (
+            "rest([1, 2, 3])",
+            Object::Array(vec![
+                Rc::new(Object::Integer(2)),
+                Rc::new(Object::Integer(3)),
+            ]),
+        ),
+        ("rest([])", Object::Null),
+        (
+            "push([], 1)",
+            Object::Array(vec![Rc::new(Object::Integer(1))]),
+        ),
+        // push returns a new array and leaves its argument alone.
+        (
+            "$a = [1]; $b = push($a, 2); $a",
+            Object::Array(vec![Rc::new(Object::Integer(1))]),
+        ),
+        (
+            "$len = function ($a) { len($a); }; $len([1, 2]);",
+            Object::Integer(2),
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = assert_eval(input)?;
+
+        assert_eq!(*evaluated, expected, "input: {}", input);
+    }
+
+    let errors = vec![
+        ("len(1)", "argument to `len` not supported, got INTEGER"),
+        (
+            "len(\"one\", \"two\")",
+            "wrong number of arguments to `len`: want=1, got=2",
+        ),
+        (
+            "first(1)",
+            "argument to `first` must be an array, got INTEGER",
+        ),
+        (
+            "last(true)",
+            "argument to `last` must be an array, got BOOLEAN",
+        ),
+        (
+            "rest(\"abc\")",
+            "argument to `rest` must be an array, got STRING",
+        ),
+        (
+            "push(1, 1)",
+            "argument to `push` must be an array, got INTEGER",
+        ),
+        (
+            "push([])",
+            "wrong number of arguments to `push`: want=2, got=1",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        match assert_eval(input) {
+            Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+            Err(err) => assert_eq!(err.to_string(), expected, "input: {}", input),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_ranges() -> Result<(), Error> {
+    let integers = |values: &[i64]| {
+        Object::Array(
+            values
+                .iter()
+                .map(|value| Rc::new(Object::Integer(*value)))
+                .collect(),
+        )
+    };
+
+    let tests = vec![
+        (
+            "0..5",
+            Object::Range {
+                start: 0,
+                end: 5,
+                inclusive: false,
+            },
+        ),
+        ("to_array(0..5)", integers(&[0, 1, 2, 3, 4])),
+        ("to_array(0..=5)", integers(&[0, 1, 2, 3, 4, 5])),
+        (
+            "$n = 3; to_array(-$n..$n - 1)",
+            integers(&[-3, -2, -1, 0, 1]),
+        ),
+        ("to_array(5..0)", integers(&[])),
+        ("to_array(2..2)", integers(&[])),
+        ("to_array(2..=2)", integers(&[2])),
+        ("to_array([1])", integers(&[1])),
+        ("len(0..5)", Object::Integer(5)),
+        ("len(0..=5)", Object::Integer(6)),
+        ("len(5..0)", Object::Integer(0)),
+        ("(10..20)[3]", Object::Integer(13)),
+        ("(10..20)[-1]", Object::Integer(19)),
+        ("(10..=20)[-1]", Object::Integer(20)),
+        ("(10..20)[10]", Object::Null),
+        ("0..5 == 0..5", Object::Boolean(true)),
+        ("0..5 == 0..=5", Object::Boolean(false)),
+    ];
+
+    for (input, expected) in tests {
+        let evaluated = assert_eval(input)?;
+
+        assert_eq!(*evaluated, expected, "input: {}", input);
+    }
+
+    let errors = vec![
+        ("0..1.5", "unsupported types for range: INTEGER..FLOAT"),
+        ("\"a\"..=1", "unsupported types for range: STRING..INTEGER"),
+        (
+            "to_array(1)",
+            "argument to `to_array` must be a range or an array, got INTEGER",
+        ),
+        (
+            "len((-9223372036854775807 - 1)..9223372036854775807)",
+            "range too long for `len`: -9223372036854775808..9223372036854775807",
+        ),
+        (
+            "to_array(0..9223372036854775807)",
+            "range too long for `to_array`: 0..9223372036854775807 has 9223372036854775807 elements, the limit is 16777216",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        match assert_eval(input) {
+            Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+            Err(err) => assert_eq!(err.to_string(), expected, "input: {}", input),
+        }
+    }
+
+    assert_eq!(assert_eval("0..=5")?.to_string(), "0..=5");
+
+    Ok(())
+}
+
 fn assert_eval(input: &str) -> Result<Rc<Object>, Error> {
     let env = Rc::new(RefCell::new(Environment::new()));
 
@@ -270,3 +652,42 @@ fn assert_string_literal_object(object: Rc<Object>, expected: &str) -> Result<()
         _ => Err(Error::msg("Object is not a String.")),
     }
 }
+
+#[test]
+fn test_eval_max_call_depth() -> Result<(), Error> {
+    let unbounded = "$f = function($n) { $f($n + 1) }; $f(0)";
+
+    // Unoptimised builds need more than the default test thread stack to
+    // reach the default depth.
+    let result = std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(move || {
+            assert_eval(unbounded)
+                .map(|evaluated| evaluated.to_string())
+                .map_err(|err| err.to_string())
+        })?
+        .join()
+        .expect("evaluation thread panicked");
+
+    assert_eq!(
+        result,
+        Err("stack overflow: max call depth exceeded".to_string())
+    );
+
+    let count_down = "$f = function($n) { if ($n > 0) { $f($n - 1) } else { $n } };";
+
+    set_max_call_depth(10);
+
+    match assert_eval(&format!("{} $f(10)", count_down)) {
+        Ok(evaluated) => panic!("expected an error, got {}", evaluated),
+        Err(err) => assert_eq!(err.to_string(), "stack overflow: max call depth exceeded"),
+    }
+
+    // The depth unwinds after an error, so the limit applies afresh.
+    let evaluated = assert_eval(&format!("{} $f(9)", count_down))?;
+    assert_integer_literal_object(evaluated, 0)?;
+
+    set_max_call_depth(DEFAULT_MAX_CALL_DEPTH);
+
+    Ok(())
+}
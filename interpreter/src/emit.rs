@@ -0,0 +1,42 @@
+use anyhow::{Error, Result};
+
+use compiler::Compiler;
+use lexer::Lexer;
+use parser::{ast::Node, Parser};
+
+/// The compilation stage `compile_source` stops at and prints.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CompileTarget {
+    /// The parsed program as an indented S-expression.
+    Ast,
+    /// The disassembled bytecode of the main program.
+    Bytecode,
+}
+
+impl std::str::FromStr for CompileTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ast" => Ok(CompileTarget::Ast),
+            "bytecode" => Ok(CompileTarget::Bytecode),
+            _ => Err(Error::msg(format!("unknown emit target: {}", s))),
+        }
+    }
+}
+
+/// Lexes and parses `source`, runs it through the stages `target` needs and
+/// returns that stage's textual representation.
+pub fn compile_source(source: &str, target: CompileTarget) -> Result<String, Error> {
+    let mut parser = Parser::new(Lexer::new(source));
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let node = Node::Program(program);
+
+    match target {
+        CompileTarget::Ast => Ok(node.pretty(0)),
+        CompileTarget::Bytecode => Ok(Compiler::new().compile(&node)?.disassemble()),
+    }
+}
@@ -1,9 +1,10 @@
 use std::env;
 
-pub mod repl;
-
 use anyhow::{Error, Result};
-use repl::init_repl;
+use interpreter::{
+    emit::{compile_source, CompileTarget},
+    repl::{init_bytecode_repl, init_repl},
+};
 
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -15,9 +16,23 @@ fn main() -> Result<(), Error> {
 
     let file_path = match args.len() {
         1 => None,
+        2 if args[1] == "--bytecode" => {
+            println!("Loading bytecode REPL...");
+            return init_bytecode_repl();
+        }
         2 => Some(args[1].clone()),
+        3 if args[1].starts_with("--emit=") => {
+            let target = args[1]["--emit=".len()..].parse::<CompileTarget>()?;
+            let file = std::fs::read_to_string(&args[2])?;
+
+            println!("{}", compile_source(&file, target)?);
+            return Ok(());
+        }
         _ => {
-            println!("Usage: {} [FILE]", NAME);
+            println!(
+                "Usage: {} [--bytecode | --emit=ast|bytecode FILE | FILE]",
+                NAME
+            );
             std::process::exit(1);
         }
     };
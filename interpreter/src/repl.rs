@@ -1,4 +1,7 @@
-use std::rc::Rc;
+use std::{
+    io::{BufRead, Write},
+    rc::Rc,
+};
 
 use anyhow::{Error, Result};
 
@@ -41,7 +44,13 @@ pub fn init_repl() -> Result<(), Error> {
 
                 let mut compiler = Compiler::new_with_state(constants, symbol_table);
 
-                match compiler.compile(&Node::Program(program)) {
+                let result = compiler.compile(&Node::Program(program));
+
+                for warning in compiler.warnings() {
+                    println!("{}", warning);
+                }
+
+                match result {
                     Ok(bytecode) => {
                         let mut vm = Vm::new_with_globals_store(bytecode, globals);
 
@@ -81,3 +90,95 @@ pub fn init_repl() -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Compiles REPL input line by line, keeping the symbol table and constants
+/// from earlier lines so later ones can refer to their globals.
+pub struct BytecodeRepl {
+    constants: Vec<Rc<Object>>,
+    symbol_table: SymbolTable,
+}
+
+impl BytecodeRepl {
+    pub fn new() -> Self {
+        Self {
+            constants: vec![],
+            symbol_table: SymbolTable::new(),
+        }
+    }
+
+    /// Compiles a single line and returns its disassembled bytecode, or the
+    /// parse or compile errors it produced.
+    pub fn compile_line(&mut self, line: &str) -> Result<String, Error> {
+        let lexer = Lexer::new(line);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        let mut compiler = Compiler::new_with_state(
+            std::mem::take(&mut self.constants),
+            std::mem::replace(&mut self.symbol_table, SymbolTable::new()),
+        );
+
+        let result = compiler.compile(&Node::Program(program));
+
+        self.symbol_table = compiler.symbol_table;
+        self.constants = compiler.constants;
+
+        Ok(result?.disassemble())
+    }
+}
+
+impl Default for BytecodeRepl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the bytecode REPL over `input`, writing the disassembly of each line
+/// (or its errors) to `output`.
+pub fn run_bytecode_repl<R: BufRead, W: Write>(input: R, mut output: W) -> Result<(), Error> {
+    let mut repl = BytecodeRepl::new();
+
+    for line in input.lines() {
+        match repl.compile_line(&line?) {
+            Ok(disassembly) => write!(output, "{}", disassembly)?,
+            Err(err) => writeln!(output, "Error: {}", err)?,
+        }
+    }
+
+    Ok(())
+}
+
+pub fn init_bytecode_repl() -> Result<(), Error> {
+    let mut rl = rustyline::DefaultEditor::new()?;
+    let mut repl = BytecodeRepl::new();
+
+    loop {
+        let readline = rl.readline(PROMPT);
+
+        match readline {
+            Ok(line) => {
+                rl.add_history_entry(line.as_str())?;
+
+                match repl.compile_line(&line) {
+                    Ok(disassembly) => print!("{}", disassembly),
+                    Err(err) => println!("Error: {}", err),
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+            }
+        }
+    }
+
+    Ok(())
+}
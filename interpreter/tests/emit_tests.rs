@@ -0,0 +1,40 @@
+use anyhow::Error;
+use interpreter::emit::{compile_source, CompileTarget};
+
+#[test]
+fn test_compile_source() -> Result<(), Error> {
+    let tests = [
+        (CompileTarget::Ast, "(program\n  (+\n    1\n    2))"),
+        (
+            CompileTarget::Bytecode,
+            "0000 OpConst 0\n0003 OpConst 1\n0006 OpAdd\n0007 OpPop\n",
+        ),
+    ];
+
+    for (target, expected) in tests {
+        assert_eq!(compile_source("1 + 2", target)?, expected, "{:?}", target);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compile_target_from_str() -> Result<(), Error> {
+    assert_eq!("ast".parse::<CompileTarget>()?, CompileTarget::Ast);
+    assert_eq!(
+        "bytecode".parse::<CompileTarget>()?,
+        CompileTarget::Bytecode
+    );
+
+    match "llvm".parse::<CompileTarget>() {
+        Ok(target) => panic!("expected an error, got {:?}", target),
+        Err(e) => assert_eq!(e.to_string(), "unknown emit target: llvm"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compile_source_reports_parse_errors() {
+    assert!(compile_source("1 +", CompileTarget::Ast).is_err());
+}
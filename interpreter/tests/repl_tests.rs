@@ -0,0 +1,26 @@
+use anyhow::Error;
+use interpreter::repl::run_bytecode_repl;
+
+#[test]
+fn test_bytecode_repl() -> Result<(), Error> {
+    let input = "$x = 5;\n$x + 1;\n$x = ;\n$y;\n$x;\n";
+    let mut output = Vec::new();
+
+    run_bytecode_repl(input.as_bytes(), &mut output)?;
+
+    let expected = "0000 OpConst 0
+0003 OpSetGlobal 0
+0000 OpGetGlobal 0
+0003 OpConst 1
+0006 OpAdd
+0007 OpPop
+Error: Parser has errors: Expected an expression after =: No prefix parse function for Semicolon at 1:6
+Error: compiler: undefined variable '$y' at 1:1
+0000 OpGetGlobal 0
+0003 OpPop
+";
+
+    assert_eq!(String::from_utf8(output)?, expected);
+
+    Ok(())
+}
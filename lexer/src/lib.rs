@@ -2,11 +2,22 @@ use token::{Token, TokenType};
 
 pub mod token;
 
+/// Turns Pine source into tokens.
+///
+/// `//` line comments and `/* ... */` block comments are skipped. Block
+/// comments don't nest: the first `*/` closes the comment, and a block comment
+/// left open at the end of the input lexes as an `Illegal` token.
 pub struct Lexer<'a> {
     input: &'a str,
     position: usize,
     read_position: usize,
     ch: Option<char>,
+    line: usize,
+    column: usize,
+    /// Set once the iterator has yielded the `Eof` token.
+    finished: bool,
+    /// Set once a comment has been skipped.
+    skipped_comment: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -16,6 +27,10 @@ impl<'a> Lexer<'a> {
             position: 0,
             read_position: 0,
             ch: None,
+            line: 1,
+            column: 0,
+            finished: false,
+            skipped_comment: false,
         };
 
         lexer.read_char();
@@ -24,8 +39,47 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+        loop {
+            self.skip_whitespace();
 
+            let (line, column) = (self.line, self.column);
+
+            match (self.ch, self.peek_char()) {
+                (Some('/'), '/') => {
+                    self.skipped_comment = true;
+                    self.skip_line_comment();
+                }
+                (Some('/'), '*') => {
+                    self.skipped_comment = true;
+
+                    if !self.skip_block_comment() {
+                        return Token {
+                            line,
+                            column,
+                            ..Token::new(TokenType::Illegal, "unterminated block comment")
+                        };
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let (line, column) = (self.line, self.column);
+
+        Token {
+            line,
+            column,
+            ..self.read_token()
+        }
+    }
+
+    /// Whether a comment has been skipped so far. Comments aren't tokens,
+    /// so this is the only trace they leave.
+    pub fn skipped_comment(&self) -> bool {
+        self.skipped_comment
+    }
+
+    fn read_token(&mut self) -> Token {
         let (token_type, literal) = match self.ch {
             Some('=') => {
                 if self.peek_char() == '=' {
@@ -38,20 +92,61 @@ impl<'a> Lexer<'a> {
             Some('"') => {
                 self.read_char();
 
-                let literal = self.read_string();
+                match self.read_string() {
+                    Ok(literal) => (TokenType::String, literal),
+                    Err(message) => (TokenType::Illegal, message),
+                }
+            }
+            Some('\'') => {
+                self.read_char();
 
-                (TokenType::String, literal)
+                match self.read_char_literal() {
+                    Ok(ch) => (TokenType::Char, ch.to_string()),
+                    Err(message) => (TokenType::Illegal, message),
+                }
             }
             Some(';') => (TokenType::Semicolon, ";".to_string()),
             Some('(') => (TokenType::LParen, "(".to_string()),
             Some(')') => (TokenType::RParen, ")".to_string()),
             Some(',') => (TokenType::Comma, ",".to_string()),
-            Some('+') => (TokenType::Plus, "+".to_string()),
+            Some(':') => (TokenType::Colon, ":".to_string()),
+            Some('.') if self.peek_char() == '.' => {
+                self.read_char();
+
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::DotDotEq, "..=".to_string())
+                } else {
+                    (TokenType::DotDot, "..".to_string())
+                }
+            }
+            Some('?') => (TokenType::Question, "?".to_string()),
+            Some('+') => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::PlusAssign, "+=".to_string())
+                } else if self.peek_char() == '+' {
+                    self.read_char();
+                    (TokenType::Increment, "++".to_string())
+                } else {
+                    (TokenType::Plus, "+".to_string())
+                }
+            }
             Some('{') => (TokenType::LBrace, "{".to_string()),
             Some('}') => (TokenType::RBrace, "}".to_string()),
             Some('[') => (TokenType::LBracket, "[".to_string()),
             Some(']') => (TokenType::RBracket, "]".to_string()),
-            Some('-') => (TokenType::Minus, "-".to_string()),
+            Some('-') => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::MinusAssign, "-=".to_string())
+                } else if self.peek_char() == '-' {
+                    self.read_char();
+                    (TokenType::Decrement, "--".to_string())
+                } else {
+                    (TokenType::Minus, "-".to_string())
+                }
+            }
             Some('!') => {
                 if self.peek_char() == '=' {
                     self.read_char();
@@ -60,10 +155,63 @@ impl<'a> Lexer<'a> {
                     (TokenType::Bang, "!".to_string())
                 }
             }
-            Some('/') => (TokenType::Slash, "/".to_string()),
-            Some('*') => (TokenType::Asterisk, "*".to_string()),
-            Some('<') => (TokenType::Lt, "<".to_string()),
-            Some('>') => (TokenType::Gt, ">".to_string()),
+            Some('&') => {
+                if self.peek_char() == '&' {
+                    self.read_char();
+                    (TokenType::And, "&&".to_string())
+                } else {
+                    (TokenType::Ampersand, "&".to_string())
+                }
+            }
+            Some('|') => {
+                if self.peek_char() == '|' {
+                    self.read_char();
+                    (TokenType::Or, "||".to_string())
+                } else {
+                    (TokenType::Pipe, "|".to_string())
+                }
+            }
+            Some('^') => (TokenType::Caret, "^".to_string()),
+            Some('~') => (TokenType::Tilde, "~".to_string()),
+            Some('/') => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::SlashAssign, "/=".to_string())
+                } else {
+                    (TokenType::Slash, "/".to_string())
+                }
+            }
+            Some('*') => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::AsteriskAssign, "*=".to_string())
+                } else {
+                    (TokenType::Asterisk, "*".to_string())
+                }
+            }
+            Some('%') => (TokenType::Percent, "%".to_string()),
+            Some('<') => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::LtEq, "<=".to_string())
+                } else if self.peek_char() == '<' {
+                    self.read_char();
+                    (TokenType::ShiftLeft, "<<".to_string())
+                } else {
+                    (TokenType::Lt, "<".to_string())
+                }
+            }
+            Some('>') => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    (TokenType::GtEq, ">=".to_string())
+                } else if self.peek_char() == '>' {
+                    self.read_char();
+                    (TokenType::ShiftRight, ">>".to_string())
+                } else {
+                    (TokenType::Gt, ">".to_string())
+                }
+            }
             Some('$') => {
                 self.read_char();
 
@@ -72,10 +220,7 @@ impl<'a> Lexer<'a> {
                         let identifier = self.read_identifier();
                         let var_name = format!("${}", identifier);
 
-                        return Token {
-                            token_type: TokenType::Ident,
-                            literal: var_name,
-                        };
+                        return Token::new(TokenType::Ident, &var_name);
                     } else {
                         (TokenType::Illegal, "$".to_string())
                     }
@@ -87,17 +232,9 @@ impl<'a> Lexer<'a> {
                 if ch.is_alphabetic() {
                     let literal = self.read_identifier();
 
-                    return Token {
-                        token_type: TokenType::lookup_ident(&literal),
-                        literal,
-                    };
+                    return Token::new(TokenType::lookup_ident(&literal), &literal);
                 } else if ch.is_ascii_digit() {
-                    let literal = self.read_number();
-
-                    return Token {
-                        token_type: TokenType::Int,
-                        literal,
-                    };
+                    return self.read_number();
                 } else {
                     (TokenType::Illegal, ch.to_string())
                 }
@@ -107,10 +244,7 @@ impl<'a> Lexer<'a> {
 
         self.read_char();
 
-        Token {
-            token_type,
-            literal,
-        }
+        Token::new(token_type, &literal)
     }
 
     fn peek_char(&self) -> char {
@@ -122,6 +256,14 @@ impl<'a> Lexer<'a> {
     }
 
     fn read_char(&mut self) -> () {
+        match self.ch {
+            Some('\n') => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1,
+        }
+
         if self.read_position >= self.input.len() {
             self.ch = None;
         } else {
@@ -136,7 +278,7 @@ impl<'a> Lexer<'a> {
         let position = self.position;
 
         while match self.ch {
-            Some(ch) => ch.is_alphabetic(),
+            Some(ch) => ch.is_alphabetic() || ch == '_',
             _ => false,
         } {
             self.read_char();
@@ -145,30 +287,224 @@ impl<'a> Lexer<'a> {
         self.input[position..self.position].to_owned()
     }
 
-    fn read_number(&mut self) -> String {
+    fn read_number(&mut self) -> Token {
         let position = self.position;
+        let mut token_type = TokenType::Int;
+
+        if self.ch == Some('0') {
+            let radix = match self.peek_char() {
+                'x' | 'X' => Some(16),
+                'o' | 'O' => Some(8),
+                'b' | 'B' => Some(2),
+                _ => None,
+            };
+
+            if let Some(radix) = radix {
+                return self.read_prefixed_integer(radix);
+            }
+        }
+
+        self.read_digits();
+
+        if self.input[position..self.position].ends_with('_') {
+            return Token::new(
+                TokenType::Illegal,
+                &format!(
+                    "malformed integer literal `{}`",
+                    &self.input[position..self.position]
+                ),
+            );
+        }
+
+        // A '.' only belongs to the number when a digit follows it.
+        let next_is_digit = self
+            .input
+            .as_bytes()
+            .get(self.read_position)
+            .is_some_and(|byte| byte.is_ascii_digit());
+
+        if self.ch == Some('.') && next_is_digit {
+            token_type = TokenType::Float;
+
+            self.read_char();
+            self.read_digits();
+        }
+
+        if matches!(self.ch, Some('e' | 'E')) {
+            token_type = TokenType::Float;
+
+            self.read_char();
+
+            if matches!(self.ch, Some('+' | '-')) {
+                self.read_char();
+            }
+
+            let exponent_position = self.position;
+
+            self.read_digits();
+
+            let exponent = &self.input[exponent_position..self.position];
+
+            if exponent.is_empty() || exponent.starts_with('_') || exponent.ends_with('_') {
+                return Token::new(
+                    TokenType::Illegal,
+                    &format!(
+                        "malformed float literal `{}`",
+                        &self.input[position..self.position]
+                    ),
+                );
+            }
+        }
+
+        Token::new(token_type, &self.input[position..self.position])
+    }
+
+    /// Reads a `0x`, `0o` or `0b` integer. Everything alphanumeric after the
+    /// prefix is taken as part of the literal so `0xG` is rejected as a whole
+    /// rather than lexed as `0` followed by an identifier.
+    fn read_prefixed_integer(&mut self, radix: u32) -> Token {
+        let position = self.position;
+
+        // Step over the "0x" prefix.
+        self.read_char();
+        self.read_char();
+
+        let digits_position = self.position;
 
         while match self.ch {
-            Some(ch) => ch.is_numeric(),
+            Some(ch) => ch.is_ascii_alphanumeric() || ch == '_',
             _ => false,
         } {
             self.read_char();
         }
 
-        self.input[position..self.position].to_owned()
+        let literal = &self.input[position..self.position];
+        let digits = &self.input[digits_position..self.position];
+
+        let is_valid = digits.chars().any(|ch| ch != '_')
+            && !digits.ends_with('_')
+            && digits.chars().all(|ch| ch == '_' || ch.is_digit(radix));
+
+        if is_valid {
+            Token::new(TokenType::Int, literal)
+        } else {
+            Token::new(
+                TokenType::Illegal,
+                &format!("malformed integer literal `{}`", literal),
+            )
+        }
     }
 
-    fn read_string(&mut self) -> String {
-        let position = self.position;
+    /// Reads decimal digits along with any `_` separators between them.
+    fn read_digits(&mut self) {
+        while match self.ch {
+            Some(ch) => ch.is_ascii_digit() || ch == '_',
+            _ => false,
+        } {
+            self.read_char();
+        }
+    }
+
+    /// Reads up to the closing quote, decoding escape sequences. An unknown
+    /// escape still consumes the rest of the string so lexing can carry on
+    /// after it.
+    fn read_string(&mut self) -> Result<String, String> {
+        let mut bytes = vec![];
+        let mut error = None;
+
+        loop {
+            match self.ch {
+                Some('"') => break,
+                Some('\\') => {
+                    self.read_char();
+
+                    let byte = match self.ch {
+                        Some(ch) => match unescape(ch) {
+                            Some(unescaped) => unescaped as u8,
+                            None => {
+                                error.get_or_insert(format!("unknown escape sequence \\{}", ch));
+                                ch as u8
+                            }
+                        },
+                        None => return Err("unterminated string".to_string()),
+                    };
+
+                    bytes.push(byte);
+                }
+                Some(ch) => bytes.push(ch as u8),
+                None => return Err("unterminated string".to_string()),
+            }
 
+            self.read_char();
+        }
+
+        match error {
+            Some(message) => Err(message),
+            None => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+
+    /// Reads a single, possibly escaped, character and the closing quote.
+    fn read_char_literal(&mut self) -> Result<char, String> {
+        let ch = match self.ch {
+            Some('\\') => {
+                self.read_char();
+
+                match self.ch {
+                    Some(ch) => unescape(ch).ok_or(format!("unknown escape sequence \\{}", ch))?,
+                    None => return Err("unterminated char literal".to_string()),
+                }
+            }
+            Some('\'') => return Err("empty char literal".to_string()),
+            Some(_) => {
+                // The lexer walks the input a byte at a time, so step over the
+                // remaining bytes of a multi-byte character here.
+                let ch = self.input[self.position..].chars().next().unwrap();
+
+                for _ in 1..ch.len_utf8() {
+                    self.read_char();
+                }
+
+                ch
+            }
+            None => return Err("unterminated char literal".to_string()),
+        };
+
+        self.read_char();
+
+        match self.ch {
+            Some('\'') => Ok(ch),
+            _ => Err("unterminated char literal".to_string()),
+        }
+    }
+
+    fn skip_line_comment(&mut self) {
         while match self.ch {
-            Some(ch) => ch != '"',
+            Some(ch) => ch != '\n',
             _ => false,
         } {
             self.read_char();
         }
+    }
 
-        self.input[position..self.position].to_owned()
+    /// Skips past the closing `*/`, returning false if the input ends first.
+    fn skip_block_comment(&mut self) -> bool {
+        // Step over the opening "/*".
+        self.read_char();
+        self.read_char();
+
+        loop {
+            match (self.ch, self.peek_char()) {
+                (Some('*'), '/') => {
+                    self.read_char();
+                    self.read_char();
+
+                    return true;
+                }
+                (Some(_), _) => self.read_char(),
+                (None, _) => return false,
+            }
+        }
     }
 
     fn skip_whitespace(&mut self) -> () {
@@ -180,3 +516,33 @@ impl<'a> Lexer<'a> {
         }
     }
 }
+
+/// Yields every token up to and including the `Eof` token, then stops.
+/// Calling `next_token` directly instead keeps returning `Eof`.
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if self.finished {
+            return None;
+        }
+
+        let token = self.next_token();
+        self.finished = token.token_type == TokenType::Eof;
+
+        Some(token)
+    }
+}
+
+/// Maps the character after a `\` in a string or char literal to the
+/// character it stands for.
+fn unescape(ch: char) -> Option<char> {
+    match ch {
+        'n' => Some('\n'),
+        't' => Some('\t'),
+        'r' => Some('\r'),
+        '0' => Some('\0'),
+        '\\' | '"' | '\'' => Some(ch),
+        _ => None,
+    }
+}
@@ -1,19 +1,37 @@
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenType {
+    /// An unrecognised character, or a malformed string, char or number
+    /// literal in which case the literal describes what went wrong.
+    #[default]
     Illegal,
     Eof,
 
     Ident,
     Int,
+    Float,
     False,
     True,
+    Null,
 
     Eq,
     NotEq,
+    And,
+    Or,
 
     Assign,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    Increment,
+    Decrement,
     Plus,
     Comma,
+    Colon,
+    DotDot,
+    DotDotEq,
+    Question,
     Semicolon,
     LParen,
     RParen,
@@ -24,24 +42,62 @@ pub enum TokenType {
     Minus,
     Slash,
     Asterisk,
+    Percent,
     Lt,
     Gt,
+    LtEq,
+    GtEq,
     Bang,
+    Tilde,
+
+    Ampersand,
+    Pipe,
+    Caret,
+    ShiftLeft,
+    ShiftRight,
 
     LBracket,
     RBracket,
 
     String,
+    Char,
 
     If,
     Else,
     Return,
+    While,
+    For,
+    Break,
+    Continue,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// A lexed token and the 1-based line and column it starts at.
+///
+/// Equality only compares the type and literal, so tokens (and the AST nodes
+/// holding them) compare the same regardless of where they appeared.
+#[derive(Clone, Debug, Default, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Token {
+    pub fn new(token_type: TokenType, literal: &str) -> Self {
+        Self {
+            token_type,
+            literal: literal.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type && self.literal == other.literal
+    }
 }
 
 impl TokenType {
@@ -50,9 +106,14 @@ impl TokenType {
             "function" => TokenType::Function,
             "true" => TokenType::True,
             "false" => TokenType::False,
+            "null" => TokenType::Null,
             "if" => TokenType::If,
             "else" => TokenType::Else,
             "return" => TokenType::Return,
+            "while" => TokenType::While,
+            "for" => TokenType::For,
+            "break" => TokenType::Break,
+            "continue" => TokenType::Continue,
             _ => TokenType::Ident,
         }
     }
@@ -71,13 +132,27 @@ impl std::fmt::Display for TokenType {
             TokenType::Eof => "Eof",
             TokenType::Ident => "Ident",
             TokenType::Int => "Int",
+            TokenType::Float => "Float",
             TokenType::True => "True",
             TokenType::False => "False",
+            TokenType::Null => "Null",
             TokenType::Eq => "Eq",
             TokenType::NotEq => "NotEq",
+            TokenType::And => "And",
+            TokenType::Or => "Or",
             TokenType::Assign => "Assign",
+            TokenType::PlusAssign => "PlusAssign",
+            TokenType::MinusAssign => "MinusAssign",
+            TokenType::AsteriskAssign => "AsteriskAssign",
+            TokenType::SlashAssign => "SlashAssign",
+            TokenType::Increment => "Increment",
+            TokenType::Decrement => "Decrement",
             TokenType::Plus => "Plus",
             TokenType::Comma => "Comma",
+            TokenType::Colon => "Colon",
+            TokenType::DotDot => "DotDot",
+            TokenType::DotDotEq => "DotDotEq",
+            TokenType::Question => "Question",
             TokenType::Semicolon => "Semicolon",
             TokenType::LParen => "LParen",
             TokenType::RParen => "RParen",
@@ -90,13 +165,27 @@ impl std::fmt::Display for TokenType {
             TokenType::Minus => "Minus",
             TokenType::Slash => "Slash",
             TokenType::Asterisk => "Asterisk",
+            TokenType::Percent => "Percent",
             TokenType::Lt => "Lt",
             TokenType::Gt => "Gt",
+            TokenType::LtEq => "LtEq",
+            TokenType::GtEq => "GtEq",
             TokenType::Bang => "Bang",
+            TokenType::Tilde => "Tilde",
+            TokenType::Ampersand => "Ampersand",
+            TokenType::Pipe => "Pipe",
+            TokenType::Caret => "Caret",
+            TokenType::ShiftLeft => "ShiftLeft",
+            TokenType::ShiftRight => "ShiftRight",
             TokenType::If => "If",
             TokenType::Else => "Else",
             TokenType::Return => "Return",
+            TokenType::While => "While",
+            TokenType::For => "For",
+            TokenType::Break => "Break",
+            TokenType::Continue => "Continue",
             TokenType::String => "String",
+            TokenType::Char => "Char",
         };
 
         write!(f, "{}", token_type)
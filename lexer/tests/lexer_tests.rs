@@ -23,138 +23,172 @@ fn test_lexer() -> Result<(), Error> {
         Token {
             token_type: TokenType::Ident,
             literal: "$five".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Assign,
             literal: "=".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Int,
             literal: "5".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Semicolon,
             literal: ";".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$ten".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Assign,
             literal: "=".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$five".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Plus,
             literal: "+".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Int,
             literal: "5".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Semicolon,
             literal: ";".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$add".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Assign,
             literal: "=".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Function,
             literal: "function".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::LParen,
             literal: "(".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$x".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Comma,
             literal: ",".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$y".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::RParen,
             literal: ")".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::LBrace,
             literal: "{".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Return,
             literal: "return".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$x".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Plus,
             literal: "+".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$y".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Semicolon,
             literal: ";".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::RBrace,
             literal: "}".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$result".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Assign,
             literal: "=".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$add".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::LParen,
             literal: "(".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$five".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Comma,
             literal: ",".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Ident,
             literal: "$ten".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::RParen,
             literal: ")".to_string(),
+            ..Default::default()
         },
         Token {
             token_type: TokenType::Semicolon,
             literal: ";".to_string(),
+            ..Default::default()
         },
     ];
 
@@ -172,3 +206,421 @@ fn test_lexer() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_float_literals() -> Result<(), Error> {
+    let tests = vec![
+        ("2.75", vec![(TokenType::Float, "2.75")]),
+        (
+            "10.0;",
+            vec![(TokenType::Float, "10.0"), (TokenType::Semicolon, ";")],
+        ),
+        ("42", vec![(TokenType::Int, "42")]),
+        ("1.", vec![(TokenType::Int, "1"), (TokenType::Illegal, ".")]),
+        ("1e10", vec![(TokenType::Float, "1e10")]),
+        ("2.5e-3", vec![(TokenType::Float, "2.5e-3")]),
+        ("6.02E+23", vec![(TokenType::Float, "6.02E+23")]),
+        (
+            "-1.5e2",
+            vec![(TokenType::Minus, "-"), (TokenType::Float, "1.5e2")],
+        ),
+        (
+            "1e",
+            vec![(TokenType::Illegal, "malformed float literal `1e`")],
+        ),
+        (
+            "1e-;",
+            vec![
+                (TokenType::Illegal, "malformed float literal `1e-`"),
+                (TokenType::Semicolon, ";"),
+            ],
+        ),
+    ];
+
+    for (input, expected_tokens) in tests {
+        let mut lexer = Lexer::new(input);
+
+        for (token_type, literal) in expected_tokens {
+            let token = lexer.next_token();
+
+            assert_eq!(token.token_type, token_type, "input: {}", input);
+            assert_eq!(token.literal, literal, "input: {}", input);
+        }
+
+        assert_eq!(lexer.next_token().token_type, TokenType::Eof);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_logical_operators() -> Result<(), Error> {
+    let input = "&& || & | ^ ~ << >> <= >=";
+
+    let expected_tokens = vec![
+        (TokenType::And, "&&"),
+        (TokenType::Or, "||"),
+        (TokenType::Ampersand, "&"),
+        (TokenType::Pipe, "|"),
+        (TokenType::Caret, "^"),
+        (TokenType::Tilde, "~"),
+        (TokenType::ShiftLeft, "<<"),
+        (TokenType::ShiftRight, ">>"),
+        (TokenType::LtEq, "<="),
+        (TokenType::GtEq, ">="),
+        (TokenType::Eof, ""),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compound_assignment_operators() -> Result<(), Error> {
+    let input = "+= -= *= /= + - * /";
+
+    let expected_tokens = vec![
+        (TokenType::PlusAssign, "+="),
+        (TokenType::MinusAssign, "-="),
+        (TokenType::AsteriskAssign, "*="),
+        (TokenType::SlashAssign, "/="),
+        (TokenType::Plus, "+"),
+        (TokenType::Minus, "-"),
+        (TokenType::Asterisk, "*"),
+        (TokenType::Slash, "/"),
+        (TokenType::Eof, ""),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_increment_decrement_operators() -> Result<(), Error> {
+    let input = "$i++ $i-- ++-";
+
+    let expected_tokens = vec![
+        (TokenType::Ident, "$i"),
+        (TokenType::Increment, "++"),
+        (TokenType::Ident, "$i"),
+        (TokenType::Decrement, "--"),
+        (TokenType::Increment, "++"),
+        (TokenType::Minus, "-"),
+        (TokenType::Eof, ""),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_comparison_operators() -> Result<(), Error> {
+    let input = "< <= > >= = ==";
+
+    let expected_tokens = vec![
+        (TokenType::Lt, "<"),
+        (TokenType::LtEq, "<="),
+        (TokenType::Gt, ">"),
+        (TokenType::GtEq, ">="),
+        (TokenType::Assign, "="),
+        (TokenType::Eq, "=="),
+        (TokenType::Eof, ""),
+    ];
+
+    let mut lexer = Lexer::new(input);
+
+    for (token_type, literal) in expected_tokens {
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type);
+        assert_eq!(token.literal, literal);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_token_positions() -> Result<(), Error> {
+    let input = "$a = 1;\n$b = 2;\n  $c = $a + $b;";
+
+    let mut lexer = Lexer::new(input);
+
+    let mut tokens = vec![];
+
+    loop {
+        let token = lexer.next_token();
+
+        if token.token_type == TokenType::Eof {
+            break;
+        }
+
+        tokens.push(token);
+    }
+
+    let positions = tokens
+        .iter()
+        .map(|token| (token.literal.as_str(), token.line, token.column))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        positions,
+        vec![
+            ("$a", 1, 1),
+            ("=", 1, 4),
+            ("1", 1, 6),
+            (";", 1, 7),
+            ("$b", 2, 1),
+            ("=", 2, 4),
+            ("2", 2, 6),
+            (";", 2, 7),
+            ("$c", 3, 3),
+            ("=", 3, 6),
+            ("$a", 3, 8),
+            ("+", 3, 11),
+            ("$b", 3, 13),
+            (";", 3, 15),
+        ]
+    );
+
+    // Positions don't take part in equality.
+    assert_eq!(tokens[0], Token::new(TokenType::Ident, "$a"));
+
+    Ok(())
+}
+
+#[test]
+fn test_string_escapes() -> Result<(), Error> {
+    let tests = vec![
+        (r#""a\nb""#, TokenType::String, "a\nb"),
+        (r#""a\tb""#, TokenType::String, "a\tb"),
+        (r#""a\rb""#, TokenType::String, "a\rb"),
+        (r#""a\\b""#, TokenType::String, "a\\b"),
+        (r#""a\"b""#, TokenType::String, "a\"b"),
+        (r#""a\0b""#, TokenType::String, "a\0b"),
+        (r#""héllo""#, TokenType::String, "héllo"),
+        (
+            r#""a\qb""#,
+            TokenType::Illegal,
+            "unknown escape sequence \\q",
+        ),
+        (r#""abc"#, TokenType::Illegal, "unterminated string"),
+        (r#""abc\"#, TokenType::Illegal, "unterminated string"),
+    ];
+
+    for (input, token_type, literal) in tests {
+        let mut lexer = Lexer::new(input);
+
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type, "input: {}", input);
+        assert_eq!(token.literal, literal, "input: {}", input);
+
+        assert_eq!(lexer.next_token().token_type, TokenType::Eof);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_comments() -> Result<(), Error> {
+    let plain = "$a = 1;\n$b = $a / 2;\n$c = $a * $b;";
+    let commented = "// leading comment\n$a = 1; // trailing comment\n/* a block\n   comment */ $b = $a / 2;\n$c = $a /* inline */ * $b; //";
+
+    let mut plain_lexer = Lexer::new(plain);
+    let mut commented_lexer = Lexer::new(commented);
+
+    loop {
+        let expected = plain_lexer.next_token();
+        let token = commented_lexer.next_token();
+
+        assert_eq!(token, expected);
+
+        if expected.token_type == TokenType::Eof {
+            break;
+        }
+    }
+
+    assert!(!plain_lexer.skipped_comment());
+    assert!(commented_lexer.skipped_comment());
+
+    // The block comment's newline still advances the line counter.
+    let mut lexer = Lexer::new("/* one\ntwo */ $b");
+
+    assert_eq!(lexer.next_token().line, 2);
+
+    // Block comments don't nest, so the first "*/" closes the comment.
+    let mut lexer = Lexer::new("/* outer /* inner */ 1 */");
+
+    assert_eq!(lexer.next_token(), Token::new(TokenType::Int, "1"));
+    assert_eq!(lexer.next_token(), Token::new(TokenType::Asterisk, "*"));
+    assert_eq!(lexer.next_token(), Token::new(TokenType::Slash, "/"));
+
+    let mut lexer = Lexer::new("1 /* never closed");
+
+    assert_eq!(lexer.next_token(), Token::new(TokenType::Int, "1"));
+    assert_eq!(
+        lexer.next_token(),
+        Token::new(TokenType::Illegal, "unterminated block comment")
+    );
+    assert_eq!(lexer.next_token().token_type, TokenType::Eof);
+
+    Ok(())
+}
+
+#[test]
+fn test_integer_literal_bases() -> Result<(), Error> {
+    let tests = vec![
+        ("1_000_000", TokenType::Int, "1_000_000"),
+        ("0xFF", TokenType::Int, "0xFF"),
+        ("0b1010", TokenType::Int, "0b1010"),
+        ("0o755", TokenType::Int, "0o755"),
+        ("0", TokenType::Int, "0"),
+        ("0xG", TokenType::Illegal, "malformed integer literal `0xG`"),
+        ("0x", TokenType::Illegal, "malformed integer literal `0x`"),
+        (
+            "0b102",
+            TokenType::Illegal,
+            "malformed integer literal `0b102`",
+        ),
+        ("0o8", TokenType::Illegal, "malformed integer literal `0o8`"),
+        ("1_", TokenType::Illegal, "malformed integer literal `1_`"),
+        (
+            "0xF_",
+            TokenType::Illegal,
+            "malformed integer literal `0xF_`",
+        ),
+    ];
+
+    for (input, token_type, literal) in tests {
+        let mut lexer = Lexer::new(input);
+
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type, "input: {}", input);
+        assert_eq!(token.literal, literal, "input: {}", input);
+
+        assert_eq!(lexer.next_token().token_type, TokenType::Eof);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_char_literals() -> Result<(), Error> {
+    let tests = vec![
+        ("'x'", TokenType::Char, "x"),
+        (r"'\n'", TokenType::Char, "\n"),
+        (r"'\''", TokenType::Char, "'"),
+        ("'é'", TokenType::Char, "é"),
+        ("''", TokenType::Illegal, "empty char literal"),
+        ("'ab'", TokenType::Illegal, "unterminated char literal"),
+        ("'a", TokenType::Illegal, "unterminated char literal"),
+        (r"'\q'", TokenType::Illegal, "unknown escape sequence \\q"),
+    ];
+
+    for (input, token_type, literal) in tests {
+        let mut lexer = Lexer::new(input);
+
+        let token = lexer.next_token();
+
+        assert_eq!(token.token_type, token_type, "input: {}", input);
+        assert_eq!(token.literal, literal, "input: {}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_keywords() -> Result<(), Error> {
+    let tests = vec![
+        ("null", TokenType::Null),
+        ("true", TokenType::True),
+        ("false", TokenType::False),
+        ("nullable", TokenType::Ident),
+        ("to_array", TokenType::Ident),
+    ];
+
+    for (input, token_type) in tests {
+        let token = Lexer::new(input).next_token();
+
+        assert_eq!(token.token_type, token_type, "input: {}", input);
+        assert_eq!(token.literal, input, "input: {}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_range_operators() -> Result<(), Error> {
+    let tokens = Lexer::new("0..5 0..=$n 1.5..2")
+        .map(|token| (token.token_type, token.literal))
+        .collect::<Vec<_>>();
+
+    let expected = vec![
+        (TokenType::Int, "0".to_string()),
+        (TokenType::DotDot, "..".to_string()),
+        (TokenType::Int, "5".to_string()),
+        (TokenType::Int, "0".to_string()),
+        (TokenType::DotDotEq, "..=".to_string()),
+        (TokenType::Ident, "$n".to_string()),
+        (TokenType::Float, "1.5".to_string()),
+        (TokenType::DotDot, "..".to_string()),
+        (TokenType::Int, "2".to_string()),
+        (TokenType::Eof, "".to_string()),
+    ];
+
+    assert_eq!(tokens, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_lexer_iterator() -> Result<(), Error> {
+    let tokens = Lexer::new("$x = 5;")
+        .map(|token| (token.token_type, token.literal))
+        .collect::<Vec<_>>();
+
+    let expected = vec![
+        (TokenType::Ident, "$x".to_string()),
+        (TokenType::Assign, "=".to_string()),
+        (TokenType::Int, "5".to_string()),
+        (TokenType::Semicolon, ";".to_string()),
+        (TokenType::Eof, "".to_string()),
+    ];
+
+    assert_eq!(tokens, expected);
+
+    let mut lexer = Lexer::new("");
+
+    assert_eq!(
+        lexer.next().map(|token| token.token_type),
+        Some(TokenType::Eof)
+    );
+    assert_eq!(lexer.next(), None);
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use anyhow::Error;
+
+use crate::{range_len, Object};
+
+/// The most elements `to_array` builds from a range before giving up, so a
+/// huge range is an error rather than an allocation failure.
+pub const MAX_ARRAY_LEN: usize = 1 << 24;
+
+pub type BuiltinFn = fn(Vec<Rc<Object>>) -> Result<Rc<Object>, Error>;
+
+#[derive(Clone, Debug)]
+pub struct BuiltinFunction {
+    pub name: &'static str,
+    pub func: BuiltinFn,
+}
+
+impl PartialEq for BuiltinFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// Builtins in the order the compiler assigns their indexes, so the
+/// position of each entry is the operand of `OpGetBuiltin`.
+pub const BUILTINS: &[BuiltinFunction] = &[
+    BuiltinFunction {
+        name: "len",
+        func: len,
+    },
+    BuiltinFunction {
+        name: "first",
+        func: first,
+    },
+    BuiltinFunction {
+        name: "last",
+        func: last,
+    },
+    BuiltinFunction {
+        name: "rest",
+        func: rest,
+    },
+    BuiltinFunction {
+        name: "push",
+        func: push,
+    },
+    BuiltinFunction {
+        name: "to_array",
+        func: to_array,
+    },
+    BuiltinFunction {
+        name: "print",
+        func: print,
+    },
+];
+
+thread_local! {
+    static OUTPUT: RefCell<Box<dyn Write>> = RefCell::new(Box::new(std::io::stdout()));
+}
+
+/// Sends everything `print` writes on the current thread to `output`
+/// instead of stdout, returning the writer it replaces.
+pub fn set_output(output: Box<dyn Write>) -> Box<dyn Write> {
+    OUTPUT.with(|current| current.replace(output))
+}
+
+pub fn lookup(name: &str) -> Option<&'static BuiltinFunction> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+fn check_arguments(name: &str, arguments: &[Rc<Object>], want: usize) -> Result<(), Error> {
+    if arguments.len() != want {
+        return Err(Error::msg(format!(
+            "wrong number of arguments to `{}`: want={}, got={}",
+            name,
+            want,
+            arguments.len()
+        )));
+    }
+
+    Ok(())
+}
+
+fn len(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    check_arguments("len", &arguments, 1)?;
+
+    match &*arguments[0] {
+        Object::String(string) => Ok(Rc::new(Object::Integer(string.chars().count() as i64))),
+        Object::Array(elements) => Ok(Rc::new(Object::Integer(elements.len() as i64))),
+        Object::Range {
+            start,
+            end,
+            inclusive,
+        } => match i64::try_from(range_len(*start, *end, *inclusive)) {
+            Ok(length) => Ok(Rc::new(Object::Integer(length))),
+            Err(_) => Err(Error::msg(format!(
+                "range too long for `len`: {}",
+                arguments[0]
+            ))),
+        },
+        argument => Err(Error::msg(format!(
+            "argument to `len` not supported, got {}",
+            argument.type_name()
+        ))),
+    }
+}
+
+fn first(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    check_arguments("first", &arguments, 1)?;
+
+    match &*arguments[0] {
+        Object::Array(elements) => Ok(elements
+            .first()
+            .cloned()
+            .unwrap_or_else(|| Rc::new(Object::Null))),
+        argument => Err(Error::msg(format!(
+            "argument to `first` must be an array, got {}",
+            argument.type_name()
+        ))),
+    }
+}
+
+fn last(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    check_arguments("last", &arguments, 1)?;
+
+    match &*arguments[0] {
+        Object::Array(elements) => Ok(elements
+            .last()
+            .cloned()
+            .unwrap_or_else(|| Rc::new(Object::Null))),
+        argument => Err(Error::msg(format!(
+            "argument to `last` must be an array, got {}",
+            argument.type_name()
+        ))),
+    }
+}
+
+fn rest(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    check_arguments("rest", &arguments, 1)?;
+
+    match &*arguments[0] {
+        Object::Array(elements) if elements.is_empty() => Ok(Rc::new(Object::Null)),
+        Object::Array(elements) => Ok(Rc::new(Object::Array(elements[1..].to_vec()))),
+        argument => Err(Error::msg(format!(
+            "argument to `rest` must be an array, got {}",
+            argument.type_name()
+        ))),
+    }
+}
+
+fn push(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    check_arguments("push", &arguments, 2)?;
+
+    match &*arguments[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.clone();
+            elements.push(Rc::clone(&arguments[1]));
+
+            Ok(Rc::new(Object::Array(elements)))
+        }
+        argument => Err(Error::msg(format!(
+            "argument to `push` must be an array, got {}",
+            argument.type_name()
+        ))),
+    }
+}
+
+fn to_array(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    check_arguments("to_array", &arguments, 1)?;
+
+    match &*arguments[0] {
+        Object::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let length = range_len(*start, *end, *inclusive);
+
+            if length > MAX_ARRAY_LEN {
+                return Err(Error::msg(format!(
+                    "range too long for `to_array`: {} has {} elements, the limit is {}",
+                    arguments[0], length, MAX_ARRAY_LEN
+                )));
+            }
+
+            let elements = (0..length)
+                .map(|offset| Rc::new(Object::Integer(start + offset as i64)))
+                .collect();
+
+            Ok(Rc::new(Object::Array(elements)))
+        }
+        Object::Array(_) => Ok(Rc::clone(&arguments[0])),
+        argument => Err(Error::msg(format!(
+            "argument to `to_array` must be a range or an array, got {}",
+            argument.type_name()
+        ))),
+    }
+}
+
+fn print(arguments: Vec<Rc<Object>>) -> Result<Rc<Object>, Error> {
+    let line = arguments
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    OUTPUT.with(|output| writeln!(output.borrow_mut(), "{}", line))?;
+
+    Ok(Rc::new(Object::Null))
+}
@@ -31,13 +31,15 @@ impl Environment {
         env
     }
 
+    /// Looks `name` up in this scope, then in each enclosing scope in turn.
     pub fn get(&self, name: &str) -> Option<Rc<Object>> {
         match self.store.get(name) {
             Some(value) => Some(Rc::clone(value)),
-            None => None,
+            None => self.outer.as_ref()?.borrow().get(name),
         }
     }
 
+    /// Binds `name` in this scope, shadowing any binding in an outer scope.
     pub fn set(&mut self, name: String, value: Rc<Object>) -> Option<Rc<Object>> {
         self.store.insert(name, value)
     }
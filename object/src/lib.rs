@@ -1,30 +1,301 @@
-use std::rc::Rc;
+use std::{collections::HashMap, rc::Rc};
 
+use anyhow::Error;
 use opcode::Instructions;
 use parser::ast::{BlockStatement, Identifier};
 
-use self::environment::Env;
+use self::{builtins::BuiltinFunction, environment::Env};
 
+pub mod builtins;
 pub mod environment;
 
+/// Runtime values shared by the evaluator, compiler and VM.
+///
+/// `PartialEq` is derived, so `Float` follows IEEE 754 semantics: a constant
+/// holding `NaN` never compares equal to anything, including itself.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Object {
     Integer(i64),
+    Float(f64),
     Boolean(bool),
     String(String),
+    Char(char),
     Function(Vec<Identifier>, BlockStatement, Env),
     CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Builtin(BuiltinFunction),
     Return(Rc<Object>),
     Array(Vec<Rc<Object>>),
+    Hash(HashMap<HashKey, Rc<Object>>),
+    Tuple(Vec<Rc<Object>>),
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
     Null,
 }
 
+impl Object {
+    /// The name of this object's type, for use in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Char(_) => "CHAR",
+            Object::Function(..) => "FUNCTION",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            Object::Closure(_) => "CLOSURE",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Return(_) => "RETURN_VALUE",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Tuple(_) => "TUPLE",
+            Object::Range { .. } => "RANGE",
+            Object::Null => "NULL",
+        }
+    }
+
+    /// Whether the object counts as true in a condition. Only `null` and
+    /// `false` are falsy; `0`, `""` and empty collections are all truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+
+    /// Structural equality: arrays and tuples compare element by element,
+    /// hashes compare their entries regardless of insertion order, and
+    /// everything else falls back to `PartialEq`.
+    pub fn equals(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Array(left), Object::Array(right))
+            | (Object::Tuple(left), Object::Tuple(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| left.equals(right))
+            }
+            (Object::Hash(left), Object::Hash(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .all(|(key, value)| right.get(key).is_some_and(|other| value.equals(other)))
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Returns the key used to store this object in a hash, or an error if
+    /// the object can't be used as a key.
+    pub fn hash_key(&self) -> Result<HashKey, Error> {
+        match self {
+            Object::Integer(integer) => Ok(HashKey::Integer(*integer)),
+            Object::Boolean(boolean) => Ok(HashKey::Boolean(*boolean)),
+            Object::String(string) => Ok(HashKey::String(string.clone())),
+            _ => Err(Error::msg(format!("unusable as hash key: {}", self))),
+        }
+    }
+
+    /// Indexes a string by character, returning the `Char` at `index` or
+    /// null when it's out of range. Negative indexes work as for arrays.
+    pub fn string_index(string: &str, index: i64) -> Object {
+        let length = string.chars().count();
+
+        match array_index(length, index) {
+            Some(index) => Object::Char(string.chars().nth(index).unwrap()),
+            None => Object::Null,
+        }
+    }
+
+    /// Builds the range `start..end`, or `start..=end` when `inclusive` is
+    /// set. Both bounds must be integers.
+    pub fn range(start: &Object, end: &Object, inclusive: bool) -> Result<Object, Error> {
+        match (start, end) {
+            (Object::Integer(start), Object::Integer(end)) => Ok(Object::Range {
+                start: *start,
+                end: *end,
+                inclusive,
+            }),
+            _ => Err(Error::msg(format!(
+                "unsupported types for range: {}..{}",
+                start.type_name(),
+                end.type_name()
+            ))),
+        }
+    }
+
+    /// Indexes a range, returning the `Integer` at `index` or null when it's
+    /// out of range. Negative indexes work as for arrays.
+    pub fn range_index(start: i64, end: i64, inclusive: bool, index: i64) -> Object {
+        match array_index(range_len(start, end, inclusive), index) {
+            Some(index) => Object::Integer(start + index as i64),
+            None => Object::Null,
+        }
+    }
+
+    /// Slices an array or a string (by character) from `start` up to but
+    /// not including `end`. Either bound may be null to mean the start or
+    /// end, and out-of-range bounds are clamped, so only the types can make
+    /// a slice fail.
+    pub fn slice(&self, start: &Object, end: &Object) -> Result<Object, Error> {
+        let bound = |bound: &Object| match bound {
+            Object::Integer(integer) => Some(Some(*integer)),
+            Object::Null => Some(None),
+            _ => None,
+        };
+
+        let error = || {
+            Error::msg(format!(
+                "unsupported types for slice: {}[{}:{}]",
+                self.type_name(),
+                start.type_name(),
+                end.type_name()
+            ))
+        };
+
+        let (start, end) = match (bound(start), bound(end)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err(error()),
+        };
+
+        match self {
+            Object::Array(elements) => Ok(Object::Array(
+                elements[slice_range(elements.len(), start, end)].to_vec(),
+            )),
+            Object::String(string) => {
+                let chars = string.chars().collect::<Vec<char>>();
+
+                Ok(Object::String(
+                    chars[slice_range(chars.len(), start, end)].iter().collect(),
+                ))
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+/// Resolves `index` into an array of `length` elements. Negative indexes
+/// count back from the end, so `-1` is the last element. Returns `None` when
+/// the index is out of range either way; indexing then evaluates to null.
+pub fn array_index(length: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 {
+        length as i64 + index
+    } else {
+        index
+    };
+
+    (0..length as i64)
+        .contains(&index)
+        .then_some(index as usize)
+}
+
+/// The number of integers in `start..end`, or `start..=end` when
+/// `inclusive` is set. A range whose end comes before its start is empty.
+pub fn range_len(start: i64, end: i64, inclusive: bool) -> usize {
+    let length = end as i128 - start as i128 + inclusive as i128;
+
+    length.clamp(0, usize::MAX as i128) as usize
+}
+
+/// How integer arithmetic behaves when the result doesn't fit in an `i64`.
+/// Dividing by zero is an error either way.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum WrappingMode {
+    /// Overflow is an error.
+    #[default]
+    Checked,
+    /// Results wrap around in two's complement, like `i64::wrapping_add`.
+    Wrapping,
+}
+
+/// An integer arithmetic operation that can overflow, applied through a
+/// `WrappingMode`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl std::fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let operator = match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+            ArithOp::Div => "/",
+            ArithOp::Rem => "%",
+        };
+
+        write!(f, "{}", operator)
+    }
+}
+
+impl WrappingMode {
+    /// Applies `op` to `left` and `right`.
+    pub fn apply(self, op: ArithOp, left: i64, right: i64) -> Result<i64, Error> {
+        let result = match (self, op) {
+            (_, ArithOp::Div | ArithOp::Rem) if right == 0 => {
+                return Err(Error::msg("division by zero"))
+            }
+            (WrappingMode::Checked, ArithOp::Add) => left.checked_add(right),
+            (WrappingMode::Checked, ArithOp::Sub) => left.checked_sub(right),
+            (WrappingMode::Checked, ArithOp::Mul) => left.checked_mul(right),
+            (WrappingMode::Checked, ArithOp::Div) => left.checked_div(right),
+            (WrappingMode::Checked, ArithOp::Rem) => left.checked_rem(right),
+            (WrappingMode::Wrapping, ArithOp::Add) => Some(left.wrapping_add(right)),
+            (WrappingMode::Wrapping, ArithOp::Sub) => Some(left.wrapping_sub(right)),
+            (WrappingMode::Wrapping, ArithOp::Mul) => Some(left.wrapping_mul(right)),
+            (WrappingMode::Wrapping, ArithOp::Div) => Some(left.wrapping_div(right)),
+            (WrappingMode::Wrapping, ArithOp::Rem) => Some(left.wrapping_rem(right)),
+        };
+
+        result.ok_or_else(|| Error::msg(format!("integer overflow: {} {} {}", left, op, right)))
+    }
+
+    /// Negates `value`, which only overflows for `i64::MIN`.
+    pub fn negate(self, value: i64) -> Result<i64, Error> {
+        match self {
+            WrappingMode::Checked => value
+                .checked_neg()
+                .ok_or_else(|| Error::msg(format!("integer overflow: -({})", value))),
+            WrappingMode::Wrapping => Ok(value.wrapping_neg()),
+        }
+    }
+}
+
+/// Resolves slice bounds over `length` elements, clamping them into range.
+/// Negative bounds count back from the end and a start past the end gives
+/// an empty range.
+fn slice_range(length: usize, start: Option<i64>, end: Option<i64>) -> std::ops::Range<usize> {
+    let clamp = |bound: i64| {
+        let bound = if bound < 0 {
+            length as i64 + bound
+        } else {
+            bound
+        };
+
+        bound.clamp(0, length as i64) as usize
+    };
+
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(length, clamp);
+
+    start..end.max(start)
+}
+
 impl std::fmt::Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Object::Integer(integer) => write!(f, "{}", integer),
+            Object::Float(float) => write!(f, "{}", float),
             Object::Boolean(boolean) => write!(f, "{}", boolean),
             Object::String(string) => write!(f, "{}", string),
+            Object::Char(ch) => write!(f, "{}", ch),
             Object::Function(parameters, body, _env) => {
                 let mut parameters_string = String::new();
 
@@ -51,6 +322,40 @@ impl std::fmt::Display for Object {
 
                 write!(f, "[{}]", elements_string)
             }
+            Object::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>();
+
+                match elements.as_slice() {
+                    [element] => write!(f, "({},)", element),
+                    _ => write!(f, "({})", elements.join(", ")),
+                }
+            }
+            Object::Hash(pairs) => {
+                // Sort by key so the output doesn't depend on the map's iteration order.
+                let mut pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<String>>();
+
+                pairs.sort();
+
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(
+                f,
+                "{}{}{}",
+                start,
+                if *inclusive { "..=" } else { ".." },
+                end
+            ),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
             Object::Return(value) => write!(f, "{}", value),
             Object::Null => write!(f, "null"),
             _ => Ok(()),
@@ -58,17 +363,37 @@ impl std::fmt::Display for Object {
     }
 }
 
+/// The subset of objects that can be used as keys in a hash.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl std::fmt::Display for HashKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HashKey::Integer(integer) => write!(f, "{}", integer),
+            HashKey::Boolean(boolean) => write!(f, "{}", boolean),
+            HashKey::String(string) => write!(f, "{}", string),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct CompiledFunction {
     pub instructions: Instructions,
     pub num_locals: usize,
+    pub num_parameters: usize,
 }
 
 impl CompiledFunction {
-    pub fn new(instructions: Instructions, num_locals: usize) -> Self {
-        Self { 
+    pub fn new(instructions: Instructions, num_locals: usize, num_parameters: usize) -> Self {
+        Self {
             instructions,
             num_locals,
+            num_parameters,
         }
     }
 
@@ -82,3 +407,21 @@ impl std::fmt::Display for CompiledFunction {
         write!(f, "function ({})", self.instructions)
     }
 }
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Rc<Object>>,
+}
+
+impl Closure {
+    pub fn new(function: Rc<CompiledFunction>, free: Vec<Rc<Object>>) -> Self {
+        Self { function, free }
+    }
+}
+
+impl std::fmt::Display for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "closure[{}]", self.function)
+    }
+}
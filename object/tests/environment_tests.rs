@@ -0,0 +1,34 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::Error;
+use object::{environment::Environment, Object};
+
+#[test]
+fn test_enclosed_environment() -> Result<(), Error> {
+    let outer = Rc::new(RefCell::new(Environment::new()));
+
+    outer
+        .borrow_mut()
+        .set("$a".to_string(), Rc::new(Object::Integer(1)));
+    outer
+        .borrow_mut()
+        .set("$b".to_string(), Rc::new(Object::Integer(2)));
+
+    let mut inner = Environment::new_enclosed_environment(&outer);
+
+    inner.set("$a".to_string(), Rc::new(Object::Integer(10)));
+
+    // The inner binding shadows the outer one...
+    assert_eq!(inner.get("$a").as_deref(), Some(&Object::Integer(10)));
+    // ...while outer bindings stay visible from the inner scope...
+    assert_eq!(inner.get("$b").as_deref(), Some(&Object::Integer(2)));
+    // ...and the outer scope is left untouched.
+    assert_eq!(
+        outer.borrow().get("$a").as_deref(),
+        Some(&Object::Integer(1))
+    );
+
+    assert_eq!(inner.get("$c"), None);
+
+    Ok(())
+}
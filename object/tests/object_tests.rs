@@ -0,0 +1,282 @@
+use std::{cell::RefCell, collections::HashMap, io::Write, rc::Rc};
+
+use anyhow::Error;
+use object::{
+    array_index,
+    builtins::{self, BUILTINS},
+    environment::Environment,
+    Closure, CompiledFunction, HashKey, Object,
+};
+use opcode::Instructions;
+use parser::ast::{BlockStatement, Identifier};
+
+#[test]
+fn test_hash_equality_ignores_insertion_order() -> Result<(), Error> {
+    let mut first = HashMap::new();
+    first.insert(
+        HashKey::String("a".to_string()),
+        Rc::new(Object::Integer(1)),
+    );
+    first.insert(
+        HashKey::String("b".to_string()),
+        Rc::new(Object::Integer(2)),
+    );
+
+    let mut second = HashMap::new();
+    second.insert(
+        HashKey::String("b".to_string()),
+        Rc::new(Object::Integer(2)),
+    );
+    second.insert(
+        HashKey::String("a".to_string()),
+        Rc::new(Object::Integer(1)),
+    );
+
+    assert_eq!(Object::Hash(first.clone()), Object::Hash(second));
+
+    first.insert(HashKey::Integer(1), Rc::new(Object::Boolean(true)));
+
+    assert_ne!(Object::Hash(first), Object::Hash(HashMap::new()));
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_display() -> Result<(), Error> {
+    let hash = Object::Hash(HashMap::from([
+        (
+            HashKey::String("b".to_string()),
+            Rc::new(Object::Integer(2)),
+        ),
+        (
+            HashKey::String("a".to_string()),
+            Rc::new(Object::Integer(1)),
+        ),
+    ]));
+
+    assert_eq!(hash.to_string(), "{a: 1, b: 2}");
+    assert_eq!(Object::Hash(HashMap::new()).to_string(), "{}");
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_keys() -> Result<(), Error> {
+    let hello_one = Object::String("Hello World".to_string());
+    let hello_two = Object::String("Hello World".to_string());
+    let diff = Object::String("My name is johnny".to_string());
+
+    assert_eq!(hello_one.hash_key()?, hello_two.hash_key()?);
+    assert_ne!(hello_one.hash_key()?, diff.hash_key()?);
+
+    assert_eq!(Object::Integer(1).hash_key()?, HashKey::Integer(1));
+    assert_eq!(Object::Boolean(true).hash_key()?, HashKey::Boolean(true));
+    assert_ne!(
+        Object::Integer(1).hash_key()?,
+        Object::String("1".to_string()).hash_key()?
+    );
+
+    assert!(Object::Array(vec![]).hash_key().is_err());
+    assert!(Object::Null.hash_key().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_char_display() -> Result<(), Error> {
+    assert_eq!(Object::Char('x').to_string(), "x");
+    assert_eq!(Object::Char('\n').to_string(), "\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_type_names() -> Result<(), Error> {
+    let function = Rc::new(CompiledFunction::new(Instructions::default(), 0, 0));
+
+    let tests = vec![
+        (Object::Integer(1), "INTEGER"),
+        (Object::Float(1.5), "FLOAT"),
+        (Object::Boolean(true), "BOOLEAN"),
+        (Object::String("a".to_string()), "STRING"),
+        (Object::Char('a'), "CHAR"),
+        (
+            Object::Function(
+                Vec::<Identifier>::new(),
+                BlockStatement {
+                    token: Default::default(),
+                    statements: vec![],
+                },
+                Rc::new(RefCell::new(Environment::new())),
+            ),
+            "FUNCTION",
+        ),
+        (
+            Object::CompiledFunction(Rc::clone(&function)),
+            "COMPILED_FUNCTION",
+        ),
+        (
+            Object::Closure(Rc::new(Closure::new(function, vec![]))),
+            "CLOSURE",
+        ),
+        (Object::Builtin(BUILTINS[0].clone()), "BUILTIN"),
+        (Object::Return(Rc::new(Object::Null)), "RETURN_VALUE"),
+        (Object::Array(vec![]), "ARRAY"),
+        (Object::Hash(HashMap::new()), "HASH"),
+        (Object::Tuple(vec![]), "TUPLE"),
+        (Object::Null, "NULL"),
+    ];
+
+    for (object, expected) in tests {
+        assert_eq!(object.type_name(), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_structural_equality() -> Result<(), Error> {
+    let nested = |last: i64| {
+        Object::Array(vec![
+            Rc::new(Object::Integer(1)),
+            Rc::new(Object::Array(vec![
+                Rc::new(Object::Integer(2)),
+                Rc::new(Object::Integer(last)),
+            ])),
+        ])
+    };
+
+    assert!(nested(3).equals(&nested(3)));
+    assert!(!nested(3).equals(&nested(4)));
+    assert!(!nested(3).equals(&Object::Array(vec![])));
+
+    let mut first = HashMap::new();
+    first.insert(HashKey::Integer(1), Rc::new(nested(3)));
+    first.insert(
+        HashKey::String("a".to_string()),
+        Rc::new(Object::Boolean(true)),
+    );
+
+    let mut second = HashMap::new();
+    second.insert(
+        HashKey::String("a".to_string()),
+        Rc::new(Object::Boolean(true)),
+    );
+    second.insert(HashKey::Integer(1), Rc::new(nested(3)));
+
+    assert!(Object::Hash(first.clone()).equals(&Object::Hash(second.clone())));
+
+    second.insert(HashKey::Integer(1), Rc::new(nested(4)));
+
+    assert!(!Object::Hash(first).equals(&Object::Hash(second)));
+
+    assert!(Object::Integer(1).equals(&Object::Integer(1)));
+    assert!(!Object::Integer(1).equals(&Object::String("1".to_string())));
+
+    Ok(())
+}
+
+#[test]
+fn test_array_index() -> Result<(), Error> {
+    let tests = [
+        (3, 0, Some(0)),
+        (3, 2, Some(2)),
+        (3, 3, None),
+        (3, -1, Some(2)),
+        (3, -3, Some(0)),
+        (3, -4, None),
+        (0, 0, None),
+        (0, -1, None),
+    ];
+
+    for (length, index, expected) in tests {
+        assert_eq!(
+            array_index(length, index),
+            expected,
+            "{}[{}]",
+            length,
+            index
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_is_truthy() -> Result<(), Error> {
+    let function = Rc::new(CompiledFunction::new(Instructions::default(), 0, 0));
+
+    let tests = [
+        (Object::Null, false),
+        (Object::Boolean(false), false),
+        (Object::Boolean(true), true),
+        (Object::Integer(0), true),
+        (Object::Integer(-1), true),
+        (Object::Float(0.0), true),
+        (Object::String(String::new()), true),
+        (Object::Char('\0'), true),
+        (Object::Array(vec![]), true),
+        (Object::Hash(HashMap::new()), true),
+        (Object::Tuple(vec![]), true),
+        (
+            Object::Function(
+                Vec::<Identifier>::new(),
+                BlockStatement {
+                    token: Default::default(),
+                    statements: vec![],
+                },
+                Rc::new(RefCell::new(Environment::new())),
+            ),
+            true,
+        ),
+        (Object::CompiledFunction(Rc::clone(&function)), true),
+        (
+            Object::Closure(Rc::new(Closure::new(function, vec![]))),
+            true,
+        ),
+        (Object::Builtin(BUILTINS[0].clone()), true),
+        (Object::Return(Rc::new(Object::Null)), true),
+    ];
+
+    for (object, expected) in tests {
+        assert_eq!(object.is_truthy(), expected, "{:?}", object);
+    }
+
+    Ok(())
+}
+
+/// A writer that appends to a buffer the test can still read afterwards.
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_print_builtin() -> Result<(), Error> {
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+    let previous = builtins::set_output(Box::new(Capture(Rc::clone(&buffer))));
+
+    let print = builtins::lookup("print").unwrap();
+
+    let arguments = vec![
+        Rc::new(Object::String("x".to_string())),
+        Rc::new(Object::Integer(1)),
+        Rc::new(Object::Boolean(true)),
+    ];
+
+    assert_eq!(*(print.func)(arguments)?, Object::Null);
+    assert_eq!(*(print.func)(vec![])?, Object::Null);
+
+    builtins::set_output(previous);
+
+    assert_eq!(String::from_utf8(buffer.take())?, "x 1 true\n\n");
+
+    Ok(())
+}
@@ -1,4 +1,9 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    ops::{Index, IndexMut},
+    slice::SliceIndex,
+    str::FromStr,
+};
 
 use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
 use lazy_static::lazy_static;
@@ -110,6 +115,34 @@ impl Instructions {
                 .fold(vec![], |sum, &i| [sum.as_slice(), i.0.as_slice()].concat()),
         );
     }
+
+    /// Returns the length of the instructions in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Appends the bytes of `other` to the end of these instructions.
+    pub fn extend_from(&mut self, other: Instructions) {
+        self.0.extend(other.0);
+    }
+}
+
+impl<I: SliceIndex<[u8]>> Index<I> for Instructions {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<I: SliceIndex<[u8]>> IndexMut<I> for Instructions {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        &mut self.0[index]
+    }
 }
 
 pub struct OpcodeDefinition {
@@ -178,11 +211,38 @@ pub enum Opcode {
     OpGetFree,
     /// 0x1C -  Current closure
     OpCurrentClosure,
+    /// 0x1D -  Greater than or equal
+    OpGreaterThanOrEqual,
+    /// 0x1E -  Remainder of dividing two integers
+    OpMod,
+    /// 0x1F -  Jump if the popped value is truthy
+    OpJumpTruthy,
+    /// 0x20 -  Get a builtin function
+    OpGetBuiltin,
+    /// 0x21 -  Bitwise AND of two integers
+    OpBitAnd,
+    /// 0x22 -  Bitwise OR of two integers
+    OpBitOr,
+    /// 0x23 -  Bitwise XOR of two integers
+    OpBitXor,
+    /// 0x24 -  Shift an integer left
+    OpShiftLeft,
+    /// 0x25 -  Shift an integer right (arithmetic)
+    OpShiftRight,
+    /// 0x26 -  Bitwise complement of an integer
+    OpBitNot,
+    /// 0x27 -  Create a tuple
+    OpTuple,
+    /// 0x28 -  Slice an array or string
+    OpSlice,
+    /// 0x29 -  Create a range, inclusive when the operand is 1
+    OpRange,
 }
 
-impl From<u8> for Opcode {
-    fn from(opcode: u8) -> Self {
-        match opcode {
+impl Opcode {
+    /// The opcode encoded as `byte`, or `None` if no opcode uses it.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        let opcode = match byte {
             0x00 => Opcode::OpConst,
             0x01 => Opcode::OpAdd,
             0x02 => Opcode::OpPop,
@@ -212,8 +272,29 @@ impl From<u8> for Opcode {
             0x1A => Opcode::OpClosure,
             0x1B => Opcode::OpGetFree,
             0x1C => Opcode::OpCurrentClosure,
-            _ => panic!("Opcode not found: {}", opcode),
-        }
+            0x1D => Opcode::OpGreaterThanOrEqual,
+            0x1E => Opcode::OpMod,
+            0x1F => Opcode::OpJumpTruthy,
+            0x20 => Opcode::OpGetBuiltin,
+            0x21 => Opcode::OpBitAnd,
+            0x22 => Opcode::OpBitOr,
+            0x23 => Opcode::OpBitXor,
+            0x24 => Opcode::OpShiftLeft,
+            0x25 => Opcode::OpShiftRight,
+            0x26 => Opcode::OpBitNot,
+            0x27 => Opcode::OpTuple,
+            0x28 => Opcode::OpSlice,
+            0x29 => Opcode::OpRange,
+            _ => return None,
+        };
+
+        Some(opcode)
+    }
+}
+
+impl From<u8> for Opcode {
+    fn from(opcode: u8) -> Self {
+        Opcode::from_byte(opcode).unwrap_or_else(|| panic!("Opcode not found: {}", opcode))
     }
 }
 
@@ -225,6 +306,19 @@ impl std::fmt::Display for Opcode {
     }
 }
 
+impl FromStr for Opcode {
+    type Err = String;
+
+    /// Parses an opcode from its definition name, e.g. `OpConst`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        DEFINITIONS
+            .iter()
+            .find(|(_, definition)| definition.name == name)
+            .map(|(opcode, _)| *opcode)
+            .ok_or_else(|| format!("unknown opcode: {}", name))
+    }
+}
+
 pub fn read_operands(def: &OpcodeDefinition, ins: &[u8]) -> (Vec<usize>, usize) {
     let mut operands = Vec::with_capacity(def.operand_widths.len());
     let mut offset = 0;
@@ -456,6 +550,97 @@ lazy_static! {
                 operand_widths: vec![],
             },
         );
+        definitions.insert(
+            Opcode::OpGreaterThanOrEqual,
+            OpcodeDefinition {
+                name: "OpGreaterThanOrEqual",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpMod,
+            OpcodeDefinition {
+                name: "OpMod",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpJumpTruthy,
+            OpcodeDefinition {
+                name: "OpJumpTruthy",
+                operand_widths: vec![2],
+            },
+        );
+        definitions.insert(
+            Opcode::OpGetBuiltin,
+            OpcodeDefinition {
+                name: "OpGetBuiltin",
+                operand_widths: vec![1],
+            },
+        );
+        definitions.insert(
+            Opcode::OpBitAnd,
+            OpcodeDefinition {
+                name: "OpBitAnd",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpBitOr,
+            OpcodeDefinition {
+                name: "OpBitOr",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpBitXor,
+            OpcodeDefinition {
+                name: "OpBitXor",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpShiftLeft,
+            OpcodeDefinition {
+                name: "OpShiftLeft",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpShiftRight,
+            OpcodeDefinition {
+                name: "OpShiftRight",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpBitNot,
+            OpcodeDefinition {
+                name: "OpBitNot",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpTuple,
+            OpcodeDefinition {
+                name: "OpTuple",
+                operand_widths: vec![2],
+            },
+        );
+        definitions.insert(
+            Opcode::OpSlice,
+            OpcodeDefinition {
+                name: "OpSlice",
+                operand_widths: vec![],
+            },
+        );
+        definitions.insert(
+            Opcode::OpRange,
+            OpcodeDefinition {
+                name: "OpRange",
+                operand_widths: vec![1],
+            },
+        );
 
         definitions
     };
@@ -1,13 +1,27 @@
+use std::str::FromStr;
+
 use anyhow::Error;
-use opcode::{concat_instructions, make, Opcode};
+use opcode::{concat_instructions, lookup, make, read_operands, Instructions, Opcode, DEFINITIONS};
 
 #[test]
 fn test_make() -> Result<(), Error> {
-    let tests = vec![(
-        Opcode::OpConst,
-        vec![65534],
-        vec![Opcode::OpConst as u8, 255, 254],
-    )];
+    let tests = vec![
+        (
+            Opcode::OpConst,
+            vec![65534],
+            vec![Opcode::OpConst as u8, 255, 254],
+        ),
+        (
+            Opcode::OpGetGlobal,
+            vec![65534],
+            vec![Opcode::OpGetGlobal as u8, 255, 254],
+        ),
+        (
+            Opcode::OpSetGlobal,
+            vec![65534],
+            vec![Opcode::OpSetGlobal as u8, 255, 254],
+        ),
+    ];
 
     for (opcode, operands, expected) in tests {
         let instruction = make(opcode, &operands);
@@ -17,6 +31,33 @@ fn test_make() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_global_opcodes_round_trip() -> Result<(), Error> {
+    for opcode in [Opcode::OpGetGlobal, Opcode::OpSetGlobal] {
+        let instruction = make(opcode, &vec![65534]);
+
+        assert_eq!(instruction.0.len(), 3);
+        assert_eq!(Opcode::from(instruction.0[0]), opcode);
+
+        let (operands, read) = read_operands(lookup(opcode), &instruction.0[1..]);
+
+        assert_eq!(operands, vec![65534]);
+        assert_eq!(read, 2);
+    }
+
+    let instructions = concat_instructions(&vec![
+        make(Opcode::OpSetGlobal, &vec![3]),
+        make(Opcode::OpGetGlobal, &vec![3]),
+    ]);
+
+    assert_eq!(
+        instructions.to_string(),
+        "0000 OpSetGlobal 3\n0003 OpGetGlobal 3\n"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_instructions_string() -> Result<(), Error> {
     let instructions = vec![
@@ -33,3 +74,94 @@ fn test_instructions_string() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_instructions_len() -> Result<(), Error> {
+    let empty = Instructions::default();
+
+    assert_eq!(empty.len(), 0);
+    assert!(empty.is_empty());
+
+    let instructions = make(Opcode::OpConst, &vec![1]);
+
+    assert_eq!(instructions.len(), 3);
+    assert!(!instructions.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_instructions_extend_from() -> Result<(), Error> {
+    let mut instructions = make(Opcode::OpConst, &vec![1]);
+
+    instructions.extend_from(make(Opcode::OpPop, &vec![]));
+
+    assert_eq!(
+        instructions,
+        concat_instructions(&vec![
+            make(Opcode::OpConst, &vec![1]),
+            make(Opcode::OpPop, &vec![]),
+        ])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_instructions_index() -> Result<(), Error> {
+    let mut instructions = make(Opcode::OpConst, &vec![65534]);
+
+    assert_eq!(instructions[0], Opcode::OpConst as u8);
+    assert_eq!(&instructions[1..], &[255, 254]);
+
+    instructions[2] = 255;
+
+    assert_eq!(instructions, make(Opcode::OpConst, &vec![65535]));
+
+    Ok(())
+}
+
+#[test]
+fn test_read_operands_round_trips_every_opcode() -> Result<(), Error> {
+    for byte in 0..DEFINITIONS.len() as u8 {
+        let opcode = Opcode::from(byte);
+        let definition = lookup(opcode);
+
+        // Use the largest value each operand can hold to catch truncation.
+        let operands = definition
+            .operand_widths
+            .iter()
+            .map(|width| (1 << (width * 8)) - 1)
+            .collect::<Vec<usize>>();
+        let width = definition.operand_widths.iter().sum::<usize>();
+
+        let instruction = make(opcode, &operands);
+
+        assert_eq!(instruction.0.len(), 1 + width, "{}", definition.name);
+        assert_eq!(Opcode::from(instruction.0[0]), opcode);
+
+        let (decoded, read) = read_operands(definition, &instruction.0[1..]);
+
+        assert_eq!(decoded, operands, "{}", definition.name);
+        assert_eq!(read, width, "{}", definition.name);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_opcode_names_round_trip() -> Result<(), Error> {
+    for byte in 0..DEFINITIONS.len() as u8 {
+        let opcode = Opcode::from(byte);
+
+        assert_eq!(opcode.to_string(), lookup(opcode).name);
+        assert_eq!(Opcode::from_str(&opcode.to_string()), Ok(opcode));
+    }
+
+    assert_eq!(
+        Opcode::from_str("OpNope"),
+        Err("unknown opcode: OpNope".to_string())
+    );
+
+    Ok(())
+}
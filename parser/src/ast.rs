@@ -1,5 +1,60 @@
-use lexer::token::Token;
+use lexer::{
+    token::{Token, TokenType},
+    Lexer,
+};
 
+use crate::Parser;
+
+/// A 1-based line and column in the source.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The region of source a node was parsed from, running from the start of
+/// its first token to the start of its last token.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn from_token(token: &Token) -> Self {
+        let position = Position {
+            line: token.line,
+            column: token.column,
+        };
+
+        Self {
+            start: position,
+            end: position,
+        }
+    }
+
+    /// Returns a span from the start of this one to the end of `other`.
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            start: self.start,
+            end: other.end,
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Node {
     Expression(Expression),
     Program(Program),
@@ -26,13 +81,331 @@ impl std::fmt::Display for Node {
     }
 }
 
+impl Node {
+    /// Renders the tree as an indented S-expression, one node per line, with
+    /// every line prefixed by at least `indent` spaces. Unlike `Display`, the
+    /// output isn't meant to be parsed back; it makes the shape of the tree,
+    /// and so the precedence the parser applied, easy to read:
+    ///
+    /// ```text
+    /// (+
+    ///   1
+    ///   (*
+    ///     2
+    ///     3))
+    /// ```
+    pub fn pretty(&self, indent: usize) -> String {
+        match self {
+            Node::Expression(expression) => pretty_expression(expression, indent),
+            Node::Program(program) => pretty_list(
+                indent,
+                "program",
+                program
+                    .statements
+                    .iter()
+                    .map(|statement| pretty_statement(statement, indent + 2))
+                    .collect(),
+            ),
+            Node::Statement(statement) => pretty_statement(statement, indent),
+        }
+    }
+}
+
+fn pretty_atom(indent: usize, atom: impl std::fmt::Display) -> String {
+    format!("{}{}", " ".repeat(indent), atom)
+}
+
+fn pretty_list(indent: usize, head: &str, children: Vec<String>) -> String {
+    let mut output = format!("{}({}", " ".repeat(indent), head);
+
+    for child in children {
+        output.push('\n');
+        output.push_str(&child);
+    }
+
+    output.push(')');
+    output
+}
+
+fn pretty_block(block: &BlockStatement, indent: usize) -> String {
+    pretty_list(
+        indent,
+        "block",
+        block
+            .statements
+            .iter()
+            .map(|statement| pretty_statement(statement, indent + 2))
+            .collect(),
+    )
+}
+
+fn pretty_statement(statement: &Statement, indent: usize) -> String {
+    let child = indent + 2;
+
+    match statement {
+        Statement::Assign(Assignment { name, value, .. }) => pretty_list(
+            indent,
+            "=",
+            vec![pretty_atom(child, name), pretty_expression(value, child)],
+        ),
+        Statement::IndexAssign(IndexAssignment {
+            target,
+            index,
+            value,
+            ..
+        }) => pretty_list(
+            indent,
+            "[]=",
+            vec![
+                pretty_expression(target, child),
+                pretty_expression(index, child),
+                pretty_expression(value, child),
+            ],
+        ),
+        Statement::Expr(expression) => pretty_expression(expression, indent),
+        Statement::Return(ReturnStatement { return_value, .. }) => pretty_list(
+            indent,
+            "return",
+            vec![pretty_expression(return_value, child)],
+        ),
+        Statement::Break(token) | Statement::Continue(token) => pretty_atom(indent, token),
+    }
+}
+
+fn pretty_expression(expression: &Expression, indent: usize) -> String {
+    let child = indent + 2;
+    let expressions = |expressions: &[Expression]| -> Vec<String> {
+        expressions
+            .iter()
+            .map(|expression| pretty_expression(expression, child))
+            .collect()
+    };
+
+    match expression {
+        Expression::Identifier(identifier) => pretty_atom(indent, identifier),
+        Expression::Assign(assignment) => pretty_list(
+            indent,
+            "=",
+            vec![
+                pretty_atom(child, &assignment.name),
+                pretty_expression(&assignment.value, child),
+            ],
+        ),
+        Expression::Literal(literal) => match literal {
+            Literal::String(StringLiteral { value, .. }) => {
+                pretty_atom(indent, format!("{:?}", value))
+            }
+            Literal::Char(CharLiteral { value, .. }) => pretty_atom(indent, format!("{:?}", value)),
+            Literal::Array(ArrayLiteral { elements, .. }) => {
+                pretty_list(indent, "array", expressions(elements))
+            }
+            Literal::Tuple(TupleLiteral { elements, .. }) => {
+                pretty_list(indent, "tuple", expressions(elements))
+            }
+            Literal::Hash(HashLiteral { pairs, .. }) => pretty_list(
+                indent,
+                "hash",
+                pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        pretty_list(
+                            child,
+                            ":",
+                            vec![
+                                pretty_expression(key, child + 2),
+                                pretty_expression(value, child + 2),
+                            ],
+                        )
+                    })
+                    .collect(),
+            ),
+            literal => pretty_atom(indent, literal),
+        },
+        Expression::Infix(InfixExpression {
+            left,
+            operator,
+            right,
+            ..
+        }) => pretty_list(
+            indent,
+            &operator.literal,
+            vec![
+                pretty_expression(left, child),
+                pretty_expression(right, child),
+            ],
+        ),
+        Expression::Range(RangeExpression {
+            token, start, end, ..
+        }) => pretty_list(
+            indent,
+            &token.literal,
+            vec![
+                pretty_expression(start, child),
+                pretty_expression(end, child),
+            ],
+        ),
+        Expression::Prefix(PrefixExpression {
+            operator, right, ..
+        }) => pretty_list(
+            indent,
+            &operator.literal,
+            vec![pretty_expression(right, child)],
+        ),
+        Expression::Postfix(PostfixExpression { operator, left, .. }) => pretty_list(
+            indent,
+            &operator.literal,
+            vec![pretty_expression(left, child)],
+        ),
+        Expression::If(IfExpression {
+            condition,
+            consequence,
+            alternative,
+            ..
+        }) => {
+            let mut children = vec![
+                pretty_expression(condition, child),
+                pretty_block(consequence, child),
+            ];
+
+            if let Some(alternative) = alternative {
+                children.push(pretty_block(alternative, child));
+            }
+
+            pretty_list(indent, "if", children)
+        }
+        Expression::Ternary(TernaryExpression {
+            condition,
+            consequence,
+            alternative,
+            ..
+        }) => pretty_list(
+            indent,
+            "?",
+            vec![
+                pretty_expression(condition, child),
+                pretty_expression(consequence, child),
+                pretty_expression(alternative, child),
+            ],
+        ),
+        Expression::While(WhileExpression {
+            condition, body, ..
+        }) => pretty_list(
+            indent,
+            "while",
+            vec![
+                pretty_expression(condition, child),
+                pretty_block(body, child),
+            ],
+        ),
+        Expression::For(ForExpression {
+            init,
+            condition,
+            update,
+            body,
+            ..
+        }) => {
+            let empty = || pretty_atom(child, "_");
+
+            pretty_list(
+                indent,
+                "for",
+                vec![
+                    init.as_ref()
+                        .map_or_else(empty, |init| pretty_statement(init, child)),
+                    condition
+                        .as_ref()
+                        .map_or_else(empty, |condition| pretty_expression(condition, child)),
+                    update
+                        .as_ref()
+                        .map_or_else(empty, |update| pretty_statement(update, child)),
+                    pretty_block(body, child),
+                ],
+            )
+        }
+        Expression::Function(FunctionLiteral {
+            parameters, body, ..
+        }) => {
+            let parameters = parameters
+                .iter()
+                .map(|parameter| parameter.to_string())
+                .collect::<Vec<String>>();
+
+            pretty_list(
+                indent,
+                &format!("function ({})", parameters.join(" ")),
+                vec![pretty_block(body, child)],
+            )
+        }
+        Expression::Call(CallExpression {
+            function,
+            arguments,
+            ..
+        }) => {
+            let mut children = vec![pretty_expression(function, child)];
+            children.extend(expressions(arguments));
+
+            pretty_list(indent, "call", children)
+        }
+        Expression::Index(IndexExpression { left, index, .. }) => pretty_list(
+            indent,
+            "index",
+            vec![
+                pretty_expression(left, child),
+                pretty_expression(index, child),
+            ],
+        ),
+        Expression::Slice(SliceExpression {
+            left, start, end, ..
+        }) => {
+            // A missing bound is shown as `_`.
+            let bound = |bound: &Option<Box<Expression>>| match bound {
+                Some(bound) => pretty_expression(bound, child),
+                None => pretty_atom(child, "_"),
+            };
+
+            pretty_list(
+                indent,
+                "slice",
+                vec![pretty_expression(left, child), bound(start), bound(end)],
+            )
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
     Integer(IntegerLiteral),
     Float(FloatLiteral),
     Boolean(BooleanLiteral),
+    Null(NullLiteral),
     String(StringLiteral),
+    Char(CharLiteral),
     Array(ArrayLiteral),
+    Hash(HashLiteral),
+    Tuple(TupleLiteral),
+}
+
+impl Literal {
+    pub fn span(&self) -> Span {
+        match self {
+            Literal::Integer(IntegerLiteral { token, .. })
+            | Literal::Float(FloatLiteral { token, .. })
+            | Literal::Boolean(BooleanLiteral { token, .. })
+            | Literal::Null(NullLiteral { token })
+            | Literal::String(StringLiteral { token, .. })
+            | Literal::Char(CharLiteral { token, .. }) => Span::from_token(token),
+            Literal::Array(ArrayLiteral { token, elements })
+            | Literal::Tuple(TupleLiteral { token, elements }) => match elements.last() {
+                Some(last) => Span::from_token(token).to(last.span()),
+                None => Span::from_token(token),
+            },
+            Literal::Hash(HashLiteral { token, pairs }) => match pairs.last() {
+                Some((_, value)) => Span::from_token(token).to(value.span()),
+                None => Span::from_token(token),
+            },
+        }
+    }
 }
 
 impl std::fmt::Display for Literal {
@@ -40,8 +413,15 @@ impl std::fmt::Display for Literal {
         match self {
             Literal::Integer(IntegerLiteral { token: _, value }) => write!(f, "{}", value),
             Literal::Boolean(BooleanLiteral { token: _, value }) => write!(f, "{}", value),
-            Literal::String(StringLiteral { token: _, value }) => write!(f, "{}", value),
-            Literal::Float(FloatLiteral { token: _, value }) => write!(f, "{}", value),
+            Literal::Null(_) => write!(f, "null"),
+            Literal::String(StringLiteral { token: _, value }) => {
+                write!(f, "\"{}\"", escape(value, '"'))
+            }
+            // `Debug` keeps the decimal point, so `1.0` reads back as a float.
+            Literal::Float(FloatLiteral { token: _, value }) => write!(f, "{:?}", value),
+            Literal::Char(CharLiteral { token: _, value }) => {
+                write!(f, "'{}'", escape(&value.to_string(), '\''))
+            }
             Literal::Array(ArrayLiteral { token: _, elements }) => {
                 let mut elements_string = String::new();
 
@@ -55,32 +435,148 @@ impl std::fmt::Display for Literal {
 
                 write!(f, "[{}]", elements_string)
             }
+            Literal::Hash(HashLiteral { token: _, pairs }) => {
+                let pairs = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect::<Vec<String>>();
+
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Literal::Tuple(TupleLiteral { token: _, elements }) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.to_string())
+                    .collect::<Vec<String>>();
+
+                // A one-element tuple keeps its trailing comma so it doesn't
+                // read back as a grouped expression.
+                match elements.as_slice() {
+                    [element] => write!(f, "({},)", element),
+                    _ => write!(f, "({})", elements.join(", ")),
+                }
+            }
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
     Identifier(Identifier),
+    /// An assignment in expression position, such as `$y = ($x = 3)`. At
+    /// the start of a statement, an assignment is a `Statement::Assign`
+    /// instead, which leaves no value behind.
+    Assign(Box<Assignment>),
     Literal(Literal),
     Infix(InfixExpression),
     Prefix(PrefixExpression),
+    Postfix(PostfixExpression),
     If(IfExpression),
     Function(FunctionLiteral),
     Call(CallExpression),
     Index(IndexExpression),
+    Slice(SliceExpression),
+    Range(RangeExpression),
+    While(WhileExpression),
+    For(ForExpression),
+    Ternary(TernaryExpression),
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Identifier(Identifier { token, .. }) => Span::from_token(token),
+            Expression::Assign(assignment) => {
+                Span::from_token(&assignment.token).to(assignment.value.span())
+            }
+            Expression::Literal(literal) => literal.span(),
+            Expression::Infix(InfixExpression { left, right, .. })
+            | Expression::Range(RangeExpression {
+                start: left,
+                end: right,
+                ..
+            }) => left.span().to(right.span()),
+            Expression::Prefix(PrefixExpression { token, right, .. }) => {
+                Span::from_token(token).to(right.span())
+            }
+            Expression::Postfix(PostfixExpression { operator, left, .. }) => {
+                left.span().to(Span::from_token(operator))
+            }
+            Expression::If(IfExpression {
+                token,
+                condition,
+                consequence,
+                alternative,
+            }) => {
+                let end = alternative
+                    .as_ref()
+                    .and_then(BlockStatement::last_span)
+                    .or_else(|| consequence.last_span())
+                    .unwrap_or_else(|| condition.span());
+
+                Span::from_token(token).to(end)
+            }
+            Expression::Function(FunctionLiteral { token, body, .. })
+            | Expression::While(WhileExpression { token, body, .. })
+            | Expression::For(ForExpression { token, body, .. }) => {
+                let start = Span::from_token(token);
+
+                body.last_span().map_or(start, |end| start.to(end))
+            }
+            Expression::Call(CallExpression {
+                function,
+                arguments,
+                ..
+            }) => match arguments.last() {
+                Some(last) => function.span().to(last.span()),
+                None => function.span(),
+            },
+            Expression::Index(IndexExpression { left, index, .. }) => left.span().to(index.span()),
+            Expression::Slice(SliceExpression {
+                left, start, end, ..
+            }) => match end.as_ref().or(start.as_ref()) {
+                Some(bound) => left.span().to(bound.span()),
+                None => left.span(),
+            },
+            Expression::Ternary(TernaryExpression {
+                condition,
+                alternative,
+                ..
+            }) => condition.span().to(alternative.span()),
+        }
+    }
 }
 
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Expression::Identifier(identifier) => write!(f, "{}", identifier),
+            Expression::Assign(assignment) => write!(f, "({})", assignment),
             Expression::Literal(literal) => write!(f, "{}", literal),
             Expression::Index(IndexExpression {
                 token: _,
                 left,
                 index,
             }) => write!(f, "({}[{}])", left, index),
+            Expression::Slice(SliceExpression {
+                token: _,
+                left,
+                start,
+                end,
+            }) => {
+                let bound = |bound: &Option<Box<Expression>>| {
+                    bound.as_ref().map(|b| b.to_string()).unwrap_or_default()
+                };
+
+                write!(f, "({}[{}:{}])", left, bound(start), bound(end))
+            }
+            Expression::Range(RangeExpression {
+                token,
+                start,
+                end,
+                inclusive: _,
+            }) => write!(f, "({}{}{})", start, token, end),
             Expression::Infix(InfixExpression {
                 token: _,
                 left,
@@ -92,6 +588,11 @@ impl std::fmt::Display for Expression {
                 operator,
                 right,
             }) => write!(f, "({}{})", operator, right),
+            Expression::Postfix(PostfixExpression {
+                token: _,
+                operator,
+                left,
+            }) => write!(f, "({}{})", left, operator),
             Expression::If(IfExpression {
                 token: _,
                 condition,
@@ -101,13 +602,42 @@ impl std::fmt::Display for Expression {
                 if let Some(alternative) = alternative {
                     write!(
                         f,
-                        "if {} {{\n{}\n}} else {{\n{}\n}}",
+                        "if ({}) {{\n{}}} else {{\n{}}}",
                         condition, consequence, alternative
                     )
                 } else {
-                    write!(f, "if {} {{\n{}\n}}", condition, consequence)
+                    write!(f, "if ({}) {{\n{}}}", condition, consequence)
                 }
             }
+            Expression::Ternary(TernaryExpression {
+                token: _,
+                condition,
+                consequence,
+                alternative,
+            }) => write!(f, "({} ? {} : {})", condition, consequence, alternative),
+            Expression::While(WhileExpression {
+                token: _,
+                condition,
+                body,
+            }) => write!(f, "while ({}) {{\n{}}}", condition, body),
+            Expression::For(ForExpression {
+                token: _,
+                init,
+                condition,
+                update,
+                body,
+            }) => {
+                let clause = |clause: Option<String>| clause.unwrap_or_default();
+
+                write!(
+                    f,
+                    "for ({}; {}; {}) {{\n{}}}",
+                    clause(init.as_ref().map(|init| init.to_string())),
+                    clause(condition.as_ref().map(|condition| condition.to_string())),
+                    clause(update.as_ref().map(|update| update.to_string())),
+                    body
+                )
+            }
             Expression::Function(FunctionLiteral {
                 token: _,
                 parameters,
@@ -118,7 +648,7 @@ impl std::fmt::Display for Expression {
                     .map(|p| p.to_string())
                     .collect::<Vec<String>>();
 
-                write!(f, "fn({}) {{\n{}\n}}", params.join(", "), body)
+                write!(f, "function({}) {{\n{}}}", params.join(", "), body)
             }
             Expression::Call(CallExpression {
                 token: _,
@@ -142,28 +672,60 @@ impl std::fmt::Display for Expression {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
     Assign(Assignment),
+    IndexAssign(IndexAssignment),
     Expr(Expression),
     Return(ReturnStatement),
+    Break(Token),
+    Continue(Token),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Assign(Assignment { token, value, .. }) => {
+                Span::from_token(token).to(value.span())
+            }
+            Statement::IndexAssign(IndexAssignment { target, value, .. }) => {
+                target.span().to(value.span())
+            }
+            Statement::Expr(expression) => expression.span(),
+            Statement::Return(ReturnStatement {
+                token,
+                return_value,
+            }) => Span::from_token(token).to(return_value.span()),
+            Statement::Break(token) | Statement::Continue(token) => Span::from_token(token),
+        }
+    }
 }
 
 impl std::fmt::Display for Statement {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Statement::Assign(Assignment { token, name, value }) => {
-                write!(f, "{} {} = {}", token, name, value)
-            }
+            Statement::Assign(assignment) => write!(f, "{}", assignment),
+            Statement::IndexAssign(IndexAssignment {
+                token,
+                target,
+                index,
+                value,
+            }) => match compound_value(token, value) {
+                Some(value) => write!(f, "{}[{}] {} {}", target, index, token, value),
+                None => write!(f, "{}[{}] = {}", target, index, value),
+            },
             Statement::Expr(expression) => write!(f, "{}", expression),
             Statement::Return(ReturnStatement {
                 token,
                 return_value,
             }) => write!(f, "{} {}", token, return_value),
+            Statement::Break(token) | Statement::Continue(token) => write!(f, "{}", token),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
@@ -176,51 +738,102 @@ impl Default for Program {
     }
 }
 
-impl std::fmt::Display for Program {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let mut program_string = String::new();
+impl Program {
+    /// Whether the program's `Display` output parses back to the same tree.
+    /// Token positions aren't compared, since they change with the layout.
+    pub fn reparse_stable(&self) -> bool {
+        let source = self.to_string();
+        let mut parser = Parser::new(Lexer::new(&source));
 
-        for statement in &self.statements {
-            program_string.push_str(&format!("{}", statement));
+        match parser.parse_program() {
+            Ok(program) => parser.check_errors().is_ok() && program == *self,
+            Err(_) => false,
         }
+    }
+}
 
-        write!(f, "{}", program_string)
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let statements = self
+            .statements
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect::<Vec<String>>();
+
+        // Without separators, a statement starting with `(`, `[` or `-`
+        // would read back as continuing the one before it.
+        write!(f, "{}", statements.join("; "))
     }
 }
 
 // LITERALS
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BooleanLiteral {
     pub token: Token,
     pub value: bool,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NullLiteral {
+    pub token: Token,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntegerLiteral {
     pub token: Token,
     pub value: i64,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FloatLiteral {
     pub token: Token,
     pub value: f64,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringLiteral {
     pub token: Token,
     pub value: String,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharLiteral {
+    pub token: Token,
+    pub value: char,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArrayLiteral {
     pub token: Token,
     pub elements: Vec<Expression>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashLiteral {
+    pub token: Token,
+    pub pairs: Vec<(Expression, Expression)>,
+}
+
+/// A parenthesised, comma-separated list such as `(1, 2)` or `(1,)`. The
+/// comma is what sets it apart from a grouped expression.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TupleLiteral {
+    pub token: Token,
+    pub elements: Vec<Expression>,
+}
+
 // EXPRESSIONS
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionLiteral {
     pub token: Token,
     pub parameters: Vec<Identifier>,
@@ -228,6 +841,7 @@ pub struct FunctionLiteral {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallExpression {
     pub token: Token,
     pub function: Box<Expression>,
@@ -235,6 +849,7 @@ pub struct CallExpression {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Identifier {
     pub token: Token,
     pub value: String,
@@ -247,6 +862,7 @@ impl std::fmt::Display for Identifier {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfExpression {
     pub token: Token,
     pub condition: Box<Expression>,
@@ -255,13 +871,64 @@ pub struct IfExpression {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TernaryExpression {
+    pub token: Token,
+    pub condition: Box<Expression>,
+    pub consequence: Box<Expression>,
+    pub alternative: Box<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhileExpression {
+    pub token: Token,
+    pub condition: Box<Expression>,
+    pub body: BlockStatement,
+}
+
+/// A C-style `for (init; condition; update) { body }` loop. Any of the three
+/// clauses can be left out; a missing condition loops forever.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForExpression {
+    pub token: Token,
+    pub init: Option<Box<Statement>>,
+    pub condition: Option<Box<Expression>>,
+    pub update: Option<Box<Statement>>,
+    pub body: BlockStatement,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IndexExpression {
     pub token: Token,
     pub left: Box<Expression>,
     pub index: Box<Expression>,
 }
 
+/// `left[start:end]`, where either bound may be left out.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SliceExpression {
+    pub token: Token,
+    pub left: Box<Expression>,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+}
+
+/// `start..end`, or `start..=end` when `inclusive` is set.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeExpression {
+    pub token: Token,
+    pub start: Box<Expression>,
+    pub end: Box<Expression>,
+    pub inclusive: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InfixExpression {
     pub token: Token,
     pub left: Box<Expression>,
@@ -270,32 +937,87 @@ pub struct InfixExpression {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PrefixExpression {
     pub token: Token,
     pub operator: Token,
     pub right: Box<Expression>,
 }
 
+/// `left++` or `left--`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PostfixExpression {
+    pub token: Token,
+    pub operator: Token,
+    pub left: Box<Expression>,
+}
+
 // STATEMENTS
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Assignment {
     pub token: Token,
     pub name: Identifier,
+    /// The `=` or compound assignment operator that was written.
+    pub operator: Token,
+    pub value: Expression,
+}
+
+impl std::fmt::Display for Assignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match compound_value(&self.operator, &self.value) {
+            Some(value) => write!(f, "{} {} {}", self.name, self.operator, value),
+            None => write!(f, "{} = {}", self.name, self.value),
+        }
+    }
+}
+
+/// The value as written for a compound assignment, which the parser turns
+/// into `target op value`.
+fn compound_value<'a>(operator: &Token, value: &'a Expression) -> Option<&'a Expression> {
+    match (&operator.token_type, value) {
+        (
+            TokenType::PlusAssign
+            | TokenType::MinusAssign
+            | TokenType::AsteriskAssign
+            | TokenType::SlashAssign,
+            Expression::Infix(InfixExpression { right, .. }),
+        ) => Some(right),
+        _ => None,
+    }
+}
+
+/// An assignment into an element of an indexable value: `target[index] = value`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexAssignment {
+    pub token: Token,
+    pub target: Box<Expression>,
+    pub index: Box<Expression>,
     pub value: Expression,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockStatement {
     pub token: Token,
     pub statements: Vec<Statement>,
 }
 
+impl BlockStatement {
+    /// The span of the last statement in the block, if there is one.
+    pub fn last_span(&self) -> Option<Span> {
+        self.statements.last().map(Statement::span)
+    }
+}
+
 impl std::fmt::Display for BlockStatement {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let mut block_string = String::new();
 
         for statement in &self.statements {
-            block_string.push_str(&format!("{}\n", statement));
+            block_string.push_str(&format!("{};\n", statement));
         }
 
         write!(f, "{}", block_string)
@@ -303,7 +1025,31 @@ impl std::fmt::Display for BlockStatement {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReturnStatement {
     pub token: Token,
     pub return_value: Expression,
 }
+
+/// Escapes `value` for a string or char literal delimited by `quote`,
+/// undoing what the lexer's escape handling does.
+pub(crate) fn escape(value: &str, quote: char) -> String {
+    let mut escaped = String::new();
+
+    for ch in value.chars() {
+        match ch {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            ch if ch == quote => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            ch => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
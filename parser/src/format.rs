@@ -0,0 +1,72 @@
+use anyhow::{Error, Result};
+use lexer::{token::TokenType, Lexer};
+
+use crate::Parser;
+
+const INDENT: &str = "    ";
+
+/// Parses `source` and prints it back in canonical form using the tree's
+/// `Display` impls: one statement per line, each ending in `;`, and blocks
+/// indented by four spaces.
+///
+/// The output parses to the same tree as the input, so formatting is
+/// idempotent. Comments aren't part of the tree, so source containing them
+/// is rejected rather than formatted without them.
+pub fn format_source(source: &str) -> Result<String, Error> {
+    let mut lexer = Lexer::new(source);
+    lexer.by_ref().for_each(drop);
+
+    if lexer.skipped_comment() {
+        return Err(Error::msg(
+            "format: source contains comments, which would be lost",
+        ));
+    }
+
+    let mut parser = Parser::new(Lexer::new(source));
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let mut output = String::new();
+
+    for statement in program.statements.iter() {
+        output.push_str(&indent(&format!("{};", statement)));
+    }
+
+    Ok(output)
+}
+
+/// Indents each line of `displayed` by how many blocks are open at its
+/// start. `Display` puts every statement in a block on its own line, and
+/// string and char literals are escaped, so no brace is split across lines.
+fn indent(displayed: &str) -> String {
+    let mut output = String::new();
+    let mut depth: usize = 0;
+
+    for line in displayed.lines() {
+        let tokens = Lexer::new(line)
+            .map(|token| token.token_type)
+            .collect::<Vec<TokenType>>();
+
+        let closing = tokens
+            .iter()
+            .take_while(|token_type| **token_type == TokenType::RBrace)
+            .count();
+        let opened = tokens
+            .iter()
+            .filter(|token_type| **token_type == TokenType::LBrace)
+            .count();
+        let closed = tokens
+            .iter()
+            .filter(|token_type| **token_type == TokenType::RBrace)
+            .count();
+
+        output.push_str(&INDENT.repeat(depth.saturating_sub(closing)));
+        output.push_str(line.trim());
+        output.push('\n');
+
+        depth = (depth + opened).saturating_sub(closed);
+    }
+
+    output
+}
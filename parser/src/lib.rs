@@ -4,11 +4,15 @@ use anyhow::{Error, Result};
 use log::info;
 
 pub mod ast;
+pub mod format;
+pub mod typecheck;
 
 use ast::{
-    ArrayLiteral, Assignment, BlockStatement, BooleanLiteral, CallExpression, Expression,
-    FunctionLiteral, Identifier, IfExpression, IndexExpression, InfixExpression, IntegerLiteral,
-    Literal, PrefixExpression, Program, ReturnStatement, Statement, StringLiteral,
+    ArrayLiteral, Assignment, BlockStatement, BooleanLiteral, CallExpression, CharLiteral,
+    Expression, FloatLiteral, ForExpression, FunctionLiteral, HashLiteral, Identifier,
+    IfExpression, IndexAssignment, IndexExpression, InfixExpression, IntegerLiteral, Literal,
+    NullLiteral, PostfixExpression, PrefixExpression, Program, RangeExpression, ReturnStatement,
+    SliceExpression, Statement, StringLiteral, TernaryExpression, TupleLiteral, WhileExpression,
 };
 
 use lexer::token::{Token, TokenType};
@@ -16,33 +20,85 @@ use lexer::Lexer;
 
 type ParseResult = Result<Expression>;
 
+/// A syntax error and the 1-based line and column of the token it was
+/// reported at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.message, self.line, self.column)
+    }
+}
+
 type PrefixParseFn = fn(&mut Parser) -> ParseResult;
 type InfixParseFn = fn(&mut Parser, Expression) -> ParseResult;
 
-#[derive(Copy, Clone, PartialOrd, PartialEq)]
-enum Precedence {
+/// How tightly an operator binds, from loosest to tightest. Variants
+/// compare in that order, so `Precedence::Product > Precedence::Sum`.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Precedence {
     Lowest,
+    Ternary,
+    LogicalOr,
+    LogicalAnd,
     Equals,
     LessGreater,
+    Range,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
     Sum,
     Product,
     Prefix,
+    Postfix,
     Call,
     Index,
 }
 
+/// How tightly the infix or postfix operator `token` binds its left
+/// operand. Tokens that can't continue an expression get `Lowest`.
+///
+/// This is the table the parser itself uses, so tools that need to know
+/// when parentheses are required can rely on it.
+pub fn precedence_of(token: &Token) -> Precedence {
+    match token.token_type {
+        TokenType::Question => Precedence::Ternary,
+        TokenType::Or => Precedence::LogicalOr,
+        TokenType::And => Precedence::LogicalAnd,
+        TokenType::Eq | TokenType::NotEq => Precedence::Equals,
+        TokenType::Lt | TokenType::Gt | TokenType::LtEq | TokenType::GtEq => {
+            Precedence::LessGreater
+        }
+        TokenType::DotDot | TokenType::DotDotEq => Precedence::Range,
+        TokenType::Pipe => Precedence::BitOr,
+        TokenType::Caret => Precedence::BitXor,
+        TokenType::Ampersand => Precedence::BitAnd,
+        TokenType::ShiftLeft | TokenType::ShiftRight => Precedence::Shift,
+        TokenType::Plus | TokenType::Minus => Precedence::Sum,
+        TokenType::Slash | TokenType::Asterisk | TokenType::Percent => Precedence::Product,
+        TokenType::Increment | TokenType::Decrement => Precedence::Postfix,
+        TokenType::LParen => Precedence::Call,
+        TokenType::LBracket => Precedence::Index,
+        _ => Precedence::Lowest,
+    }
+}
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
 
-    errors: Vec<String>,
+    errors: Vec<ParseError>,
 
     current_token: Option<Token>,
     peek_token: Option<Token>,
 
     prefix_parse_fns: HashMap<TokenType, PrefixParseFn>,
     infix_parse_fns: HashMap<TokenType, InfixParseFn>,
-
-    precedences: HashMap<TokenType, Precedence>,
 }
 
 impl<'a> Parser<'a> {
@@ -60,32 +116,44 @@ impl<'a> Parser<'a> {
         Err(Error::msg(error_message))
     }
 
-    pub fn errors(&self) -> &Vec<String> {
+    pub fn errors(&self) -> &Vec<ParseError> {
         &self.errors
     }
 
+    fn push_error(&mut self, message: String, token: &Token) {
+        self.errors.push(ParseError {
+            message,
+            line: token.line,
+            column: token.column,
+        });
+    }
+
     fn peek_error(&mut self, token_type: &TokenType) -> () {
+        let peek_token = self.peek_token.clone().unwrap();
+
         let message = format!(
             "Expected next token to be {}, got {}",
-            token_type,
-            self.peek_token.as_ref().unwrap()
+            token_type, peek_token
         );
 
-        self.errors.push(message);
+        self.push_error(message, &peek_token);
+    }
+
+    /// Skips to the end of the current statement so parsing can carry on
+    /// after an error.
+    fn synchronize(&mut self) {
+        while !self.current_token_is(TokenType::Semicolon) && !self.current_token_is(TokenType::Eof)
+        {
+            self.next_token();
+        }
     }
 
     fn current_precedence(&mut self) -> Precedence {
-        *self
-            .precedences
-            .get(&self.current_token.as_ref().unwrap().token_type)
-            .unwrap_or(&Precedence::Lowest)
+        precedence_of(self.current_token.as_ref().unwrap())
     }
 
     fn peek_precedence(&mut self) -> Precedence {
-        *self
-            .precedences
-            .get(&self.peek_token.as_ref().unwrap().token_type)
-            .unwrap_or(&Precedence::Lowest)
+        precedence_of(self.peek_token.as_ref().unwrap())
     }
 
     pub fn new(lexer: Lexer<'a>) -> Self {
@@ -96,26 +164,17 @@ impl<'a> Parser<'a> {
             peek_token: None,
             prefix_parse_fns: HashMap::new(),
             infix_parse_fns: HashMap::new(),
-            precedences: HashMap::from([
-                (TokenType::Eq, Precedence::Equals),
-                (TokenType::NotEq, Precedence::Equals),
-                (TokenType::Lt, Precedence::LessGreater),
-                (TokenType::Gt, Precedence::LessGreater),
-                (TokenType::Plus, Precedence::Sum),
-                (TokenType::Minus, Precedence::Sum),
-                (TokenType::Slash, Precedence::Product),
-                (TokenType::Asterisk, Precedence::Product),
-                (TokenType::LParen, Precedence::Call),
-                (TokenType::LBracket, Precedence::Index),
-            ]),
         };
 
         parser.register_prefix(TokenType::Ident, |p| Parser::parse_identifier(p));
         parser.register_prefix(TokenType::Function, |p| Parser::parse_function_literal(p));
         parser.register_prefix(TokenType::LParen, |p| Parser::parse_grouped_expression(p));
         parser.register_prefix(TokenType::If, |p| Parser::parse_if_expression(p));
+        parser.register_prefix(TokenType::While, |p| Parser::parse_while_expression(p));
+        parser.register_prefix(TokenType::For, |p| Parser::parse_for_expression(p));
         parser.register_prefix(TokenType::Bang, |p| Parser::parse_prefix_expression(p));
         parser.register_prefix(TokenType::Minus, |p| Parser::parse_prefix_expression(p));
+        parser.register_prefix(TokenType::Tilde, |p| Parser::parse_prefix_expression(p));
 
         parser.register_prefix(TokenType::Dollar, |p| {
             Parser::parse_variable_reference_expression(p)
@@ -123,9 +182,13 @@ impl<'a> Parser<'a> {
 
         parser.register_prefix(TokenType::True, |p| Parser::parse_boolean_literal(p));
         parser.register_prefix(TokenType::False, |p| Parser::parse_boolean_literal(p));
+        parser.register_prefix(TokenType::Null, |p| Parser::parse_null_literal(p));
         parser.register_prefix(TokenType::Int, |p| Parser::parse_integer_literal(p));
+        parser.register_prefix(TokenType::Float, |p| Parser::parse_float_literal(p));
         parser.register_prefix(TokenType::String, |p| Parser::parse_string_literal(p));
+        parser.register_prefix(TokenType::Char, |p| Parser::parse_char_literal(p));
         parser.register_prefix(TokenType::LBracket, |p| Parser::parse_array_literal(p));
+        parser.register_prefix(TokenType::LBrace, |p| Parser::parse_hash_literal(p));
 
         parser.register_infix(TokenType::LParen, |p, left| {
             Parser::parse_call_expression(p, left)
@@ -133,6 +196,21 @@ impl<'a> Parser<'a> {
         parser.register_infix(TokenType::LBracket, |p, left| {
             Parser::parse_index_expression(p, left)
         });
+        parser.register_infix(TokenType::Question, |p, left| {
+            Parser::parse_ternary_expression(p, left)
+        });
+        parser.register_infix(TokenType::DotDot, |p, left| {
+            Parser::parse_range_expression(p, left)
+        });
+        parser.register_infix(TokenType::DotDotEq, |p, left| {
+            Parser::parse_range_expression(p, left)
+        });
+        parser.register_infix(TokenType::Increment, |p, left| {
+            Parser::parse_postfix_expression(p, left)
+        });
+        parser.register_infix(TokenType::Decrement, |p, left| {
+            Parser::parse_postfix_expression(p, left)
+        });
 
         parser.register_infix(TokenType::Plus, |p, left| {
             Parser::parse_infix_expression(p, left)
@@ -146,9 +224,18 @@ impl<'a> Parser<'a> {
         parser.register_infix(TokenType::Asterisk, |p, left| {
             Parser::parse_infix_expression(p, left)
         });
+        parser.register_infix(TokenType::Percent, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
         parser.register_infix(TokenType::Eq, |p, left| {
             Parser::parse_infix_expression(p, left)
         });
+        parser.register_infix(TokenType::And, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::Or, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
         parser.register_infix(TokenType::NotEq, |p, left| {
             Parser::parse_infix_expression(p, left)
         });
@@ -158,6 +245,27 @@ impl<'a> Parser<'a> {
         parser.register_infix(TokenType::Gt, |p, left| {
             Parser::parse_infix_expression(p, left)
         });
+        parser.register_infix(TokenType::LtEq, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::GtEq, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::Ampersand, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::Pipe, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::Caret, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::ShiftLeft, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
+        parser.register_infix(TokenType::ShiftRight, |p, left| {
+            Parser::parse_infix_expression(p, left)
+        });
 
         parser.next_token();
         parser.next_token();
@@ -167,7 +275,9 @@ impl<'a> Parser<'a> {
 
     pub fn next_token(&mut self) -> Option<Token> {
         self.current_token = self.peek_token.take();
-        self.peek_token = Some(self.lexer.next_token());
+        // Once the lexer runs dry the `Eof` token just moved into
+        // `current_token` stays the lookahead.
+        self.peek_token = self.lexer.next().or_else(|| self.current_token.clone());
 
         self.current_token.clone()
     }
@@ -183,8 +293,12 @@ impl<'a> Parser<'a> {
         if self.current_token.is_some() && self.current_token.as_ref().unwrap().token_type == t {
             true
         } else {
-            self.errors
-                .push(format!("Expected {:?}, got {:?}", t, self.current_token));
+            let current_token = self.current_token.clone().unwrap_or_default();
+
+            self.push_error(
+                format!("Expected {:?}, got {:?}", t, current_token.token_type),
+                &current_token,
+            );
             false
         }
     }
@@ -236,6 +350,12 @@ impl<'a> Parser<'a> {
         })))
     }
 
+    fn parse_null_literal(&mut self) -> Result<Expression> {
+        Ok(Expression::Literal(Literal::Null(NullLiteral {
+            token: self.current_token.clone().unwrap(),
+        })))
+    }
+
     fn parse_string_literal(&mut self) -> Result<Expression> {
         let current_token = self.current_token.clone().unwrap();
 
@@ -247,13 +367,41 @@ impl<'a> Parser<'a> {
         })))
     }
 
+    fn parse_char_literal(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        let value = current_token.literal.chars().next().ok_or_else(|| {
+            Error::msg(format!(
+                "Could not parse {} as a char",
+                current_token.literal
+            ))
+        })?;
+
+        Ok(Expression::Literal(Literal::Char(CharLiteral {
+            token: current_token,
+            value,
+        })))
+    }
+
     pub fn parse_program(&mut self) -> Result<Program> {
         let mut program = Program::default();
 
         while !self.current_token_is(TokenType::Eof) {
+            let error_count = self.errors.len();
+
             match self.parse_statement() {
                 Ok(stmt) => program.statements.push(stmt),
-                Err(e) => self.errors.push(e.to_string()),
+                Err(e) => {
+                    // Errors reported while parsing the statement are more
+                    // specific than the one it bailed out with.
+                    if self.errors.len() == error_count {
+                        let current_token = self.current_token.clone().unwrap_or_default();
+
+                        self.push_error(e.to_string(), &current_token);
+                    }
+
+                    self.synchronize();
+                }
             }
             self.next_token();
         }
@@ -261,9 +409,15 @@ impl<'a> Parser<'a> {
         if self.errors.is_empty() {
             Ok(program)
         } else {
+            let errors = self
+                .errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<String>>();
+
             Err(Error::msg(format!(
                 "Parser has errors: {}",
-                self.errors.join(", ")
+                errors.join(", ")
             )))
         }
     }
@@ -272,8 +426,10 @@ impl<'a> Parser<'a> {
         if let Some(token) = &self.current_token {
             match &token.token_type {
                 TokenType::Return => self.parse_return_statement(),
+                TokenType::Break | TokenType::Continue => self.parse_loop_control_statement(),
                 TokenType::Ident if token.literal.starts_with('$') => {
-                    if self.peek_token_is(&TokenType::Assign) {
+                    if self.peek_token_is(&TokenType::Assign) || self.peek_is_compound_assignment()
+                    {
                         self.parse_assignment_statement()
                     } else {
                         self.parse_expression_statement()
@@ -310,18 +466,26 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_assignment_statement(&mut self) -> Result<Statement> {
+        let assignment = self.parse_assignment()?;
+
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+        }
+
+        Ok(Statement::Assign(assignment))
+    }
+
+    /// Parses `$name = value` (or a compound assignment) starting at the
+    /// name, leaving the value's last token current.
+    fn parse_assignment(&mut self) -> Result<Assignment> {
         // Ensure the variable name is an identifier.
         let name_token = if let Some(token) = &self.current_token {
             if token.token_type == TokenType::Ident {
                 token.clone()
             } else {
-                self.errors
-                    .push(format!("Expected identifier, got {:?}", token));
                 return Err(Error::msg(format!("Expected identifier, got {:?}", token)));
             }
         } else {
-            self.errors
-                .push("Expected identifier, got None".to_string());
             return Err(Error::msg("Expected identifier, got None"));
         };
 
@@ -329,7 +493,9 @@ impl<'a> Parser<'a> {
         self.next_token();
 
         if let Some(token) = &self.current_token {
-            if token.token_type == TokenType::Assign {
+            if token.token_type == TokenType::Assign || compound_operator(token).is_some() {
+                let assign_token = token.clone();
+
                 // Parse as assignment
                 self.next_token();
 
@@ -337,28 +503,24 @@ impl<'a> Parser<'a> {
                     "parse_assignment_statement: Next token: {:?}",
                     self.current_token
                 );
-                let value_expression = self.parse_expression(Precedence::Lowest);
-
-                if let Ok(value_expression) = value_expression {
-                    let variable_assignment = Assignment {
+                let value_expression = self.parse_operand(&assign_token, Precedence::Lowest)?;
+
+                let target = Expression::Identifier(Identifier {
+                    token: name_token.clone(),
+                    value: name_token.literal.clone(),
+                });
+                let value_expression =
+                    desugar_compound_assignment(&assign_token, target, value_expression);
+
+                return Ok(Assignment {
+                    token: name_token.clone(),
+                    name: Identifier {
                         token: name_token.clone(),
-                        name: Identifier {
-                            token: name_token.clone(),
-                            value: name_token.literal.clone(),
-                        },
-                        value: value_expression,
-                    };
-
-                    if self.peek_token_is(&TokenType::Semicolon) {
-                        self.next_token();
-                    }
-
-                    return Ok(Statement::Assign(variable_assignment));
-                } else {
-                    self.errors
-                        .push("Expected an expression after =".to_string());
-                    return Err(Error::msg("Expected an expression after ="));
-                }
+                        value: name_token.literal.clone(),
+                    },
+                    operator: assign_token,
+                    value: value_expression,
+                });
             } else {
                 // If it's not an assignment, then it's not an assignment statement.
                 return Err(Error::msg(format!(
@@ -367,7 +529,6 @@ impl<'a> Parser<'a> {
                 )));
             }
         } else {
-            self.errors.push("Unexpected end of input".to_string());
             return Err(Error::msg("Unexpected end of input"));
         }
     }
@@ -380,9 +541,18 @@ impl<'a> Parser<'a> {
             .map(|x| *x);
 
         if prefix_fn.is_none() {
+            let current_token = self.current_token.as_ref().unwrap();
+
+            if current_token.token_type == TokenType::Illegal {
+                return Err(Error::msg(format!(
+                    "Illegal token `{}`",
+                    current_token.literal
+                )));
+            }
+
             return Err(Error::msg(format!(
                 "No prefix parse function for {:?}",
-                self.current_token.as_ref().unwrap().token_type
+                current_token.token_type
             )));
         }
 
@@ -401,12 +571,32 @@ impl<'a> Parser<'a> {
 
             self.next_token();
 
-            left = infix_fn.unwrap()(self, left.unwrap());
+            left = infix_fn.unwrap()(self, left?);
         }
 
         left
     }
 
+    /// Parses the operand following `operator`, which has just been
+    /// consumed. Only an operand that can't start an expression is blamed on
+    /// `operator`; errors further into it are reported as they are.
+    fn parse_operand(&mut self, operator: &Token, precedence: Precedence) -> Result<Expression> {
+        let starts_expression = self
+            .prefix_parse_fns
+            .contains_key(&self.current_token.as_ref().unwrap().token_type);
+
+        if starts_expression {
+            return self.parse_expression(precedence);
+        }
+
+        self.parse_expression(precedence).map_err(|e| {
+            Error::msg(format!(
+                "Expected an expression after {}: {}",
+                operator.literal, e
+            ))
+        })
+    }
+
     fn parse_expression_list(&mut self, end: TokenType) -> Result<Vec<Expression>> {
         let mut list = vec![];
 
@@ -438,6 +628,12 @@ impl<'a> Parser<'a> {
     fn parse_expression_statement(&mut self) -> Result<Statement> {
         let expr = self.parse_expression(Precedence::Lowest)?;
 
+        if let Expression::Index(index_expression) = &expr {
+            if self.peek_token_is(&TokenType::Assign) || self.peek_is_compound_assignment() {
+                return self.parse_index_assignment(index_expression.clone());
+            }
+        }
+
         if self.peek_token_is(&TokenType::Semicolon) {
             self.next_token();
         }
@@ -445,6 +641,37 @@ impl<'a> Parser<'a> {
         Ok(Statement::Expr(expr))
     }
 
+    fn parse_index_assignment(&mut self, target: IndexExpression) -> Result<Statement> {
+        // Move onto the `=` (or compound assignment operator).
+        self.next_token();
+
+        let current_token = self.current_token.clone().unwrap();
+
+        self.next_token();
+
+        let value = self.parse_operand(&current_token, Precedence::Lowest)?;
+
+        let value =
+            desugar_compound_assignment(&current_token, Expression::Index(target.clone()), value);
+
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+        }
+
+        Ok(Statement::IndexAssign(IndexAssignment {
+            token: current_token,
+            target: target.left,
+            index: target.index,
+            value,
+        }))
+    }
+
+    fn peek_is_compound_assignment(&self) -> bool {
+        self.peek_token
+            .as_ref()
+            .is_some_and(|token| compound_operator(token).is_some())
+    }
+
     fn parse_block_statement(&mut self) -> Result<BlockStatement> {
         let current_token = self.current_token.clone().unwrap();
 
@@ -538,20 +765,100 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_grouped_expression(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
         self.next_token();
 
-        let expression = self.parse_expression(Precedence::Lowest);
+        let expression = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token_is(&TokenType::Comma) {
+            return self.parse_tuple_literal(current_token, expression);
+        }
 
         if !self.expect_peek(&TokenType::RParen) {
             return Err(Error::msg("Expected RParen"));
         }
 
-        expression
+        Ok(expression)
+    }
+
+    /// Parses the rest of a tuple after its first element, allowing a
+    /// trailing comma before the closing paren.
+    fn parse_tuple_literal(&mut self, token: Token, first: Expression) -> Result<Expression> {
+        let mut elements = vec![first];
+
+        while self.peek_token_is(&TokenType::Comma) {
+            self.next_token();
+
+            if self.peek_token_is(&TokenType::RParen) {
+                break;
+            }
+
+            self.next_token();
+            elements.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if !self.expect_peek(&TokenType::RParen) {
+            return Err(Error::msg("Expected RParen"));
+        }
+
+        Ok(Expression::Literal(Literal::Tuple(TupleLiteral {
+            token,
+            elements,
+        })))
+    }
+
+    fn parse_hash_literal(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(&TokenType::RBrace) {
+            self.next_token();
+
+            let key = self.parse_expression(Precedence::Lowest)?;
+
+            if !self.expect_peek(&TokenType::Colon) {
+                return Err(Error::msg(format!(
+                    "Expected Colon after hash key, got {:?}",
+                    self.peek_token
+                )));
+            }
+
+            self.next_token();
+
+            let value = self.parse_expression(Precedence::Lowest)?;
+
+            pairs.push((key, value));
+
+            if !self.peek_token_is(&TokenType::RBrace) && !self.expect_peek(&TokenType::Comma) {
+                return Err(Error::msg(format!(
+                    "Expected RBrace or comma, got {:?}",
+                    self.peek_token
+                )));
+            }
+        }
+
+        self.next_token();
+
+        Ok(Expression::Literal(Literal::Hash(HashLiteral {
+            token: current_token,
+            pairs,
+        })))
     }
 
     fn parse_identifier(&mut self) -> Result<Expression> {
         let current_token = self.current_token.clone().unwrap();
 
+        // Inside an expression, an assignment evaluates to the assigned
+        // value. It takes everything to its right, so `1 + $x = 2` assigns
+        // 2 to `$x`.
+        if current_token.literal.starts_with('$')
+            && (self.peek_token_is(&TokenType::Assign) || self.peek_is_compound_assignment())
+        {
+            return Ok(Expression::Assign(Box::new(self.parse_assignment()?)));
+        }
+
         let identifier = Identifier {
             token: current_token,
             value: self.current_token.as_ref().unwrap().to_string(),
@@ -563,13 +870,23 @@ impl<'a> Parser<'a> {
     fn parse_integer_literal(&mut self) -> Result<Expression> {
         let current_token = self.current_token.clone().unwrap();
 
-        let value = self
-            .current_token
-            .as_ref()
-            .unwrap()
-            .to_string()
-            .parse::<i64>()
-            .unwrap();
+        // The lexer has already checked the digits, but they may still carry
+        // `_` separators, a radix prefix, or be too large for an i64.
+        let digits = current_token.literal.replace('_', "");
+
+        let (digits, radix) = match digits.get(..2) {
+            Some("0x" | "0X") => (&digits[2..], 16),
+            Some("0o" | "0O") => (&digits[2..], 8),
+            Some("0b" | "0B") => (&digits[2..], 2),
+            _ => (&digits[..], 10),
+        };
+
+        let value = i64::from_str_radix(digits, radix).map_err(|_| {
+            Error::msg(format!(
+                "Could not parse {} as an integer",
+                current_token.literal
+            ))
+        })?;
 
         Ok(Expression::Literal(Literal::Integer(IntegerLiteral {
             token: current_token,
@@ -577,35 +894,55 @@ impl<'a> Parser<'a> {
         })))
     }
 
-    fn parse_call_arguments(&mut self) -> Vec<Expression> {
+    fn parse_float_literal(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        let value = current_token
+            .literal
+            .replace('_', "")
+            .parse::<f64>()
+            .map_err(|_| {
+                Error::msg(format!(
+                    "Could not parse {} as a float",
+                    current_token.literal
+                ))
+            })?;
+
+        Ok(Expression::Literal(Literal::Float(FloatLiteral {
+            token: current_token,
+            value,
+        })))
+    }
+
+    fn parse_call_arguments(&mut self) -> Result<Vec<Expression>> {
         let mut arguments = vec![];
 
         if self.peek_token_is(&TokenType::RParen) {
             self.next_token(); // Consume the RParen and exit
-            return arguments;
+            return Ok(arguments);
         }
 
         self.next_token(); // Consume the LParen
 
-        arguments.push(self.parse_expression(Precedence::Lowest).unwrap());
+        arguments.push(self.parse_expression(Precedence::Lowest)?);
 
         while self.peek_token_is(&TokenType::Comma) {
             self.next_token(); // Consume the comma
             self.next_token(); // Consume the next token
-            arguments.push(self.parse_expression(Precedence::Lowest).unwrap());
+            arguments.push(self.parse_expression(Precedence::Lowest)?);
         }
 
         if !self.expect_peek(&TokenType::RParen) {
-            return vec![];
+            return Ok(vec![]);
         }
 
-        arguments
+        Ok(arguments)
     }
 
     fn parse_call_expression(&mut self, function: Expression) -> Result<Expression> {
         let current_token = self.current_token.clone().unwrap();
 
-        let arguments = self.parse_call_arguments();
+        let arguments = self.parse_call_arguments()?;
 
         Ok(Expression::Call(CallExpression {
             token: current_token,
@@ -615,6 +952,8 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_if_expression(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
         self.expect_peek(&TokenType::LParen);
         self.next_token();
 
@@ -634,27 +973,209 @@ impl<'a> Parser<'a> {
 
         return Ok(Expression::If(IfExpression {
             condition: Box::new(condition),
-            token: self.current_token.clone().unwrap(),
+            token: current_token,
             consequence,
             alternative,
         }));
     }
 
+    fn parse_while_expression(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        if !self.expect_peek(&TokenType::LParen) {
+            return Err(Error::msg("Expected LParen"));
+        }
+
+        self.next_token();
+
+        let condition = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(&TokenType::RParen) {
+            return Err(Error::msg("Expected RParen"));
+        }
+
+        if !self.expect_peek(&TokenType::LBrace) {
+            return Err(Error::msg("Expected LBrace"));
+        }
+
+        let body = self.parse_block_statement()?;
+
+        Ok(Expression::While(WhileExpression {
+            token: current_token,
+            condition: Box::new(condition),
+            body,
+        }))
+    }
+
+    fn parse_for_expression(&mut self) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        if !self.expect_peek(&TokenType::LParen) {
+            return Err(Error::msg("Expected LParen"));
+        }
+
+        // Parsing a statement also consumes its trailing semicolon, so
+        // either way we finish this clause sitting on the first `;`.
+        let init = if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+
+            None
+        } else {
+            self.next_token();
+
+            let init = self.parse_statement()?;
+
+            if !self.current_token_is(TokenType::Semicolon) {
+                return Err(Error::msg("Expected Semicolon after for loop initialiser"));
+            }
+
+            Some(Box::new(init))
+        };
+
+        let condition = if self.peek_token_is(&TokenType::Semicolon) {
+            None
+        } else {
+            self.next_token();
+
+            Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+        };
+
+        if !self.expect_peek(&TokenType::Semicolon) {
+            return Err(Error::msg("Expected Semicolon after for loop condition"));
+        }
+
+        let update = if self.peek_token_is(&TokenType::RParen) {
+            None
+        } else {
+            self.next_token();
+
+            Some(Box::new(self.parse_statement()?))
+        };
+
+        if !self.expect_peek(&TokenType::RParen) {
+            return Err(Error::msg("Expected RParen"));
+        }
+
+        if !self.expect_peek(&TokenType::LBrace) {
+            return Err(Error::msg("Expected LBrace"));
+        }
+
+        let body = self.parse_block_statement()?;
+
+        Ok(Expression::For(ForExpression {
+            token: current_token,
+            init,
+            condition,
+            update,
+            body,
+        }))
+    }
+
+    fn parse_postfix_expression(&mut self, left: Expression) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        let operator = self.current_token.as_ref().unwrap().clone();
+
+        Ok(Expression::Postfix(PostfixExpression {
+            token: current_token,
+            operator,
+            left: Box::new(left),
+        }))
+    }
+
+    /// Parses `left[index]`, or the slice `left[start:end]` with either
+    /// bound optional.
     fn parse_index_expression(&mut self, left: Expression) -> Result<Expression> {
         let current_token = self.current_token.clone().unwrap();
 
         self.next_token();
 
-        let index = self.parse_expression(Precedence::Lowest)?;
+        let start = if self.current_token_is(TokenType::Colon) {
+            None
+        } else {
+            let index = self.parse_expression(Precedence::Lowest)?;
+
+            if !self.peek_token_is(&TokenType::Colon) {
+                if !self.expect_peek(&TokenType::RBracket) {
+                    return Err(Error::msg("Expected RBracket"));
+                }
+
+                return Ok(Expression::Index(IndexExpression {
+                    token: current_token,
+                    left: Box::new(left),
+                    index: Box::new(index),
+                }));
+            }
+
+            self.next_token();
+
+            Some(Box::new(index))
+        };
+
+        // On the `:`.
+        let end = if self.peek_token_is(&TokenType::RBracket) {
+            None
+        } else {
+            self.next_token();
+
+            Some(Box::new(self.parse_expression(Precedence::Lowest)?))
+        };
 
         if !self.expect_peek(&TokenType::RBracket) {
             return Err(Error::msg("Expected RBracket"));
         }
 
-        Ok(Expression::Index(IndexExpression {
+        Ok(Expression::Slice(SliceExpression {
             token: current_token,
             left: Box::new(left),
-            index: Box::new(index),
+            start,
+            end,
+        }))
+    }
+
+    /// Parses `condition ? consequence : alternative`. Both branches are
+    /// parsed at the lowest precedence, which makes nested ternaries
+    /// right-associative.
+    fn parse_ternary_expression(&mut self, condition: Expression) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        self.next_token();
+
+        let consequence = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(&TokenType::Colon) {
+            return Err(Error::msg(format!(
+                "Expected Colon in ternary expression, got {:?}",
+                self.peek_token
+            )));
+        }
+
+        self.next_token();
+
+        let alternative = self.parse_expression(Precedence::Lowest)?;
+
+        Ok(Expression::Ternary(TernaryExpression {
+            token: current_token,
+            condition: Box::new(condition),
+            consequence: Box::new(consequence),
+            alternative: Box::new(alternative),
+        }))
+    }
+
+    fn parse_range_expression(&mut self, start: Expression) -> Result<Expression> {
+        let current_token = self.current_token.clone().unwrap();
+
+        let inclusive = current_token.token_type == TokenType::DotDotEq;
+
+        self.next_token();
+
+        let end = self.parse_expression(Precedence::Range)?;
+
+        Ok(Expression::Range(RangeExpression {
+            token: current_token,
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
         }))
     }
 
@@ -667,18 +1188,14 @@ impl<'a> Parser<'a> {
 
         self.next_token();
 
-        match self.parse_expression(precedence) {
+        match self.parse_operand(&operator, precedence) {
             Ok(right) => Ok(Expression::Infix(InfixExpression {
                 token: current_token,
                 operator,
                 left: Box::new(left),
                 right: Box::new(right),
             })),
-            Err(e) => {
-                self.errors
-                    .push("Expected an expression, but found none.".to_string());
-                return Err(Error::msg(e.to_string()));
-            }
+            Err(e) => Err(e),
         }
     }
 
@@ -689,7 +1206,7 @@ impl<'a> Parser<'a> {
 
         self.next_token();
 
-        let right = self.parse_expression(Precedence::Prefix).unwrap();
+        let right = self.parse_operand(&operator, Precedence::Prefix)?;
 
         Ok(Expression::Prefix(PrefixExpression {
             token: current_token,
@@ -722,6 +1239,19 @@ impl<'a> Parser<'a> {
         }))
     }
 
+    fn parse_loop_control_statement(&mut self) -> Result<Statement> {
+        let current_token = self.current_token.clone().unwrap();
+
+        if self.peek_token_is(&TokenType::Semicolon) {
+            self.next_token();
+        }
+
+        match current_token.token_type {
+            TokenType::Break => Ok(Statement::Break(current_token)),
+            _ => Ok(Statement::Continue(current_token)),
+        }
+    }
+
     pub fn register_prefix(&mut self, token_type: TokenType, function: PrefixParseFn) {
         self.prefix_parse_fns.insert(token_type, function);
     }
@@ -730,3 +1260,40 @@ impl<'a> Parser<'a> {
         self.infix_parse_fns.insert(token_type, function);
     }
 }
+
+/// Returns the binary operator a compound assignment token such as `+=`
+/// applies, positioned where the compound token was.
+fn compound_operator(token: &Token) -> Option<Token> {
+    let (token_type, literal) = match token.token_type {
+        TokenType::PlusAssign => (TokenType::Plus, "+"),
+        TokenType::MinusAssign => (TokenType::Minus, "-"),
+        TokenType::AsteriskAssign => (TokenType::Asterisk, "*"),
+        TokenType::SlashAssign => (TokenType::Slash, "/"),
+        _ => return None,
+    };
+
+    Some(Token {
+        line: token.line,
+        column: token.column,
+        ..Token::new(token_type, literal)
+    })
+}
+
+/// Turns the value of `target op= value` into `target op value`, so the
+/// compiler only ever sees plain assignments. For index targets this means
+/// the target and index expressions are evaluated twice.
+fn desugar_compound_assignment(
+    assign_token: &Token,
+    target: Expression,
+    value: Expression,
+) -> Expression {
+    match compound_operator(assign_token) {
+        Some(operator) => Expression::Infix(InfixExpression {
+            token: operator.clone(),
+            left: Box::new(target),
+            operator,
+            right: Box::new(value),
+        }),
+        None => value,
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+
+use crate::ast::{
+    ArrayLiteral, Assignment, BlockStatement, CallExpression, Expression, ForExpression,
+    FunctionLiteral, HashLiteral, IfExpression, IndexAssignment, IndexExpression, InfixExpression,
+    Literal, PostfixExpression, PrefixExpression, Program, RangeExpression, ReturnStatement,
+    SliceExpression, Span, Statement, TernaryExpression, TupleLiteral, WhileExpression,
+};
+
+/// The coarse type the checker infers for an expression. `Unknown` covers
+/// anything it can't work out statically, such as call results or function
+/// parameters, and never produces an error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+    Char,
+    Array,
+    Hash,
+    Tuple,
+    Range,
+    Unknown,
+}
+
+impl Type {
+    fn is_numeric(self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+}
+
+impl std::fmt::Display for Type {
+    /// Uses the same names as `Object::type_name`, so static errors read
+    /// like the runtime ones.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Type::Int => "INTEGER",
+            Type::Float => "FLOAT",
+            Type::Bool => "BOOLEAN",
+            Type::String => "STRING",
+            Type::Char => "CHAR",
+            Type::Array => "ARRAY",
+            Type::Hash => "HASH",
+            Type::Tuple => "TUPLE",
+            Type::Range => "RANGE",
+            Type::Unknown => "UNKNOWN",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A mismatch found by `typecheck` and the span of the offending expression.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl std::fmt::Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+/// Walks `program` and reports operations that are bound to fail at runtime,
+/// such as `1 + true` or indexing an integer.
+///
+/// The check is deliberately conservative: a variable assigned values of
+/// different types anywhere in the program becomes `Unknown`, so loops,
+/// branches and functions reading a variable reassigned after they're
+/// defined never produce false positives.
+pub fn typecheck(program: &Program) -> Vec<TypeError> {
+    let mut settled = HashMap::new();
+
+    // Each pass can only turn more variables `Unknown`, so this stops once
+    // a pass agrees with the one before it.
+    loop {
+        let mut checker = Checker {
+            assigned: settled.clone(),
+            settled,
+            ..Checker::default()
+        };
+
+        for statement in program.statements.iter() {
+            checker.check_statement(statement);
+        }
+
+        if checker.assigned == checker.settled {
+            return checker.errors;
+        }
+
+        settled = checker.assigned;
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    variables: HashMap<String, Type>,
+    /// Every type assigned to each name by the previous pass, joined into
+    /// one, or `Unknown` where they differ.
+    settled: HashMap<String, Type>,
+    /// The same for this pass, seeded from `settled`.
+    assigned: HashMap<String, Type>,
+    errors: Vec<TypeError>,
+}
+
+impl Checker {
+    fn error(&mut self, message: String, span: Span) -> Type {
+        self.errors.push(TypeError { message, span });
+
+        Type::Unknown
+    }
+
+    fn define(&mut self, name: &str, t: Type) {
+        let join = |existing: Option<&Type>| match existing {
+            Some(existing) if *existing != t => Type::Unknown,
+            _ => t,
+        };
+
+        let settled = join(self.settled.get(name));
+        let assigned = join(self.assigned.get(name));
+
+        self.assigned.insert(name.to_string(), assigned);
+        self.variables.insert(name.to_string(), settled);
+    }
+
+    fn check_block(&mut self, block: &BlockStatement) {
+        for statement in block.statements.iter() {
+            self.check_statement(statement);
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Assign(Assignment { name, value, .. }) => {
+                let t = self.check_expression(value);
+
+                self.define(&name.value, t);
+            }
+            Statement::IndexAssign(IndexAssignment {
+                target,
+                index,
+                value,
+                ..
+            }) => {
+                self.check_index(target, index, statement.span());
+                self.check_expression(value);
+            }
+            Statement::Expr(expression) => {
+                self.check_expression(expression);
+            }
+            Statement::Return(ReturnStatement { return_value, .. }) => {
+                self.check_expression(return_value);
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn check_expression(&mut self, expression: &Expression) -> Type {
+        match expression {
+            Expression::Identifier(identifier) => self
+                .variables
+                .get(&identifier.value)
+                .copied()
+                .unwrap_or(Type::Unknown),
+            Expression::Assign(assignment) => {
+                let t = self.check_expression(&assignment.value);
+
+                self.define(&assignment.name.value, t);
+
+                t
+            }
+            Expression::Literal(literal) => match literal {
+                Literal::Integer(_) => Type::Int,
+                Literal::Float(_) => Type::Float,
+                Literal::Boolean(_) => Type::Bool,
+                // A null is typically a placeholder for a value assigned later.
+                Literal::Null(_) => Type::Unknown,
+                Literal::String(_) => Type::String,
+                Literal::Char(_) => Type::Char,
+                Literal::Array(ArrayLiteral { elements, .. }) => {
+                    for element in elements.iter() {
+                        self.check_expression(element);
+                    }
+
+                    Type::Array
+                }
+                Literal::Hash(HashLiteral { pairs, .. }) => {
+                    for (key, value) in pairs.iter() {
+                        self.check_expression(key);
+                        self.check_expression(value);
+                    }
+
+                    Type::Hash
+                }
+                Literal::Tuple(TupleLiteral { elements, .. }) => {
+                    for element in elements.iter() {
+                        self.check_expression(element);
+                    }
+
+                    Type::Tuple
+                }
+            },
+            Expression::Infix(InfixExpression {
+                left,
+                operator,
+                right,
+                ..
+            }) => {
+                let left = self.check_expression(left);
+                let right = self.check_expression(right);
+
+                self.check_infix(&operator.literal, left, right, expression.span())
+            }
+            Expression::Prefix(PrefixExpression {
+                operator, right, ..
+            }) => {
+                let right = self.check_expression(right);
+
+                match (operator.literal.as_str(), right) {
+                    ("!", _) => Type::Bool,
+                    (_, Type::Unknown) => Type::Unknown,
+                    ("-", t) if t.is_numeric() => t,
+                    ("~", Type::Int) => Type::Int,
+                    (operator, t) => self.error(
+                        format!("unknown operator: {}{}", operator, t),
+                        expression.span(),
+                    ),
+                }
+            }
+            Expression::Postfix(PostfixExpression { operator, left, .. }) => {
+                match self.check_expression(left) {
+                    t if t.is_numeric() || t == Type::Unknown => t,
+                    t => self.error(
+                        format!("unknown operator: {}{}", t, operator.literal),
+                        expression.span(),
+                    ),
+                }
+            }
+            Expression::Index(IndexExpression { left, index, .. }) => {
+                self.check_index(left, index, expression.span())
+            }
+            Expression::Slice(SliceExpression {
+                left, start, end, ..
+            }) => {
+                let left = self.check_expression(left);
+                let mut bound = |bound: &Option<Box<Expression>>| match bound {
+                    Some(bound) => self.check_expression(bound),
+                    None => Type::Unknown,
+                };
+                let (start, end) = (bound(start), bound(end));
+
+                let bounds_ok = [start, end]
+                    .iter()
+                    .all(|t| matches!(t, Type::Int | Type::Unknown));
+
+                match left {
+                    Type::String | Type::Array | Type::Unknown if bounds_ok => left,
+                    _ => self.error(
+                        format!("unsupported types for slice: {}[{}:{}]", left, start, end),
+                        expression.span(),
+                    ),
+                }
+            }
+            Expression::Range(RangeExpression { start, end, .. }) => {
+                let start = self.check_expression(start);
+                let end = self.check_expression(end);
+
+                match (start, end) {
+                    (Type::Int | Type::Unknown, Type::Int | Type::Unknown) => Type::Range,
+                    _ => self.error(
+                        format!("unsupported types for range: {}..{}", start, end),
+                        expression.span(),
+                    ),
+                }
+            }
+            Expression::If(IfExpression {
+                condition,
+                consequence,
+                alternative,
+                ..
+            }) => {
+                self.check_expression(condition);
+                self.check_block(consequence);
+
+                if let Some(alternative) = alternative {
+                    self.check_block(alternative);
+                }
+
+                Type::Unknown
+            }
+            Expression::Ternary(TernaryExpression {
+                condition,
+                consequence,
+                alternative,
+                ..
+            }) => {
+                self.check_expression(condition);
+
+                let consequence = self.check_expression(consequence);
+                let alternative = self.check_expression(alternative);
+
+                if consequence == alternative {
+                    consequence
+                } else {
+                    Type::Unknown
+                }
+            }
+            Expression::While(WhileExpression {
+                condition, body, ..
+            }) => {
+                self.check_expression(condition);
+                self.check_block(body);
+
+                Type::Unknown
+            }
+            Expression::For(ForExpression {
+                init,
+                condition,
+                update,
+                body,
+                ..
+            }) => {
+                if let Some(init) = init {
+                    self.check_statement(init);
+                }
+
+                if let Some(condition) = condition {
+                    self.check_expression(condition);
+                }
+
+                self.check_block(body);
+
+                if let Some(update) = update {
+                    self.check_statement(update);
+                }
+
+                Type::Unknown
+            }
+            Expression::Function(FunctionLiteral {
+                parameters, body, ..
+            }) => {
+                // The body sees the enclosing variables, but anything it
+                // assigns stays local to the function.
+                let enclosing = self.variables.clone();
+
+                for parameter in parameters.iter() {
+                    self.variables
+                        .insert(parameter.value.clone(), Type::Unknown);
+                }
+
+                self.check_block(body);
+                self.variables = enclosing;
+
+                Type::Unknown
+            }
+            Expression::Call(CallExpression {
+                function,
+                arguments,
+                ..
+            }) => {
+                self.check_expression(function);
+
+                for argument in arguments.iter() {
+                    self.check_expression(argument);
+                }
+
+                Type::Unknown
+            }
+        }
+    }
+
+    fn check_index(&mut self, left: &Expression, index: &Expression, span: Span) -> Type {
+        let left = self.check_expression(left);
+        let index = self.check_expression(index);
+
+        match (left, index) {
+            (Type::Array, Type::Int | Type::Unknown) | (Type::Hash, _) | (Type::Unknown, _) => {
+                Type::Unknown
+            }
+            (Type::String, Type::Int | Type::Unknown) => Type::Char,
+            (Type::Range, Type::Int | Type::Unknown) => Type::Int,
+            (left, index) => self.error(
+                format!("unsupported types for index: {}[{}]", left, index),
+                span,
+            ),
+        }
+    }
+
+    fn check_infix(&mut self, operator: &str, left: Type, right: Type, span: Span) -> Type {
+        if matches!(operator, "&&" | "||") || left == Type::Unknown || right == Type::Unknown {
+            return match operator {
+                "==" | "!=" | "<" | ">" | "<=" | ">=" => Type::Bool,
+                _ => Type::Unknown,
+            };
+        }
+
+        let numeric = left.is_numeric() && right.is_numeric();
+        let promoted = if left == Type::Float || right == Type::Float {
+            Type::Float
+        } else {
+            Type::Int
+        };
+
+        let result = match operator {
+            "+" if left == Type::String && right == Type::String => Some(Type::String),
+            "+" | "-" | "*" | "/" | "%" if numeric => Some(promoted),
+            "<" | ">" | "<=" | ">=" if numeric => Some(Type::Bool),
+            "==" | "!=" if numeric || left == right => Some(Type::Bool),
+            "&" | "|" | "^" | "<<" | ">>" if left == Type::Int && right == Type::Int => {
+                Some(Type::Int)
+            }
+            _ => None,
+        };
+
+        match result {
+            Some(t) => t,
+            None if left != right => self.error(
+                format!("type mismatch: {} {} {}", left, operator, right),
+                span,
+            ),
+            None => self.error(
+                format!("unknown operator: {} {} {}", left, operator, right),
+                span,
+            ),
+        }
+    }
+}
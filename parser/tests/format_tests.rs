@@ -0,0 +1,90 @@
+use anyhow::Error;
+use lexer::Lexer;
+use parser::{format::format_source, Parser};
+
+fn parse(input: &str) -> Result<String, Error> {
+    let mut parser = Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    Ok(program.to_string())
+}
+
+#[test]
+fn test_format_source() -> Result<(), Error> {
+    let tests = [
+        ("$x=1+2 ;", "$x = (1 + 2);\n"),
+        ("(1 + 2) * 3", "((1 + 2) * 3);\n"),
+        ("$x += 2", "$x += 2;\n"),
+        ("$a = ($b = 1)", "$a = ($b = 1);\n"),
+        ("($y = 2)", "($y = 2);\n"),
+        (
+            "$a[0]*=[1,2.0,'c',\"s\\n\"]",
+            "$a[0] *= [1, 2.0, 'c', \"s\\n\"];\n",
+        ),
+        ("(1,); (1, 2)", "(1,);\n(1, 2);\n"),
+        ("if($x){}else{ 1 }", "if ($x) {\n} else {\n    1;\n};\n"),
+        (
+            "$f = function($a,$b){ if ($a) { return $a+$b; } }; $f(1, 2)",
+            "$f = function($a, $b) {\n    if ($a) {\n        return ($a + $b);\n    };\n};\n$f(1, 2);\n",
+        ),
+        (
+            "while (true) { break; }",
+            "while (true) {\n    break;\n};\n",
+        ),
+        ("{\"}\": '{'}", "{\"}\": '{'};\n"),
+    ];
+
+    for (input, expected) in tests {
+        assert_eq!(format_source(input)?, expected, "{}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_format_source_preserves_program() -> Result<(), Error> {
+    let inputs = [
+        "1 + 2 * 3 - 4 / 5 % 6",
+        "(1 + 2) * (3 - 4)",
+        "1 << 2 | 3 & 4 ^ 5",
+        "$a && ($b || $c)",
+        "-$a[0]; (-$a)[0]; !(1 < 2)",
+        "$f(1)(2)[3]",
+        "(function ($x) { $x })(1)",
+        "$c ? $a : $b ? 1 : 2",
+        "$x = if (true) { 1 } else { 2 }; $x",
+        "for (;;) { break }; for ($i = 0; $i < 1;) { $i++ }",
+        "$a[1:2]; $a[$i + 1:]; $a[-1]",
+        "\"quote \\\" and \\\\ and \\t\"; '\\''",
+        "1.5e3 + 0.1; 0xff",
+        "$a[0] *= 2; $n--",
+        "0..5 == 0..=4; (0..5)..6; 0..(5..6)",
+        "$a = $b = $c += 1; ($x = 2); -($y = 3); $f($z = 4)[0]",
+    ];
+
+    for input in inputs {
+        let formatted = format_source(input)?;
+
+        assert_eq!(parse(&formatted)?, parse(input)?, "{}", formatted);
+        assert_eq!(format_source(&formatted)?, formatted, "{}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_format_source_errors() {
+    assert!(format_source("$x = ;").is_err());
+
+    for input in ["1; // note", "/* note */ 1", "$a /* note */ + 1"] {
+        match format_source(input) {
+            Ok(formatted) => panic!("expected an error, got {}", formatted),
+            Err(err) => assert_eq!(
+                err.to_string(),
+                "format: source contains comments, which would be lost"
+            ),
+        }
+    }
+}
@@ -3,13 +3,13 @@ use lexer::{
     Lexer,
 };
 use parser::{
-    ast::{BlockStatement, Expression, Literal},
+    ast::{BlockStatement, Expression, Literal, Node, Position, Span},
     *,
 };
 
 use anyhow::{Error, Result};
 
-use parser::ast::{ArrayLiteral, Statement};
+use parser::ast::{ArrayLiteral, HashLiteral, Statement};
 
 #[test]
 fn test_assignment_statements() -> Result<(), Error> {
@@ -38,6 +38,44 @@ fn test_assignment_statements() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_compound_assignment_statements() -> Result<(), Error> {
+    let tests = vec![
+        ("$x += 2;", "$x", "+", "2"),
+        ("$x -= $y * 3;", "$x", "-", "($y * 3)"),
+        ("$x *= 2", "$x", "*", "2"),
+        ("$x /= 1 + 1", "$x", "/", "(1 + 1)"),
+    ];
+
+    for (input, expected_name, expected_operator, expected_right) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(1, program.statements.len(), "input: {}", input);
+
+        let assignment = match &program.statements[0] {
+            Statement::Assign(assignment) => assignment,
+            statement => panic!("Expected Assignment, got {:?}", statement),
+        };
+
+        assert_eq!(assignment.name.value, expected_name);
+
+        match &assignment.value {
+            Expression::Infix(infix) => {
+                assert_eq!(infix.left.to_string(), expected_name);
+                assert_eq!(infix.operator.literal, expected_operator);
+                assert_eq!(infix.right.to_string(), expected_right);
+            }
+            expression => panic!("Expected InfixExpression, got {:?}", expression),
+        }
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_boolean_expression() -> Result<(), Error> {
     let input = "
@@ -85,6 +123,38 @@ fn test_boolean_expression() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_null_literal() -> Result<(), Error> {
+    let lexer = Lexer::new("$x = null; null");
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    assert_eq!(2, program.statements.len());
+
+    match &program.statements[0] {
+        Statement::Assign(assignment) => {
+            assert!(matches!(
+                assignment.value,
+                Expression::Literal(Literal::Null(_))
+            ));
+        }
+        statement => panic!("Expected Assign, got {:?}", statement),
+    }
+
+    match &program.statements[1] {
+        Statement::Expr(expression) => {
+            assert!(matches!(expression, Expression::Literal(Literal::Null(_))));
+        }
+        statement => panic!("Expected Expr, got {:?}", statement),
+    }
+
+    assert_eq!(program.to_string(), "$x = null; null");
+
+    Ok(())
+}
+
 #[test]
 fn test_call_expression() -> Result<(), Error> {
     let input = "add(1, 2 * 3, 4 + 5);";
@@ -111,6 +181,7 @@ fn test_call_expression() -> Result<(), Error> {
                 &Token {
                     literal: "*".to_string(),
                     token_type: TokenType::Asterisk,
+                    ..Default::default()
                 },
                 "3",
             )?;
@@ -121,6 +192,7 @@ fn test_call_expression() -> Result<(), Error> {
                 &Token {
                     literal: "+".to_string(),
                     token_type: TokenType::Plus,
+                    ..Default::default()
                 },
                 "5",
             )?;
@@ -157,6 +229,7 @@ fn test_if_expression() -> Result<(), Error> {
                 &Token {
                     literal: "<".to_string(),
                     token_type: TokenType::Lt,
+                    ..Default::default()
                 },
                 "$y",
             )?;
@@ -178,6 +251,187 @@ fn test_if_expression() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_while_expression() -> Result<(), Error> {
+    let tests = vec![
+        ("while ($x < $y) { }", 0),
+        ("while ($x < $y) { $x = $x + 1; $y = $y - 1; $x; }", 3),
+    ];
+
+    for (input, expected_statements) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(1, program.statements.len());
+
+        match &program.statements[0] {
+            Statement::Expr(Expression::While(while_expression)) => {
+                assert_infix_expression(
+                    &while_expression.condition,
+                    "$x",
+                    &Token::new(TokenType::Lt, "<"),
+                    "$y",
+                )?;
+
+                assert_eq!(
+                    expected_statements,
+                    while_expression.body.statements.len(),
+                    "input: {}",
+                    input
+                );
+            }
+            statement => panic!("Expected WhileExpression, got {:?}", statement),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_while_condition_precedence() -> Result<(), Error> {
+    let input = "while ($x + 1 < $y * 2 && !$done) { $x }";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    assert_eq!(
+        program.to_string(),
+        "while (((($x + 1) < ($y * 2)) && (!$done))) {\n$x;\n}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_break_and_continue_statements() -> Result<(), Error> {
+    let input = "while (true) { break; continue }";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let body = match &program.statements[..] {
+        [Statement::Expr(Expression::While(while_expression))] => &while_expression.body,
+        statements => panic!("Expected a while expression, got {:?}", statements),
+    };
+
+    assert!(matches!(
+        &body.statements[..],
+        [Statement::Break(_), Statement::Continue(_)]
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_for_expression() -> Result<(), Error> {
+    let tests = vec![
+        (
+            "for ($i = 0; $i < 10; $i = $i + 1) { $i; }",
+            (true, true, true),
+            1,
+        ),
+        ("for (;;) { }", (false, false, false), 0),
+        ("for ($i = 0; ;) { $i; $i; }", (true, false, false), 2),
+        ("for (; $i < 10;) { }", (false, true, false), 0),
+        ("for (;; $i = $i + 1) { }", (false, false, true), 0),
+    ];
+
+    for (input, (has_init, has_condition, has_update), expected_statements) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(1, program.statements.len(), "input: {}", input);
+
+        match &program.statements[0] {
+            Statement::Expr(Expression::For(for_expression)) => {
+                assert_eq!(for_expression.init.is_some(), has_init, "input: {}", input);
+                assert_eq!(
+                    for_expression.condition.is_some(),
+                    has_condition,
+                    "input: {}",
+                    input
+                );
+                assert_eq!(
+                    for_expression.update.is_some(),
+                    has_update,
+                    "input: {}",
+                    input
+                );
+                assert_eq!(
+                    for_expression.body.statements.len(),
+                    expected_statements,
+                    "input: {}",
+                    input
+                );
+
+                if let Some(condition) = &for_expression.condition {
+                    assert_infix_expression(
+                        condition,
+                        "$i",
+                        &Token::new(TokenType::Lt, "<"),
+                        "10",
+                    )?;
+                }
+            }
+            statement => panic!("Expected ForExpression, got {:?}", statement),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_ternary_expression() -> Result<(), Error> {
+    let input = "$a ? $b : $c ? $d : $e";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    assert_eq!(1, program.statements.len());
+
+    match &program.statements[0] {
+        Statement::Expr(Expression::Ternary(ternary)) => {
+            assert_identifier(&ternary.condition, "$a")?;
+            assert_identifier(&ternary.consequence, "$b")?;
+
+            // The alternative holds the nested ternary, so `?:` groups to the right.
+            match ternary.alternative.as_ref() {
+                Expression::Ternary(nested) => {
+                    assert_identifier(&nested.condition, "$c")?;
+                    assert_identifier(&nested.consequence, "$d")?;
+                    assert_identifier(&nested.alternative, "$e")?;
+                }
+                expression => panic!("Expected TernaryExpression, got {:?}", expression),
+            }
+        }
+        statement => panic!("Expected TernaryExpression, got {:?}", statement),
+    }
+
+    assert_eq!(program.to_string(), "($a ? $b : ($c ? $d : $e))");
+
+    let lexer = Lexer::new("$a ? $b");
+    let mut parser = Parser::new(lexer);
+
+    assert!(parser.parse_program().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_if_else_expression() -> Result<(), Error> {
     let input = "
@@ -201,6 +455,7 @@ fn test_if_else_expression() -> Result<(), Error> {
                 &Token {
                     literal: "<".to_string(),
                     token_type: TokenType::Lt,
+                    ..Default::default()
                 },
                 "$y",
             )?;
@@ -235,6 +490,123 @@ fn test_if_else_expression() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_range_expressions() -> Result<(), Error> {
+    let tests = [
+        ("0..5", "0", "5", false, "(0..5)"),
+        ("0..=5", "0", "5", true, "(0..=5)"),
+        ("$a..$b", "$a", "$b", false, "($a..$b)"),
+        (
+            "1 + 2..3 * 4",
+            "(1 + 2)",
+            "(3 * 4)",
+            false,
+            "((1 + 2)..(3 * 4))",
+        ),
+        ("0..$n | 1", "0", "($n | 1)", false, "(0..($n | 1))"),
+        ("-1..=-$n", "(-1)", "(-$n)", true, "((-1)..=(-$n))"),
+    ];
+
+    for (input, expected_start, expected_end, expected_inclusive, expected_display) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        match &program.statements[..] {
+            [Statement::Expr(Expression::Range(range))] => {
+                assert_eq!(range.start.to_string(), expected_start, "{}", input);
+                assert_eq!(range.end.to_string(), expected_end, "{}", input);
+                assert_eq!(range.inclusive, expected_inclusive, "{}", input);
+            }
+            statements => panic!("Expected RangeExpression, got {:?}", statements),
+        }
+
+        assert_eq!(program.to_string(), expected_display);
+    }
+
+    // Ranges bind looser than arithmetic but tighter than comparisons.
+    let tests = [
+        ("0..5 == 0..5", "((0..5) == (0..5))"),
+        ("$i < 0..5", "($i < (0..5))"),
+        ("$r = 0..len($a) - 1", "$r = (0..(len($a) - 1))"),
+        ("(0..5)[1]", "((0..5)[1])"),
+    ];
+
+    for (input, expected) in tests {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(program.to_string(), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_slice_expressions() -> Result<(), Error> {
+    let tests = [
+        ("$s[1:3]", Some("1"), Some("3"), "($s[1:3])"),
+        ("$s[:3]", None, Some("3"), "($s[:3])"),
+        ("$s[1:]", Some("1"), None, "($s[1:])"),
+        ("$s[:]", None, None, "($s[:])"),
+        (
+            "$s[$i + 1:-1]",
+            Some("($i + 1)"),
+            Some("(-1)"),
+            "($s[($i + 1):(-1)])",
+        ),
+        (
+            "$s[$a ? 1 : 2:3]",
+            Some("($a ? 1 : 2)"),
+            Some("3"),
+            "($s[($a ? 1 : 2):3])",
+        ),
+    ];
+
+    for (input, expected_start, expected_end, expected_display) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        match &program.statements[..] {
+            [Statement::Expr(Expression::Slice(slice))] => {
+                assert_eq!(slice.left.to_string(), "$s");
+                assert_eq!(
+                    slice
+                        .start
+                        .as_ref()
+                        .map(|start| start.to_string())
+                        .as_deref(),
+                    expected_start
+                );
+                assert_eq!(
+                    slice.end.as_ref().map(|end| end.to_string()).as_deref(),
+                    expected_end
+                );
+            }
+            statements => panic!("Expected SliceExpression, got {:?}", statements),
+        }
+
+        assert_eq!(program.to_string(), expected_display);
+    }
+
+    for input in ["$s[1:2:3]", "$s[1:2", "$s[1"] {
+        assert!(
+            Parser::new(Lexer::new(input)).parse_program().is_err(),
+            "expected an error for {}",
+            input
+        );
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_index_expressions() -> Result<(), Error> {
     let input = "$myArray[1 + 1]";
@@ -258,6 +630,7 @@ fn test_index_expressions() -> Result<(), Error> {
                 &Token {
                     literal: "+".to_string(),
                     token_type: TokenType::Plus,
+                    ..Default::default()
                 },
                 "1",
             )?;
@@ -315,6 +688,124 @@ fn test_function_literal_parsing() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_float_literal_expression() -> Result<(), Error> {
+    let input = "2.75;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    assert_eq!(1, program.statements.len());
+
+    match &program.statements[0] {
+        Statement::Expr(Expression::Literal(Literal::Float(float))) => {
+            assert_eq!(float.value, 2.75);
+            assert_eq!(float.token.literal, "2.75");
+        }
+        statement => panic!("Expected float literal, got {:?}", statement),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_char_literal_expression() -> Result<(), Error> {
+    let input = "'x';";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    assert_eq!(1, program.statements.len());
+
+    match &program.statements[0] {
+        Statement::Expr(Expression::Literal(Literal::Char(ch))) => {
+            assert_eq!(ch.value, 'x');
+            assert_eq!(ch.token.literal, "x");
+        }
+        statement => panic!("Expected char literal, got {:?}", statement),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_float_literal_exponents() -> Result<(), Error> {
+    let tests = vec![("1e10", 1e10), ("2.5e-3", 2.5e-3), ("1_000.5e1", 10005.0)];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        match &program.statements[0] {
+            Statement::Expr(Expression::Literal(Literal::Float(float))) => {
+                assert_eq!(float.value, expected, "input: {}", input);
+            }
+            statement => panic!("Expected float literal, got {:?}", statement),
+        }
+    }
+
+    // A leading minus is a prefix operator, the same as for integers.
+    let lexer = Lexer::new("-1.5");
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    match &program.statements[0] {
+        Statement::Expr(Expression::Prefix(prefix)) => {
+            assert_eq!(prefix.operator.literal, "-");
+
+            match prefix.right.as_ref() {
+                Expression::Literal(Literal::Float(float)) => assert_eq!(float.value, 1.5),
+                expression => panic!("Expected float literal, got {:?}", expression),
+            }
+        }
+        statement => panic!("Expected prefix expression, got {:?}", statement),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_integer_literal_bases() -> Result<(), Error> {
+    let tests = vec![
+        ("1_000_000", 1_000_000),
+        ("0xFF", 255),
+        ("0b1010", 10),
+        ("0o755", 493),
+        ("0x_dead_beef", 0xdead_beef),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        match &program.statements[0] {
+            Statement::Expr(expression) => assert_integer_literal(expression, expected)?,
+            statement => panic!("Expected expression statement, got {:?}", statement),
+        }
+    }
+
+    let lexer = Lexer::new("0x8000000000000000");
+    let mut parser = Parser::new(lexer);
+
+    assert!(parser.parse_program().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn test_integer_literal_expression() -> Result<(), Error> {
     let input = "5;";
@@ -336,6 +827,48 @@ fn test_integer_literal_expression() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_tuple_literals() -> Result<(), Error> {
+    let tests: [(&str, Option<&[i64]>); 5] = [
+        ("(1)", None),
+        ("(1,)", Some(&[1])),
+        ("(1, 2)", Some(&[1, 2])),
+        ("(1, 2,)", Some(&[1, 2])),
+        ("(1, 2, 3)", Some(&[1, 2, 3])),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        let expression = match &program.statements[0] {
+            Statement::Expr(expression) => expression,
+            statement => panic!("Expected expression statement, got {:?}", statement),
+        };
+
+        match (expression, expected) {
+            (Expression::Literal(Literal::Tuple(tuple)), Some(values)) => {
+                assert_eq!(tuple.elements.len(), values.len(), "input: {}", input);
+
+                for (element, value) in tuple.elements.iter().zip(values) {
+                    assert_integer_literal(element, *value)?;
+                }
+            }
+            (expression, None) => assert_integer_literal(expression, 1)?,
+            (expression, _) => panic!("Expected tuple for {}, got {:?}", input, expression),
+        }
+    }
+
+    let program = Parser::new(Lexer::new("((1,), (2 + 3, $a))")).parse_program()?;
+
+    assert_eq!(program.to_string(), "((1,), ((2 + 3), $a))");
+
+    Ok(())
+}
+
 #[test]
 fn test_array_literal_expression() -> Result<(), Error> {
     let input = "[1, 2 * 2, 3 + 3]";
@@ -359,6 +892,7 @@ fn test_array_literal_expression() -> Result<(), Error> {
                 &Token {
                     literal: "*".to_string(),
                     token_type: TokenType::Asterisk,
+                    ..Default::default()
                 },
                 "2",
             )?;
@@ -368,6 +902,7 @@ fn test_array_literal_expression() -> Result<(), Error> {
                 &Token {
                     literal: "+".to_string(),
                     token_type: TokenType::Plus,
+                    ..Default::default()
                 },
                 "3",
             )?;
@@ -381,6 +916,104 @@ fn test_array_literal_expression() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_empty_hash_literal() -> Result<(), Error> {
+    let input = "{}";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    match &program.statements[0] {
+        Statement::Expr(Expression::Literal(Literal::Hash(HashLiteral { pairs, .. }))) => {
+            assert!(pairs.is_empty());
+        }
+        statement => panic!("Expected HashLiteral, got {:?}", statement),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_literal_expression() -> Result<(), Error> {
+    let input = r#"{"one": 1, "two": 2 * 3, 3: true}"#;
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    match &program.statements[0] {
+        Statement::Expr(Expression::Literal(Literal::Hash(HashLiteral { pairs, .. }))) => {
+            assert_eq!(3, pairs.len());
+
+            assert_eq!(pairs[0].0.to_string(), "\"one\"");
+            assert_integer_literal(&pairs[0].1, 1)?;
+
+            assert_eq!(pairs[1].0.to_string(), "\"two\"");
+            assert_infix_expression(
+                &pairs[1].1,
+                "2",
+                &Token {
+                    literal: "*".to_string(),
+                    token_type: TokenType::Asterisk,
+                    ..Default::default()
+                },
+                "3",
+            )?;
+
+            assert_integer_literal(&pairs[2].0, 3)?;
+            assert_eq!(pairs[2].1.to_string(), "true");
+        }
+        statement => panic!("Expected HashLiteral, got {:?}", statement),
+    }
+
+    assert_eq!(
+        program.to_string(),
+        "{\"one\": 1, \"two\": (2 * 3), 3: true}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_literal_errors() -> Result<(), Error> {
+    for input in [r#"{"a" 1}"#, r#"{"a": 1 "b": 2}"#] {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program();
+
+        assert!(
+            program.is_err() || parser.check_errors().is_err(),
+            "expected an error for {}",
+            input
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_precedence_of() -> Result<(), Error> {
+    let precedence = |input: &str| precedence_of(&Lexer::new(input).next_token());
+
+    assert!(precedence("*") > precedence("+"));
+    assert!(precedence("==") < precedence("<"));
+    assert_eq!(precedence("/"), Precedence::Product);
+    assert_eq!(precedence("<="), Precedence::LessGreater);
+    assert_eq!(precedence(";"), Precedence::Lowest);
+    assert_eq!(
+        precedence("&&").max(precedence("||")),
+        Precedence::LogicalAnd
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_operator_precedence_parsing() -> Result<(), Error> {
     let tests = [
@@ -391,10 +1024,35 @@ fn test_operator_precedence_parsing() -> Result<(), Error> {
         ("a * b * c", "((a * b) * c)"),
         ("a * b / c", "((a * b) / c)"),
         ("a + b / c", "(a + (b / c))"),
+        ("10 % 3", "(10 % 3)"),
+        ("a + b % c * d", "(a + ((b % c) * d))"),
         ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
-        ("3 + 4; -5 * 5", "(3 + 4)((-5) * 5)"),
+        ("3 + 4; -5 * 5", "(3 + 4); ((-5) * 5)"),
         ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
         ("5 < 4 != 3 > 4", "((5 < 4) != (3 > 4))"),
+        ("5 >= 4 == 3 <= 4", "((5 >= 4) == (3 <= 4))"),
+        ("a || b && c", "(a || (b && c))"),
+        ("a && b || c", "((a && b) || c)"),
+        ("a == b && c < d", "((a == b) && (c < d))"),
+        ("a + b <= c * d", "((a + b) <= (c * d))"),
+        ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+        ("a & b | c ^ d", "((a & b) | (c ^ d))"),
+        ("a << b + c", "(a << (b + c))"),
+        ("a & b << c >> d", "(a & ((b << c) >> d))"),
+        ("a & 1 == 0", "((a & 1) == 0)"),
+        ("a | b < c", "((a | b) < c)"),
+        ("a || b | c && d", "(a || ((b | c) && d))"),
+        ("~$x", "(~$x)"),
+        ("~a & b", "((~a) & b)"),
+        ("-~a", "(-(~a))"),
+        ("-$x++", "(-($x++))"),
+        ("$x++ * 2", "(($x++) * 2)"),
+        ("$x-- - 1", "(($x--) - 1)"),
+        ("a ? b : c", "(a ? b : c)"),
+        ("a || b ? c + 1 : d * 2", "((a || b) ? (c + 1) : (d * 2))"),
+        ("a ? b : c ? d : e", "(a ? b : (c ? d : e))"),
+        ("a ? b ? c : d : e", "(a ? (b ? c : d) : e)"),
+        ("add(a ? b : c, d)", "add((a ? b : c), d)"),
         (
             "3 + 4 * 5 == 3 * 1 + 4 * 5",
             "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
@@ -407,6 +1065,10 @@ fn test_operator_precedence_parsing() -> Result<(), Error> {
         ("(5 + 5) * 2", "((5 + 5) * 2)"),
         ("2 / (5 + 5)", "(2 / (5 + 5))"),
         ("-(5 + 5)", "(-(5 + 5))"),
+        ("((a + b) * (c - d)) / e", "(((a + b) * (c - d)) / e)"),
+        ("a * (b + (c - d))", "(a * (b + (c - d)))"),
+        ("(((a)))", "a"),
+        ("(a || b) && c", "((a || b) && c)"),
         ("!(true == true)", "(!(true == true))"),
         ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
         (
@@ -432,14 +1094,46 @@ fn test_operator_precedence_parsing() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_postfix_expressions() -> Result<(), Error> {
+    let tests = [
+        ("$x++", "$x", TokenType::Increment, "($x++)"),
+        ("$x--;", "$x", TokenType::Decrement, "($x--)"),
+        ("$a[0]++", "($a[0])", TokenType::Increment, "(($a[0])++)"),
+    ];
+
+    for (input, expected_left, expected_operator, expected_display) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(1, program.statements.len(), "input: {}", input);
+
+        match &program.statements[0] {
+            Statement::Expr(Expression::Postfix(postfix)) => {
+                assert_eq!(postfix.left.to_string(), expected_left);
+                assert_eq!(postfix.operator.token_type, expected_operator);
+            }
+            statement => panic!("Expected PostfixExpression, got {:?}", statement),
+        }
+
+        assert_eq!(program.to_string(), expected_display);
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_prefix_expressions() -> Result<(), Error> {
-    let prefix_tests: [(&str, &Token, i64); 2] = [
+    let prefix_tests: [(&str, &Token, i64); 3] = [
         (
             "!5;",
             &Token {
                 literal: "!".to_string(),
                 token_type: TokenType::Bang,
+                ..Default::default()
             },
             5,
         ),
@@ -448,9 +1142,19 @@ fn test_prefix_expressions() -> Result<(), Error> {
             &Token {
                 literal: "-".to_string(),
                 token_type: TokenType::Minus,
+                ..Default::default()
             },
             15,
         ),
+        (
+            "~7;",
+            &Token {
+                literal: "~".to_string(),
+                token_type: TokenType::Tilde,
+                ..Default::default()
+            },
+            7,
+        ),
     ];
 
     for (input, _operator, value) in prefix_tests.iter() {
@@ -563,6 +1267,7 @@ fn assert_function_literal(expression: &Expression) -> Result<(), Error> {
                         &Token {
                             literal: "+".to_string(),
                             token_type: TokenType::Plus,
+                            ..Default::default()
                         },
                         "$y",
                     )?;
@@ -574,6 +1279,7 @@ fn assert_function_literal(expression: &Expression) -> Result<(), Error> {
                         &Token {
                             literal: "+".to_string(),
                             token_type: TokenType::Plus,
+                            ..Default::default()
                         },
                         "$y",
                     )?;
@@ -602,6 +1308,7 @@ fn assert_call_expression(expression: &Expression) -> Result<(), Error> {
             &Token {
                 token_type: TokenType::Asterisk,
                 literal: "*".to_string(),
+                ..Default::default()
             },
             "3",
         )?;
@@ -612,6 +1319,7 @@ fn assert_call_expression(expression: &Expression) -> Result<(), Error> {
             &Token {
                 token_type: TokenType::Plus,
                 literal: "+".to_string(),
+                ..Default::default()
             },
             "5",
         )?;
@@ -676,3 +1384,305 @@ fn assert_literal_expression(expression: &Expression, expected: &str) -> Result<
         }
     }
 }
+
+#[test]
+fn test_errors_report_positions() -> Result<(), Error> {
+    let input = "$a = 1;\n$b = (2;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let error = parser.parse_program().unwrap_err();
+
+    assert!(
+        error.to_string().contains("at 2:8"),
+        "unexpected error: {}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_illegal_token_errors() -> Result<(), Error> {
+    let input = "1 + \"abc";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let error = parser.parse_program().unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Illegal token `unterminated string` at 1:5"),
+        "unexpected error: {}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_collects_multiple_errors() -> Result<(), Error> {
+    let input = "$a = ;\n$b = 2;\n$c = (1 + );\n$d = 4;\n$f(1 +);\n$e = 5;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    assert!(parser.parse_program().is_err());
+
+    let positions = parser
+        .errors()
+        .iter()
+        .map(|error| (error.line, error.column))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        positions,
+        vec![(1, 6), (3, 11), (5, 7)],
+        "{:?}",
+        parser.errors()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_missing_operand_errors() -> Result<(), Error> {
+    let tests = [
+        ("$a = ;", "Expected an expression after =", 1, 6),
+        ("$a = 1 +;", "Expected an expression after +", 1, 9),
+        ("$a[0] = 1 * ;", "Expected an expression after *", 1, 13),
+        ("~", "Expected an expression after ~", 1, 2),
+        ("5 ~", "Expected an expression after ~", 1, 4),
+        ("-", "Expected an expression after -", 1, 2),
+        ("!;", "Expected an expression after !", 1, 2),
+    ];
+
+    for (input, expected, line, column) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        assert!(parser.parse_program().is_err());
+
+        let errors = parser.errors();
+
+        assert_eq!(errors.len(), 1, "input: {}: {:?}", input, errors);
+        assert!(
+            errors[0].message.starts_with(expected),
+            "input: {}: {}",
+            input,
+            errors[0]
+        );
+        assert_eq!((errors[0].line, errors[0].column), (line, column));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_index_assignment_statements() -> Result<(), Error> {
+    let tests = vec![
+        ("$a[1] = 5;", "$a", "1", "5", "$a[1] = 5"),
+        (
+            "$a[$i + 1] = $b * 2",
+            "$a",
+            "($i + 1)",
+            "($b * 2)",
+            "$a[($i + 1)] = ($b * 2)",
+        ),
+        (
+            "$grid[0][$j] = true;",
+            "($grid[0])",
+            "$j",
+            "true",
+            "($grid[0])[$j] = true",
+        ),
+        ("$a[0] *= 3;", "$a", "0", "(($a[0]) * 3)", "$a[0] *= 3"),
+        ("$a[$i] -= 1", "$a", "$i", "(($a[$i]) - 1)", "$a[$i] -= 1"),
+    ];
+
+    for (input, expected_target, expected_index, expected_value, expected_display) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(1, program.statements.len(), "input: {}", input);
+
+        match &program.statements[0] {
+            Statement::IndexAssign(assignment) => {
+                assert_eq!(assignment.target.to_string(), expected_target);
+                assert_eq!(assignment.index.to_string(), expected_index);
+                assert_eq!(assignment.value.to_string(), expected_value);
+            }
+            statement => panic!("Expected IndexAssignment, got {:?}", statement),
+        }
+
+        assert_eq!(program.to_string(), expected_display);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assignment_expressions() -> Result<(), Error> {
+    let tests = vec![
+        ("$y = ($x = 3);", "$y = ($x = 3)"),
+        ("$a = $b = 1", "$a = ($b = 1)"),
+        ("($x = 1) + 2", "(($x = 1) + 2)"),
+        ("$f($x = 2)", "$f(($x = 2))"),
+        ("$n = $i += 1", "$n = ($i += 1)"),
+    ];
+
+    for (input, expected) in tests {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(1, program.statements.len(), "input: {}", input);
+        assert_eq!(program.to_string(), expected, "input: {}", input);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_spans() -> Result<(), Error> {
+    let input = "$total = 1 +\n  $foo * 2;\nreturn -$total;";
+
+    let lexer = Lexer::new(input);
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let position = |line, column| Position { line, column };
+
+    match &program.statements[0] {
+        Statement::Assign(assignment) => {
+            // The infix expression runs from its left operand to its right one.
+            assert_eq!(
+                assignment.value.span(),
+                Span {
+                    start: position(1, 10),
+                    end: position(2, 10),
+                }
+            );
+
+            assert_eq!(program.statements[0].span().start, position(1, 1));
+        }
+        statement => panic!("Expected Assignment, got {:?}", statement),
+    }
+
+    assert_eq!(
+        program.statements[1].span(),
+        Span {
+            start: position(3, 1),
+            end: position(3, 9),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_pretty() -> Result<(), Error> {
+    let lexer = Lexer::new("1 + 2 * 3");
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let expected = "(+
+  1
+  (*
+    2
+    3))";
+
+    match &program.statements[0] {
+        Statement::Expr(expression) => {
+            assert_eq!(Node::Expression(expression.clone()).pretty(0), expected)
+        }
+        statement => panic!("Expected expression statement, got {:?}", statement),
+    }
+
+    let expected = "  (program
+    (+
+      1
+      (*
+        2
+        3)))";
+
+    assert_eq!(Node::Program(program).pretty(2), expected);
+
+    let lexer = Lexer::new("$f = function ($a, $b) { if ($a) { return -$b; } }; $f(\"x\", ['c']);");
+    let mut parser = Parser::new(lexer);
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    let expected = r#"(program
+  (=
+    $f
+    (function ($a $b)
+      (block
+        (if
+          $a
+          (block
+            (return
+              (-
+                $b)))))))
+  (call
+    $f
+    "x"
+    (array
+      'c')))"#;
+
+    assert_eq!(Node::Program(program).pretty(0), expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_display_round_trip() -> Result<(), Error> {
+    let corpus = [
+        "$a = 1; $b = 2.5; $a + $b * 3",
+        "(1 + 2) * 3; -(-1); !true == false",
+        "1 << 2 | 3 & 4 ^ 5; ~$a % 2",
+        "$a && ($b || $c) ? 1 : $d ? 2 : 3",
+        "if ($a) { 1 } else { 2 }; (3)",
+        "if ($a > 1) { $b = 2; $b } ; [1][0]",
+        "if (true) {} else {}; -1",
+        "$f = function($x, $y) { return $x * $y; }; $f(1, add(2, 3))",
+        "(function() { 1 })(); $f(1)(2)",
+        "\"quote \\\" and \\\\ and \\n\"; '\\''; 'a'",
+        "$h = {\"a\": 'c', 1: [1.0, 2]}; $h[\"a\"] = (1,); (1, 2)",
+        "$s[1:]; $s[:-1]; $s[:]; $a[$i + 1]",
+        "while (true) { break; }; for ($i = 0; $i < 2; $i++) { continue; }",
+        "for (;;) { $x-- }; $x += 2",
+        "$a = $b = 1; ($c = 2) * 3; $f($d = [1])",
+    ];
+
+    for input in corpus {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+        parser.check_errors()?;
+
+        let displayed = program.to_string();
+        let mut parser = Parser::new(Lexer::new(&displayed));
+
+        let reparsed = parser.parse_program()?;
+        parser.check_errors()?;
+
+        assert_eq!(reparsed, program, "{} displayed as {}", input, displayed);
+        assert!(program.reparse_stable(), "{}", input);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "serde")]
+
+use anyhow::Error;
+use lexer::Lexer;
+use parser::{
+    ast::{Node, Program},
+    Parser,
+};
+
+fn parse(input: &str) -> Result<Program, Error> {
+    let mut parser = Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    Ok(program)
+}
+
+#[test]
+fn test_ast_json_round_trip() -> Result<(), Error> {
+    let inputs = [
+        "1 + 2",
+        "$a = [1, 2.5, 'c', \"s\"]; $a[0:1]",
+        "$f = function ($x) { if ($x) { return {1: true}; } else { (1,) } }; $f(-1)",
+        "for ($i = 0; $i < 3; $i++) { break; }; while (false) { continue; }",
+    ];
+
+    for input in inputs {
+        let program = parse(input)?;
+
+        let json = serde_json::to_string(&program)?;
+        let decoded: Program = serde_json::from_str(&json)?;
+
+        assert_eq!(decoded, program, "{}", json);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_node_json_shape() -> Result<(), Error> {
+    let node = Node::Program(parse("1 + 2")?);
+
+    let json = serde_json::to_value(&node)?;
+    let infix = &json["Program"]["statements"][0]["Expr"]["Infix"];
+
+    assert_eq!(infix["operator"]["token_type"], "Plus");
+    assert_eq!(infix["left"]["Literal"]["Integer"]["value"], 1);
+    assert_eq!(infix["right"]["Literal"]["Integer"]["value"], 2);
+
+    let decoded: Node = serde_json::from_value(json)?;
+    assert_eq!(decoded.to_string(), node.to_string());
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use anyhow::Error;
+use lexer::Lexer;
+use parser::{
+    typecheck::{typecheck, TypeError},
+    Parser,
+};
+
+fn check(input: &str) -> Result<Vec<TypeError>, Error> {
+    let mut parser = Parser::new(Lexer::new(input));
+
+    let program = parser.parse_program()?;
+    parser.check_errors()?;
+
+    Ok(typecheck(&program))
+}
+
+#[test]
+fn test_typecheck_clean_program() -> Result<(), Error> {
+    let input = r#"
+        $name = "pine";
+        $greeting = "hello " + $name;
+        $numbers = [1, 2, 3];
+        $first = $numbers[0] + 1;
+        $ratio = 1 / 2.5;
+        $flags = 6 & 3 | ~1 << 2;
+        $same = 1 == 1.0 && "a" != "b";
+        $lookup = {"a": 1};
+        $lookup["a"] + $numbers[$first];
+        $add = function ($a, $b) { $a + $b };
+        $add(1, true) + 2;
+        $x = 1;
+        while ($x < 10) { $x = $x + 1; }
+        $y = 1;
+        if (true) { $y = "changed"; }
+        $y + "still fine";
+        -$ratio > 0 ? $greeting : $name;
+        $range = 0..len($numbers);
+        $range[0] + 1;
+    "#;
+
+    let errors = check(input)?;
+
+    assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+    Ok(())
+}
+
+#[test]
+fn test_typecheck_reassigned_after_capture() -> Result<(), Error> {
+    let input = "$x = \"a\"; $f = function() { $x + 1 }; $x = 5; $f()";
+
+    let errors = check(input)?;
+
+    assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+
+    Ok(())
+}
+
+#[test]
+fn test_typecheck_errors() -> Result<(), Error> {
+    let tests = [
+        ("1 + true", "type mismatch: INTEGER + BOOLEAN at 1:1-1:5"),
+        (
+            "$a = \"s\";\n$b = $a - \"t\";",
+            "unknown operator: STRING - STRING at 2:6-2:11",
+        ),
+        (
+            "true < false",
+            "unknown operator: BOOLEAN < BOOLEAN at 1:1-1:8",
+        ),
+        ("1 == \"1\"", "type mismatch: INTEGER == STRING at 1:1-1:6"),
+        ("-true", "unknown operator: -BOOLEAN at 1:1-1:2"),
+        ("~1.5", "unknown operator: ~FLOAT at 1:1-1:2"),
+        (
+            "$n = 5; $n[0]",
+            "unsupported types for index: INTEGER[INTEGER] at 1:9-1:12",
+        ),
+        (
+            "[1, 2][\"a\"]",
+            "unsupported types for index: ARRAY[STRING] at 1:1-1:8",
+        ),
+        (
+            "0..\"5\"",
+            "unsupported types for range: INTEGER..STRING at 1:1-1:4",
+        ),
+        (
+            "$n = ($s = \"a\") + \"b\"; $s - 1",
+            "type mismatch: STRING - INTEGER at 1:24-1:29",
+        ),
+        (
+            "function () { [1 + 'c'] }",
+            "type mismatch: INTEGER + CHAR at 1:16-1:20",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let errors = check(input)?;
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>(),
+            vec![expected.to_string()],
+            "input: {}",
+            input
+        );
+    }
+
+    Ok(())
+}
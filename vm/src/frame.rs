@@ -1,24 +1,24 @@
-use object::CompiledFunction;
+use object::Closure;
 use opcode::Instructions;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Frame {
-    pub function: CompiledFunction,
+    pub closure: Closure,
 
     pub base_pointer: usize,
     pub instruction_pointer: i32,
 }
 
 impl Frame {
-    pub fn new(function: CompiledFunction, base_pointer: usize) -> Self {
+    pub fn new(closure: Closure, base_pointer: usize) -> Self {
         Self {
-            function,
+            closure,
             base_pointer,
             instruction_pointer: -1,
         }
     }
 
     pub fn instructions(&self) -> Instructions {
-        self.function.instructions.clone()
+        self.closure.function.instructions.clone()
     }
 }
@@ -1,9 +1,9 @@
-use std::{borrow::Borrow, rc::Rc};
+use std::{borrow::Borrow, collections::HashMap, io::Write, rc::Rc};
 
 use anyhow::Error;
 use byteorder::{BigEndian, ByteOrder};
 use compiler::Bytecode;
-use object::{CompiledFunction, Object};
+use object::{builtins, ArithOp, Closure, CompiledFunction, Object, WrappingMode};
 use opcode::{Instructions, Opcode};
 
 mod frame;
@@ -23,6 +23,11 @@ pub struct Vm {
 
     stack: Vec<Rc<Object>>,
     stack_pointer: usize,
+
+    wrapping_mode: WrappingMode,
+
+    trace: bool,
+    trace_output: Box<dyn Write>,
 }
 
 impl Vm {
@@ -30,9 +35,16 @@ impl Vm {
         &mut self.frames[self.frame_index - 1]
     }
 
-    pub fn push_frame(&mut self, frame: frame::Frame) {
+    /// Pushes `frame`, failing once `MAX_FRAMES` calls are active.
+    pub fn push_frame(&mut self, frame: frame::Frame) -> Result<(), Error> {
+        if self.frame_index == MAX_FRAMES {
+            return Err(Error::msg("stack overflow: max call depth exceeded"));
+        }
+
         self.frames[self.frame_index] = frame;
         self.frame_index += 1;
+
+        Ok(())
     }
 
     pub fn pop_frame(&mut self) -> frame::Frame {
@@ -44,37 +56,88 @@ impl Vm {
         &self.globals
     }
 
-    fn call_function(&mut self, num_args: usize) {
-        let function = &*self.stack[self.stack_pointer - 1 - num_args];
+    fn call_function(&mut self, num_args: usize) -> Result<(), Error> {
+        let callee = &*self.stack[self.stack_pointer - 1 - num_args];
 
-        match function {
-            Object::CompiledFunction(compiled_function) => {
-                let base_pointer = self.stack_pointer - num_args;
-                let cloned_function = compiled_function.as_ref().clone();
+        match callee {
+            Object::Builtin(builtin) => {
+                let arguments =
+                    self.stack[self.stack_pointer - num_args..self.stack_pointer].to_vec();
+
+                let result = (builtin.func)(arguments)?;
 
-                let frame = frame::Frame::new(
-                    cloned_function,
-                    base_pointer,
-                );
+                self.stack_pointer = self.stack_pointer - num_args - 1;
+                self.push(result)?;
 
-                self.stack_pointer = base_pointer + compiled_function.num_locals as usize;
-                self.push_frame(frame);
+                Ok(())
             }
-            _ => {
-                panic!("calling non-function object: {}", function);
+            Object::Closure(closure) => {
+                let function = &closure.function;
+
+                if num_args != function.num_parameters {
+                    return Err(Error::msg(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        function.num_parameters, num_args
+                    )));
+                }
+
+                let base_pointer = self.stack_pointer - num_args;
+                let num_locals = function.num_locals;
+
+                let frame = frame::Frame::new(closure.as_ref().clone(), base_pointer);
+
+                if base_pointer + num_locals > STACK_SIZE {
+                    return Err(Error::msg("stack overflow"));
+                }
+
+                self.stack_pointer = base_pointer + num_locals;
+                self.push_frame(frame)?;
+
+                Ok(())
             }
+            _ => Err(Error::msg(format!(
+                "calling non-function object: {}",
+                callee
+            ))),
+        }
+    }
+
+    fn build_hash(&self, start: usize, end: usize) -> Result<Object, Error> {
+        let mut hash = HashMap::new();
+
+        for pair in self.stack[start..end].chunks(2) {
+            hash.insert(pair[0].hash_key()?, Rc::clone(&pair[1]));
         }
+
+        Ok(Object::Hash(hash))
+    }
+
+    fn push_closure(&mut self, const_index: usize, num_free: usize) -> Result<(), Error> {
+        let function = match &*self.constants[const_index] {
+            Object::CompiledFunction(function) => Rc::clone(function),
+            constant => {
+                return Err(Error::msg(format!("not a function: {}", constant)));
+            }
+        };
+
+        let free = self.stack[self.stack_pointer - num_free..self.stack_pointer].to_vec();
+        self.stack_pointer -= num_free;
+
+        self.push(Rc::new(Object::Closure(Rc::new(Closure::new(
+            function, free,
+        )))))?;
+
+        Ok(())
     }
 
     pub fn new(bytecode: Bytecode) -> Self {
-        let empty_frame = frame::Frame::new(CompiledFunction::new(Instructions(vec![]), 0), 0);
+        let empty_function = Rc::new(CompiledFunction::new(Instructions(vec![]), 0, 0));
+        let empty_frame = frame::Frame::new(Closure::new(empty_function, vec![]), 0);
 
-        let main_function = CompiledFunction::new(
-            bytecode.instructions.clone(),
-             0
-        );
+        let main_function = Rc::new(CompiledFunction::new(bytecode.instructions.clone(), 0, 0));
+        let main_closure = Closure::new(main_function, vec![]);
 
-        let main_frame = frame::Frame::new(main_function, 0);
+        let main_frame = frame::Frame::new(main_closure, 0);
 
         let mut frames = vec![empty_frame; MAX_FRAMES];
         frames[0] = main_frame;
@@ -89,6 +152,11 @@ impl Vm {
 
             stack: vec![Rc::new(Object::Null); STACK_SIZE],
             stack_pointer: 0,
+
+            wrapping_mode: WrappingMode::default(),
+
+            trace: false,
+            trace_output: Box::new(std::io::stderr()),
         }
     }
 
@@ -100,6 +168,55 @@ impl Vm {
         compiler
     }
 
+    /// Sets how integer `+`, `-` and `*` handle overflow. The default,
+    /// `WrappingMode::Checked`, stops the program with an error.
+    pub fn with_wrapping_mode(self, wrapping_mode: WrappingMode) -> Self {
+        Self {
+            wrapping_mode,
+            ..self
+        }
+    }
+
+    /// Turns tracing on or off. While tracing, the VM writes a line before
+    /// each instruction with its offset, its disassembly and the stack it
+    /// is about to run on, e.g. `0006 OpAdd    [1, 2]`.
+    pub fn with_trace(self, trace: bool) -> Self {
+        Self { trace, ..self }
+    }
+
+    /// Sets where trace lines go. Defaults to stderr.
+    pub fn with_trace_output(self, trace_output: Box<dyn Write>) -> Self {
+        Self {
+            trace_output,
+            ..self
+        }
+    }
+
+    fn write_trace(
+        &mut self,
+        instructions: &[u8],
+        instruction_pointer: usize,
+    ) -> Result<(), Error> {
+        let definition = opcode::lookup(Opcode::from(instructions[instruction_pointer]));
+        let (operands, _) =
+            opcode::read_operands(definition, &instructions[instruction_pointer + 1..]);
+
+        let stack = self.stack[..self.stack_pointer]
+            .iter()
+            .map(|object| object.to_string())
+            .collect::<Vec<String>>();
+
+        writeln!(
+            self.trace_output,
+            "{:04} {}    [{}]",
+            instruction_pointer,
+            Instructions::format_instructions(definition, &operands),
+            stack.join(", ")
+        )?;
+
+        Ok(())
+    }
+
     pub fn run(&mut self) -> Result<(), Error> {
         let mut instruction_pointer: usize;
         let mut instructions: Vec<u8>;
@@ -116,12 +233,16 @@ impl Vm {
                 Error::msg(format!(
                     "no instruction at index {} in function {:?}",
                     instruction_pointer,
-                    self.current_frame().function
+                    self.current_frame().closure.function
                 ))
             })?;
 
             let opcode = Opcode::from(op);
 
+            if self.trace {
+                self.write_trace(&instructions, instruction_pointer)?;
+            }
+
             match opcode {
                 Opcode::OpJump => {
                     let jump_position = BigEndian::read_u16(
@@ -139,7 +260,20 @@ impl Vm {
 
                     let condition = self.pop();
 
-                    if !is_truthy(&condition) {
+                    if !condition.is_truthy() {
+                        self.current_frame().instruction_pointer = jump_position as i32 - 1;
+                    }
+                }
+                Opcode::OpJumpTruthy => {
+                    let jump_position = BigEndian::read_u16(
+                        &instructions[instruction_pointer + 1..instruction_pointer + 3],
+                    ) as usize;
+
+                    self.current_frame().instruction_pointer += 2;
+
+                    let condition = self.pop();
+
+                    if condition.is_truthy() {
                         self.current_frame().instruction_pointer = jump_position as i32 - 1;
                     }
                 }
@@ -153,7 +287,7 @@ impl Vm {
 
                     self.current_frame().instruction_pointer += 2;
 
-                    self.push(Rc::clone(&self.globals[global_index]));
+                    self.push(Rc::clone(&self.globals[global_index]))?;
                 }
                 Opcode::OpSetGlobal => {
                     let global_index = BigEndian::read_u16(
@@ -171,7 +305,7 @@ impl Vm {
 
                     let base_pointer = self.current_frame().base_pointer;
 
-                    self.push(Rc::clone(&self.stack[base_pointer + local_index]));
+                    self.push(Rc::clone(&self.stack[base_pointer + local_index]))?;
                 }
                 Opcode::OpSetLocal => {
                     let local_index = instructions[instruction_pointer + 1] as usize;
@@ -187,14 +321,47 @@ impl Vm {
 
                     self.current_frame().instruction_pointer += 1;
 
-                    self.call_function(num_args);
+                    self.call_function(num_args)?;
+                }
+                Opcode::OpClosure => {
+                    let const_index = BigEndian::read_u16(
+                        &instructions[instruction_pointer + 1..instruction_pointer + 3],
+                    ) as usize;
+                    let num_free = instructions[instruction_pointer + 3] as usize;
+
+                    self.current_frame().instruction_pointer += 3;
+
+                    self.push_closure(const_index, num_free)?;
+                }
+                Opcode::OpGetBuiltin => {
+                    let builtin_index = instructions[instruction_pointer + 1] as usize;
+
+                    self.current_frame().instruction_pointer += 1;
+
+                    let builtin = builtins::BUILTINS[builtin_index].clone();
+
+                    self.push(Rc::new(Object::Builtin(builtin)))?;
+                }
+                Opcode::OpGetFree => {
+                    let free_index = instructions[instruction_pointer + 1] as usize;
+
+                    self.current_frame().instruction_pointer += 1;
+
+                    let free = Rc::clone(&self.current_frame().closure.free[free_index]);
+
+                    self.push(free)?;
+                }
+                Opcode::OpCurrentClosure => {
+                    let closure = self.current_frame().closure.clone();
+
+                    self.push(Rc::new(Object::Closure(Rc::new(closure))))?;
                 }
                 Opcode::OpReturn => {
                     let frame = self.pop_frame();
 
                     self.stack_pointer = frame.base_pointer - 1;
 
-                    self.push(Rc::new(Object::Null));
+                    self.push(Rc::new(Object::Null))?;
                 }
                 Opcode::OpReturnValue => {
                     let return_value = self.pop();
@@ -203,10 +370,10 @@ impl Vm {
 
                     self.stack_pointer = frame.base_pointer - 1;
 
-                    self.push(return_value);
+                    self.push(return_value)?;
                 }
                 Opcode::OpNull => {
-                    self.push(Rc::new(Object::Null));
+                    self.push(Rc::new(Object::Null))?;
                 }
                 Opcode::OpConst => {
                     let const_index = BigEndian::read_u16(
@@ -215,37 +382,63 @@ impl Vm {
 
                     self.current_frame().instruction_pointer += 2;
 
-                    self.push(Rc::clone(&self.constants[const_index]));
+                    self.push(Rc::clone(&self.constants[const_index]))?;
                 }
                 Opcode::OpAdd => {
                     let right = self.pop();
                     let left = self.pop();
 
                     let result = match (&*left, &*right) {
-                        (Object::Integer(l), Object::Integer(r)) => Object::Integer(l + r),
+                        (Object::Integer(l), Object::Integer(r)) => {
+                            Object::Integer(self.wrapping_mode.apply(ArithOp::Add, *l, *r)?)
+                        }
                         (Object::String(l), Object::String(r)) => {
                             Object::String(format!("{}{}", l, r))
                         }
                         _ => {
                             return Err(Error::msg(format!(
                                 "unsupported types for addition: {} + {}",
-                                left, right
+                                left.type_name(),
+                                right.type_name()
                             )));
                         }
                     };
 
-                    self.push(Rc::new(result));
+                    self.push(Rc::new(result))?;
                 }
                 Opcode::OpDiv => {
                     let right = self.stack[self.stack_pointer - 1].borrow();
                     let left = self.stack[self.stack_pointer - 2].borrow();
 
                     let result = match (left, right) {
-                        (Object::Integer(l), Object::Integer(r)) => Object::Integer(l / r),
+                        (Object::Integer(l), Object::Integer(r)) => {
+                            Object::Integer(self.wrapping_mode.apply(ArithOp::Div, *l, *r)?)
+                        }
                         _ => {
                             return Err(Error::msg(format!(
                                 "unsupported types for division: {} / {}",
-                                left, right
+                                left.type_name(),
+                                right.type_name()
+                            )));
+                        }
+                    };
+
+                    self.stack_pointer -= 1;
+                    self.stack[self.stack_pointer - 1] = Rc::new(result);
+                }
+                Opcode::OpMod => {
+                    let right = self.stack[self.stack_pointer - 1].borrow();
+                    let left = self.stack[self.stack_pointer - 2].borrow();
+
+                    let result = match (left, right) {
+                        (Object::Integer(l), Object::Integer(r)) => {
+                            Object::Integer(self.wrapping_mode.apply(ArithOp::Rem, *l, *r)?)
+                        }
+                        _ => {
+                            return Err(Error::msg(format!(
+                                "unsupported types for modulo: {} % {}",
+                                left.type_name(),
+                                right.type_name()
                             )));
                         }
                     };
@@ -258,11 +451,14 @@ impl Vm {
                     let left = self.stack[self.stack_pointer - 2].borrow();
 
                     let result = match (left, right) {
-                        (Object::Integer(l), Object::Integer(r)) => Object::Integer(l * r),
+                        (Object::Integer(l), Object::Integer(r)) => {
+                            Object::Integer(self.wrapping_mode.apply(ArithOp::Mul, *l, *r)?)
+                        }
                         _ => {
                             return Err(Error::msg(format!(
                                 "unsupported types for multiplication: {} * {}",
-                                left, right
+                                left.type_name(),
+                                right.type_name()
                             )));
                         }
                     };
@@ -275,11 +471,14 @@ impl Vm {
                     let left = self.stack[self.stack_pointer - 2].borrow();
 
                     let result = match (left, right) {
-                        (Object::Integer(l), Object::Integer(r)) => Object::Integer(l - r),
+                        (Object::Integer(l), Object::Integer(r)) => {
+                            Object::Integer(self.wrapping_mode.apply(ArithOp::Sub, *l, *r)?)
+                        }
                         _ => {
                             return Err(Error::msg(format!(
                                 "unsupported types for subtraction: {} - {}",
-                                left, right
+                                left.type_name(),
+                                right.type_name()
                             )));
                         }
                     };
@@ -287,43 +486,68 @@ impl Vm {
                     self.stack_pointer -= 1;
                     self.stack[self.stack_pointer - 1] = Rc::new(result);
                 }
+                Opcode::OpBitAnd
+                | Opcode::OpBitOr
+                | Opcode::OpBitXor
+                | Opcode::OpShiftLeft
+                | Opcode::OpShiftRight => {
+                    let right = self.stack[self.stack_pointer - 1].borrow();
+                    let left = self.stack[self.stack_pointer - 2].borrow();
+
+                    let result = execute_bitwise_operation(opcode, left, right)?;
+
+                    self.stack_pointer -= 1;
+                    self.stack[self.stack_pointer - 1] = Rc::new(result);
+                }
                 Opcode::OpTrue => {
-                    self.push(Rc::new(Object::Boolean(true)));
+                    self.push(Rc::new(Object::Boolean(true)))?;
                 }
                 Opcode::OpFalse => {
-                    self.push(Rc::new(Object::Boolean(false)));
+                    self.push(Rc::new(Object::Boolean(false)))?;
                 }
                 Opcode::OpEqual => {
-                    let right = self.stack[self.stack_pointer - 1].borrow();
-                    let left = self.stack[self.stack_pointer - 2].borrow();
+                    let right: &Object = self.stack[self.stack_pointer - 1].borrow();
+                    let left: &Object = self.stack[self.stack_pointer - 2].borrow();
 
+                    // Values of the same type compare structurally, as in the
+                    // evaluator. Only integers and floats compare across types.
                     let result = match (left, right) {
-                        (Object::Integer(l), Object::Integer(r)) => Object::Boolean(l == r),
-                        (Object::Boolean(l), Object::Boolean(r)) => Object::Boolean(l == r),
-                        _ => {
-                            return Err(Error::msg(format!(
-                                "unsupported types for equality: {} == {}",
-                                left, right
-                            )));
+                        _ if std::mem::discriminant(left) == std::mem::discriminant(right) => {
+                            Object::Boolean(left.equals(right))
                         }
+                        _ => match promote_to_floats(left, right) {
+                            Some((l, r)) => Object::Boolean(l == r),
+                            None => {
+                                return Err(Error::msg(format!(
+                                    "unsupported types for equality: {} == {}",
+                                    left.type_name(),
+                                    right.type_name()
+                                )));
+                            }
+                        },
                     };
 
                     self.stack_pointer -= 1;
                     self.stack[self.stack_pointer - 1] = Rc::new(result);
                 }
                 Opcode::OpNotEqual => {
-                    let right = self.stack[self.stack_pointer - 1].borrow();
-                    let left = self.stack[self.stack_pointer - 2].borrow();
+                    let right: &Object = self.stack[self.stack_pointer - 1].borrow();
+                    let left: &Object = self.stack[self.stack_pointer - 2].borrow();
 
                     let result = match (left, right) {
-                        (Object::Integer(l), Object::Integer(r)) => Object::Boolean(l != r),
-                        (Object::Boolean(l), Object::Boolean(r)) => Object::Boolean(l != r),
-                        _ => {
-                            return Err(Error::msg(format!(
-                                "unsupported types for inequality: {} != {}",
-                                left, right
-                            )));
+                        _ if std::mem::discriminant(left) == std::mem::discriminant(right) => {
+                            Object::Boolean(!left.equals(right))
                         }
+                        _ => match promote_to_floats(left, right) {
+                            Some((l, r)) => Object::Boolean(l != r),
+                            None => {
+                                return Err(Error::msg(format!(
+                                    "unsupported types for inequality: {} != {}",
+                                    left.type_name(),
+                                    right.type_name()
+                                )));
+                            }
+                        },
                     };
 
                     self.stack_pointer -= 1;
@@ -335,12 +559,37 @@ impl Vm {
 
                     let result = match (left, right) {
                         (Object::Integer(l), Object::Integer(r)) => Object::Boolean(l > r),
-                        _ => {
-                            return Err(Error::msg(format!(
-                                "unsupported types for greater than: {} > {}",
-                                left, right
-                            )));
-                        }
+                        _ => match promote_to_floats(left, right) {
+                            Some((l, r)) => Object::Boolean(l > r),
+                            None => {
+                                return Err(Error::msg(format!(
+                                    "unsupported types for greater than: {} > {}",
+                                    left.type_name(),
+                                    right.type_name()
+                                )));
+                            }
+                        },
+                    };
+
+                    self.stack_pointer -= 1;
+                    self.stack[self.stack_pointer - 1] = Rc::new(result);
+                }
+                Opcode::OpGreaterThanOrEqual => {
+                    let right = self.stack[self.stack_pointer - 1].borrow();
+                    let left = self.stack[self.stack_pointer - 2].borrow();
+
+                    let result = match (left, right) {
+                        (Object::Integer(l), Object::Integer(r)) => Object::Boolean(l >= r),
+                        _ => match promote_to_floats(left, right) {
+                            Some((l, r)) => Object::Boolean(l >= r),
+                            None => {
+                                return Err(Error::msg(format!(
+                                    "unsupported types for greater than or equal: {} >= {}",
+                                    left.type_name(),
+                                    right.type_name()
+                                )));
+                            }
+                        },
                     };
 
                     self.stack_pointer -= 1;
@@ -349,34 +598,39 @@ impl Vm {
                 Opcode::OpBang => {
                     let operand = self.pop();
 
+                    self.push(Rc::new(Object::Boolean(!operand.is_truthy())))?;
+                }
+                Opcode::OpMinus => {
+                    let operand = self.pop();
+
                     let result = match &*operand {
-                        Object::Boolean(boolean) => Object::Boolean(!boolean),
-                        Object::Integer(integer) => Object::Boolean(!integer == 0),
-                        Object::Null => Object::Boolean(true),
+                        Object::Integer(integer) => {
+                            Object::Integer(self.wrapping_mode.negate(*integer)?)
+                        }
                         _ => {
                             return Err(Error::msg(format!(
-                                "unsupported type for negation: !{}",
+                                "unsupported type for negation: -{}",
                                 operand
                             )));
                         }
                     };
 
-                    self.push(Rc::new(result));
+                    self.push(Rc::new(result))?;
                 }
-                Opcode::OpMinus => {
+                Opcode::OpBitNot => {
                     let operand = self.pop();
 
                     let result = match &*operand {
-                        Object::Integer(integer) => Object::Integer(-integer),
+                        Object::Integer(integer) => Object::Integer(!integer),
                         _ => {
                             return Err(Error::msg(format!(
-                                "unsupported type for negation: -{}",
-                                operand
+                                "unsupported type for bitwise not: ~{}",
+                                operand.type_name()
                             )));
                         }
                     };
 
-                    self.push(Rc::new(result));
+                    self.push(Rc::new(result))?;
                 }
                 Opcode::OpArray => {
                     let num_elements = BigEndian::read_u16(
@@ -393,7 +647,54 @@ impl Vm {
 
                     elements.reverse();
 
-                    self.push(Rc::new(Object::Array(elements)));
+                    self.push(Rc::new(Object::Array(elements)))?;
+                }
+                Opcode::OpTuple => {
+                    let num_elements = BigEndian::read_u16(
+                        &instructions[instruction_pointer + 1..instruction_pointer + 3],
+                    ) as usize;
+
+                    self.current_frame().instruction_pointer += 2;
+
+                    let mut elements = Vec::with_capacity(num_elements);
+
+                    for _ in 0..num_elements {
+                        elements.push(self.pop());
+                    }
+
+                    elements.reverse();
+
+                    self.push(Rc::new(Object::Tuple(elements)))?;
+                }
+                Opcode::OpHash => {
+                    let num_elements = BigEndian::read_u16(
+                        &instructions[instruction_pointer + 1..instruction_pointer + 3],
+                    ) as usize;
+
+                    self.current_frame().instruction_pointer += 2;
+
+                    let hash =
+                        self.build_hash(self.stack_pointer - num_elements, self.stack_pointer)?;
+                    self.stack_pointer -= num_elements;
+
+                    self.push(Rc::new(hash))?;
+                }
+                Opcode::OpSlice => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    let left = self.pop();
+
+                    self.push(Rc::new(left.slice(&start, &end)?))?;
+                }
+                Opcode::OpRange => {
+                    let inclusive = instructions[instruction_pointer + 1] == 1;
+
+                    self.current_frame().instruction_pointer += 1;
+
+                    let end = self.pop();
+                    let start = self.pop();
+
+                    self.push(Rc::new(Object::range(&start, &end, inclusive)?))?;
                 }
                 Opcode::OpIndex => {
                     let index = self.pop();
@@ -401,30 +702,36 @@ impl Vm {
 
                     let result = match (&*left, &*index) {
                         (Object::Array(elements), Object::Integer(integer)) => {
-                            let idx = *integer as usize;
-
-                            if idx >= elements.len() {
-                                return Err(Error::msg(format!(
-                                    "index out of bounds: index={}, length={}",
-                                    idx,
-                                    elements.len()
-                                )));
+                            match object::array_index(elements.len(), *integer) {
+                                Some(idx) => Rc::clone(&elements[idx]),
+                                None => Rc::new(Object::Null),
                             }
-
-                            Rc::clone(&elements[idx])
                         }
+                        (Object::String(string), Object::Integer(integer)) => {
+                            Rc::new(Object::string_index(string, *integer))
+                        }
+                        (
+                            Object::Range {
+                                start,
+                                end,
+                                inclusive,
+                            },
+                            Object::Integer(integer),
+                        ) => Rc::new(Object::range_index(*start, *end, *inclusive, *integer)),
+                        (Object::Hash(pairs), _) => match pairs.get(&index.hash_key()?) {
+                            Some(value) => Rc::clone(value),
+                            None => Rc::new(Object::Null),
+                        },
                         _ => {
                             return Err(Error::msg(format!(
                                 "unsupported types for index: {}[{}]",
-                                left, index
+                                left.type_name(),
+                                index.type_name()
                             )));
                         }
                     };
 
-                    self.push(result);
-                }
-                _ => {
-                    return Err(Error::msg(format!("unknown opcode: {}", op)));
+                    self.push(result)?;
                 }
             }
         }
@@ -432,6 +739,15 @@ impl Vm {
         Ok(())
     }
 
+    /// Returns the value on top of the stack, such as the result a program
+    /// compiled with `Compiler::with_keep_last_value` leaves behind.
+    pub fn stack_top(&self) -> Option<Rc<Object>> {
+        match self.stack_pointer {
+            0 => None,
+            pointer => Some(Rc::clone(&self.stack[pointer - 1])),
+        }
+    }
+
     pub fn last_popped_stack_elem(&self) -> Rc<Object> {
         Rc::clone(&self.stack[self.stack_pointer])
     }
@@ -441,16 +757,62 @@ impl Vm {
         Rc::clone(&self.stack[self.stack_pointer])
     }
 
-    pub fn push(&mut self, object: Rc<Object>) {
+    /// Pushes `object`, failing once the stack holds `STACK_SIZE` values.
+    pub fn push(&mut self, object: Rc<Object>) -> Result<(), Error> {
+        if self.stack_pointer == STACK_SIZE {
+            return Err(Error::msg("stack overflow"));
+        }
+
         self.stack[self.stack_pointer] = object;
         self.stack_pointer += 1;
+
+        Ok(())
     }
 }
 
-fn is_truthy(object: &Object) -> bool {
-    match object {
-        Object::Boolean(boolean) => *boolean,
-        Object::Integer(integer) => *integer != 0,
-        _ => true,
+fn execute_bitwise_operation(op: Opcode, left: &Object, right: &Object) -> Result<Object, Error> {
+    let (name, operator) = match op {
+        Opcode::OpBitAnd => ("bitwise and", "&"),
+        Opcode::OpBitOr => ("bitwise or", "|"),
+        Opcode::OpBitXor => ("bitwise xor", "^"),
+        Opcode::OpShiftLeft => ("left shift", "<<"),
+        Opcode::OpShiftRight => ("right shift", ">>"),
+        _ => unreachable!("{} is not a bitwise operation", op),
+    };
+
+    let (l, r) = match (left, right) {
+        (Object::Integer(l), Object::Integer(r)) => (*l, *r),
+        _ => {
+            return Err(Error::msg(format!(
+                "unsupported types for {}: {} {} {}",
+                name,
+                left.type_name(),
+                operator,
+                right.type_name()
+            )));
+        }
+    };
+
+    let result = match op {
+        Opcode::OpBitAnd => Some(l & r),
+        Opcode::OpBitOr => Some(l | r),
+        Opcode::OpBitXor => Some(l ^ r),
+        Opcode::OpShiftLeft => u32::try_from(r).ok().and_then(|s| l.checked_shl(s)),
+        _ => u32::try_from(r).ok().and_then(|s| l.checked_shr(s)),
+    };
+
+    result
+        .map(Object::Integer)
+        .ok_or_else(|| Error::msg(format!("shift amount out of range: {}", r)))
+}
+
+/// Converts a pair of numeric operands to floats when at least one of them
+/// is a float, so integers compare against floats by value.
+fn promote_to_floats(left: &Object, right: &Object) -> Option<(f64, f64)> {
+    match (left, right) {
+        (Object::Float(l), Object::Float(r)) => Some((*l, *r)),
+        (Object::Integer(l), Object::Float(r)) => Some((*l as f64, *r)),
+        (Object::Float(l), Object::Integer(r)) => Some((*l, *r as f64)),
+        _ => None,
     }
 }
@@ -1,9 +1,9 @@
-use std::{borrow::Borrow, rc::Rc};
+use std::{borrow::Borrow, cell::RefCell, collections::HashMap, io::Write, rc::Rc};
 
 use anyhow::Error;
-use compiler::Compiler;
+use compiler::{Bytecode, Compiler};
 use lexer::Lexer;
-use object::Object;
+use object::{HashKey, Object, WrappingMode};
 use parser::{ast::Node, Parser};
 use vm::Vm;
 
@@ -65,6 +65,32 @@ fn test_array_expressions() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_tuple_expressions() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "(1,)".to_string(),
+            expected: Object::Tuple(vec![Object::Integer(1).into()]),
+        },
+        VmTestCase {
+            input: "(1 + 2, \"a\", [3])".to_string(),
+            expected: Object::Tuple(vec![
+                Object::Integer(3).into(),
+                Object::String("a".to_string()).into(),
+                Object::Array(vec![Object::Integer(3).into()]).into(),
+            ]),
+        },
+        VmTestCase {
+            input: "$f = function () { return (1, 2); }; $f()".to_string(),
+            expected: Object::Tuple(vec![Object::Integer(1).into(), Object::Integer(2).into()]),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
 #[test]
 fn test_boolean_expressions() -> Result<(), Error> {
     let tests = vec![
@@ -92,6 +118,22 @@ fn test_boolean_expressions() -> Result<(), Error> {
             input: "1 > 1".to_string(),
             expected: Object::Boolean(false),
         },
+        VmTestCase {
+            input: "1 <= 1".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "2 <= 1".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1 >= 1".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "1 >= 2".to_string(),
+            expected: Object::Boolean(false),
+        },
         VmTestCase {
             input: "1 == 1".to_string(),
             expected: Object::Boolean(true),
@@ -116,6 +158,22 @@ fn test_boolean_expressions() -> Result<(), Error> {
             input: "false == false".to_string(),
             expected: Object::Boolean(true),
         },
+        VmTestCase {
+            input: "null == null".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "null != null".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "!null".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "$x = null; if ($x) { 1 } else { 2 }".to_string(),
+            expected: Object::Integer(2),
+        },
         VmTestCase {
             input: "true == false".to_string(),
             expected: Object::Boolean(false),
@@ -163,9 +221,200 @@ fn test_boolean_expressions() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_structural_equality() -> Result<(), Error> {
+    let tests = [
+        ("\"a\" == \"a\"", true),
+        ("\"a\" != \"b\"", true),
+        ("'a' == 'a'", true),
+        ("[1, [2, 3]] == [1, [2, 3]]", true),
+        ("[1, [2, 3]] != [1, [2, 4]]", true),
+        ("[1, 2] == [1, 2, 3]", false),
+        ("{\"a\": 1, \"b\": [2]} == {\"b\": [2], \"a\": 1}", true),
+        ("(1, \"a\") == (1, \"a\")", true),
+        ("0..5 == 0..=5", false),
+        ("1 == 1.0", true),
+    ]
+    .into_iter()
+    .map(|(input, expected)| VmTestCase {
+        input: input.to_string(),
+        expected: Object::Boolean(expected),
+    })
+    .collect();
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_float_comparisons() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "1.5 < 2.0".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "2.0 < 1.5".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1.5 > 1.5".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1.5 >= 1.5".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "1.5 <= 1.25".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1.0 == 1.0".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "0.1 != 0.1".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "0.5 == 0.25".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1 == 1.0".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "1.0 != 1".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1 < 1.5".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "2.5 > 2".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "2 >= 2.0".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "3 <= 2.5".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "(1 < 1.5) == true".to_string(),
+            expected: Object::Boolean(true),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    let errors = vec![
+        (
+            "1.5 < true",
+            "unsupported types for greater than: BOOLEAN > FLOAT",
+        ),
+        (
+            r#"1.0 == "1""#,
+            "unsupported types for equality: FLOAT == STRING",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_closures() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$newClosure = function ($a) { function () { $a; }; }; $closure = $newClosure(99); $closure();".to_string(),
+            expected: Object::Integer(99),
+        },
+        VmTestCase {
+            input: "$newAdder = function ($a, $b) { function ($c) { $a + $b + $c; }; }; $adder = $newAdder(1, 2); $adder(8);".to_string(),
+            expected: Object::Integer(11),
+        },
+        VmTestCase {
+            input: "$newAdder = function ($a) { $b = 2; function ($c) { function ($d) { $a + $b + $c + $d; }; }; }; $adder = $newAdder(1); $adder(3)(4);".to_string(),
+            expected: Object::Integer(10),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_logical_expressions() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "true && true".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "true && false".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "false || true".to_string(),
+            expected: Object::Boolean(true),
+        },
+        VmTestCase {
+            input: "false || false".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "1 < 2 && 2 < 3 || false".to_string(),
+            expected: Object::Boolean(true),
+        },
+        // The right-hand side would panic on division by zero if it ran.
+        VmTestCase {
+            input: "false && 1 / 0 == 1".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "true || 1 / 0 == 1".to_string(),
+            expected: Object::Boolean(true),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
 #[test]
 fn test_conditionals() -> Result<(), Error> {
     let tests = vec![
+        VmTestCase {
+            input: "1 > 2 ? 10 : 2 > 1 ? 20 : 30".to_string(),
+            expected: Object::Integer(20),
+        },
+        VmTestCase {
+            input: "(true ? 3 : 4) * 5".to_string(),
+            expected: Object::Integer(15),
+        },
         VmTestCase {
             input: "if (true) { 10 }".to_string(),
             expected: Object::Integer(10),
@@ -202,6 +451,46 @@ fn test_conditionals() -> Result<(), Error> {
             input: "if (false) { 10 }".to_string(),
             expected: Object::Null,
         },
+        VmTestCase {
+            input: "".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "if (true) { }".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "if (false) { 10 } else { }".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "if (0) { 10 } else { 20 }".to_string(),
+            expected: Object::Integer(10),
+        },
+        VmTestCase {
+            input: "if (\"\") { 10 } else { 20 }".to_string(),
+            expected: Object::Integer(10),
+        },
+        VmTestCase {
+            input: "if ([]) { 10 } else { 20 }".to_string(),
+            expected: Object::Integer(10),
+        },
+        VmTestCase {
+            input: "if (if (false) { 1 }) { 10 } else { 20 }".to_string(),
+            expected: Object::Integer(20),
+        },
+        VmTestCase {
+            input: "!0 || !\"\"".to_string(),
+            expected: Object::Boolean(false),
+        },
+        VmTestCase {
+            input: "$x = 0; if (true) { $x = 5 }".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "$x = if (true) { }; 1; $x".to_string(),
+            expected: Object::Null,
+        },
     ];
 
     run_vm_tests(tests)?;
@@ -260,11 +549,26 @@ fn test_functions_with_arguments() -> Result<(), Error> {
 }
 
 #[test]
-fn test_functions_with_no_return_value() -> Result<(), Error> {
-    let tests = vec![VmTestCase {
-        input: "$x = function () { }; $x();".to_string(),
-        expected: Object::Null,
-    }];
+fn test_functions_with_local_bindings() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$one = function () { $one = 1; $one; }; $one();".to_string(),
+            expected: Object::Integer(1),
+        },
+        VmTestCase {
+            input: "$sum = function () { $a = 1; $b = 2; $a + $b; }; $sum();".to_string(),
+            expected: Object::Integer(3),
+        },
+        VmTestCase {
+            input: "$first = function () { $a = 50; $a; }; $second = function () { $a = 100; $a; }; $first() + $second();".to_string(),
+            expected: Object::Integer(150),
+        },
+        VmTestCase {
+            input: "$seed = 50; $minus = function () { $num = 1; $seed - $num; }; $minus();"
+                .to_string(),
+            expected: Object::Integer(49),
+        },
+    ];
 
     run_vm_tests(tests)?;
 
@@ -272,13 +576,10 @@ fn test_functions_with_no_return_value() -> Result<(), Error> {
 }
 
 #[test]
-fn test_first_class_functions() -> Result<(), Error> {
+fn test_functions_with_no_return_value() -> Result<(), Error> {
     let tests = vec![VmTestCase {
-        input: "$returnsOne = function () { 1; }; 
-                    $returnsOneReturner = function () { $returnsOne; };
-                    $returnsOneReturner()();"
-            .to_string(),
-        expected: Object::Integer(1),
+        input: "$x = function () { }; $x();".to_string(),
+        expected: Object::Null,
     }];
 
     run_vm_tests(tests)?;
@@ -287,58 +588,316 @@ fn test_first_class_functions() -> Result<(), Error> {
 }
 
 #[test]
-fn test_integer_arithmetic() -> Result<(), Error> {
+fn test_builtin_functions() -> Result<(), Error> {
     let tests = vec![
         VmTestCase {
-            input: "1".to_string(),
-            expected: Object::Integer(1),
+            input: "len(\"\")".to_string(),
+            expected: Object::Integer(0),
         },
         VmTestCase {
-            input: "2".to_string(),
-            expected: Object::Integer(2),
+            input: "len(\"four\")".to_string(),
+            expected: Object::Integer(4),
         },
         VmTestCase {
-            input: "1 + 2".to_string(),
+            input: "len([1, 2, 3])".to_string(),
             expected: Object::Integer(3),
         },
         VmTestCase {
-            input: "1 - 2".to_string(),
-            expected: Object::Integer(-1),
-        },
-        VmTestCase {
-            input: "1 * 2".to_string(),
-            expected: Object::Integer(2),
+            input: "first([1, 2, 3])".to_string(),
+            expected: Object::Integer(1),
         },
         VmTestCase {
-            input: "4 / 2".to_string(),
-            expected: Object::Integer(2),
+            input: "first([])".to_string(),
+            expected: Object::Null,
         },
         VmTestCase {
-            input: "50 / 2 * 2 + 10 - 5".to_string(),
-            expected: Object::Integer(55),
+            input: "last([1, 2, 3])".to_string(),
+            expected: Object::Integer(3),
         },
         VmTestCase {
-            input: "-5".to_string(),
-            expected: Object::Integer(-5),
+            input: "rest([1, 2, 3])".to_string(),
+            expected: Object::Array(vec![
+                Rc::new(Object::Integer(2)),
+                Rc::new(Object::Integer(3)),
+            ]),
         },
         VmTestCase {
-            input: "-10".to_string(),
-            expected: Object::Integer(-10),
+            input: "rest([])".to_string(),
+            expected: Object::Null,
         },
         VmTestCase {
-            input: "-50 + 100 + -50".to_string(),
-            expected: Object::Integer(0),
+            input: "push([], 1)".to_string(),
+            expected: Object::Array(vec![Rc::new(Object::Integer(1))]),
         },
         VmTestCase {
-            input: "(5 + 10 * 2 + 15 / 3) * 2 + -10".to_string(),
-            expected: Object::Integer(50),
+            input: "$length = function ($a) { len($a); }; $length([1, 2]);".to_string(),
+            expected: Object::Integer(2),
         },
     ];
 
     run_vm_tests(tests)?;
 
-    Ok(())
-}
+    let errors = vec![
+        ("len(1)", "argument to `len` not supported, got INTEGER"),
+        (
+            "len(\"one\", \"two\")",
+            "wrong number of arguments to `len`: want=1, got=2",
+        ),
+        (
+            "push(1, 1)",
+            "argument to `push` must be an array, got INTEGER",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let mut compiler = Compiler::new();
+
+        let bytecode = compiler.compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_calling_functions_with_wrong_arguments() -> Result<(), Error> {
+    let tests = vec![
+        (
+            "function () { 1; }(1);",
+            "wrong number of arguments: want=0, got=1",
+        ),
+        (
+            "function ($a) { $a; }();",
+            "wrong number of arguments: want=1, got=0",
+        ),
+        (
+            "function ($a, $b) { $a + $b; }(1);",
+            "wrong number of arguments: want=2, got=1",
+        ),
+    ];
+
+    for (input, expected) in tests {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let mut compiler = Compiler::new();
+
+        let bytecode = compiler.compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_first_class_functions() -> Result<(), Error> {
+    let tests = vec![VmTestCase {
+        input: "$returnsOne = function () { 1; }; 
+                    $returnsOneReturner = function () { $returnsOne; };
+                    $returnsOneReturner()();"
+            .to_string(),
+        expected: Object::Integer(1),
+    }];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_bitwise_operators() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "5 & 3".to_string(),
+            expected: Object::Integer(1),
+        },
+        VmTestCase {
+            input: "5 | 3".to_string(),
+            expected: Object::Integer(7),
+        },
+        VmTestCase {
+            input: "5 ^ 3".to_string(),
+            expected: Object::Integer(6),
+        },
+        VmTestCase {
+            input: "1 << 4".to_string(),
+            expected: Object::Integer(16),
+        },
+        VmTestCase {
+            input: "16 >> 2".to_string(),
+            expected: Object::Integer(4),
+        },
+        VmTestCase {
+            input: "-16 >> 2".to_string(),
+            expected: Object::Integer(-4),
+        },
+        VmTestCase {
+            input: "1 << 63 >> 63".to_string(),
+            expected: Object::Integer(-1),
+        },
+        VmTestCase {
+            input: "6 & 3 | 8 ^ 1".to_string(),
+            expected: Object::Integer(11),
+        },
+        VmTestCase {
+            input: "1 + 1 << 2".to_string(),
+            expected: Object::Integer(8),
+        },
+        VmTestCase {
+            input: "~5".to_string(),
+            expected: Object::Integer(-6),
+        },
+        VmTestCase {
+            input: "~0 & 7".to_string(),
+            expected: Object::Integer(7),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    let errors = vec![
+        ("1 << 64", "shift amount out of range: 64"),
+        ("1 >> -1", "shift amount out of range: -1"),
+        (
+            "1 & true",
+            "unsupported types for bitwise and: INTEGER & BOOLEAN",
+        ),
+        (
+            "1.5 << 1",
+            "unsupported types for left shift: FLOAT << INTEGER",
+        ),
+        ("~true", "unsupported type for bitwise not: ~BOOLEAN"),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_integer_arithmetic() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "1".to_string(),
+            expected: Object::Integer(1),
+        },
+        VmTestCase {
+            input: "2".to_string(),
+            expected: Object::Integer(2),
+        },
+        VmTestCase {
+            input: "1 + 2".to_string(),
+            expected: Object::Integer(3),
+        },
+        VmTestCase {
+            input: "1 - 2".to_string(),
+            expected: Object::Integer(-1),
+        },
+        VmTestCase {
+            input: "1 * 2".to_string(),
+            expected: Object::Integer(2),
+        },
+        VmTestCase {
+            input: "4 / 2".to_string(),
+            expected: Object::Integer(2),
+        },
+        VmTestCase {
+            input: "10 % 3".to_string(),
+            expected: Object::Integer(1),
+        },
+        VmTestCase {
+            input: "-7 % 3".to_string(),
+            expected: Object::Integer(-1),
+        },
+        VmTestCase {
+            input: "50 / 2 * 2 + 10 - 5".to_string(),
+            expected: Object::Integer(55),
+        },
+        VmTestCase {
+            input: "-5".to_string(),
+            expected: Object::Integer(-5),
+        },
+        VmTestCase {
+            input: "-10".to_string(),
+            expected: Object::Integer(-10),
+        },
+        VmTestCase {
+            input: "-50 + 100 + -50".to_string(),
+            expected: Object::Integer(0),
+        },
+        VmTestCase {
+            input: "(5 + 10 * 2 + 15 / 3) * 2 + -10".to_string(),
+            expected: Object::Integer(50),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_division_errors() -> Result<(), Error> {
+    let errors = vec![
+        ("1 / 0", "division by zero".to_string()),
+        ("1 % 0", "division by zero".to_string()),
+        (
+            "(-9223372036854775807 - 1) % -1",
+            format!("integer overflow: {} % -1", i64::MIN),
+        ),
+        (
+            "(-9223372036854775807 - 1) / -1",
+            format!("integer overflow: {} / -1", i64::MIN),
+        ),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected, "{}", input),
+        }
+    }
+
+    Ok(())
+}
 
 #[test]
 fn test_global_dollar_statements() -> Result<(), Error> {
@@ -355,6 +914,46 @@ fn test_global_dollar_statements() -> Result<(), Error> {
             input: "$one = 1; $two = $one + $one; $one + $two".to_string(),
             expected: Object::Integer(3),
         },
+        VmTestCase {
+            input: "$y = ($x = 3); $x + $y".to_string(),
+            expected: Object::Integer(6),
+        },
+        VmTestCase {
+            input: "function () { $a = $b = 2; $a * $b }()".to_string(),
+            expected: Object::Integer(4),
+        },
+        VmTestCase {
+            input: "$i = 0; $n = 0; while (($i = $i + 1) < 4) { $n += $i; }; $n".to_string(),
+            expected: Object::Integer(6),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_literals() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "{}".to_string(),
+            expected: Object::Hash(HashMap::new()),
+        },
+        VmTestCase {
+            input: "{1: 2, 2: 3}".to_string(),
+            expected: Object::Hash(HashMap::from([
+                (HashKey::Integer(1), Rc::new(Object::Integer(2))),
+                (HashKey::Integer(2), Rc::new(Object::Integer(3))),
+            ])),
+        },
+        VmTestCase {
+            input: "{1 + 1: 2 * 2, 3 + 3: 4 * 4}".to_string(),
+            expected: Object::Hash(HashMap::from([
+                (HashKey::Integer(2), Rc::new(Object::Integer(4))),
+                (HashKey::Integer(6), Rc::new(Object::Integer(16))),
+            ])),
+        },
     ];
 
     run_vm_tests(tests)?;
@@ -377,6 +976,42 @@ fn test_index_expressions() -> Result<(), Error> {
             input: "[[1, 1, 1]][0][0]".to_string(),
             expected: Object::Integer(1),
         },
+        VmTestCase {
+            input: "[1, 2, 3][-1]".to_string(),
+            expected: Object::Integer(3),
+        },
+        VmTestCase {
+            input: "[1, 2, 3][-3]".to_string(),
+            expected: Object::Integer(1),
+        },
+        VmTestCase {
+            input: "[1, 2, 3][-4]".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "[1, 2, 3][3]".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "[][0]".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "{1: 1, 2: 2}[1]".to_string(),
+            expected: Object::Integer(1),
+        },
+        VmTestCase {
+            input: "{\"one\": 1, true: 2}[true]".to_string(),
+            expected: Object::Integer(2),
+        },
+        VmTestCase {
+            input: "{1: 1}[0]".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "{}[0]".to_string(),
+            expected: Object::Null,
+        },
     ];
 
     run_vm_tests(tests)?;
@@ -384,6 +1019,94 @@ fn test_index_expressions() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn test_slice_expressions() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "\"hello\"[1]".to_string(),
+            expected: Object::Char('e'),
+        },
+        VmTestCase {
+            input: "\"hello\"[-1]".to_string(),
+            expected: Object::Char('o'),
+        },
+        VmTestCase {
+            input: "\"hello\"[1:3]".to_string(),
+            expected: Object::String("el".to_string()),
+        },
+        VmTestCase {
+            input: "$s = \"hello\"; $s[:2] + $s[3:]".to_string(),
+            expected: Object::String("helo".to_string()),
+        },
+        VmTestCase {
+            input: "\"hello\"[2:100]".to_string(),
+            expected: Object::String("llo".to_string()),
+        },
+        VmTestCase {
+            input: "[1, 2, 3, 4][-2:]".to_string(),
+            expected: Object::Array(vec![
+                Rc::new(Object::Integer(3)),
+                Rc::new(Object::Integer(4)),
+            ]),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_ranges() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "1..=3".to_string(),
+            expected: Object::Range {
+                start: 1,
+                end: 3,
+                inclusive: true,
+            },
+        },
+        VmTestCase {
+            input: "to_array(0..3)".to_string(),
+            expected: Object::Array(vec![
+                Rc::new(Object::Integer(0)),
+                Rc::new(Object::Integer(1)),
+                Rc::new(Object::Integer(2)),
+            ]),
+        },
+        VmTestCase {
+            input: "$n = 4; len(0..=$n)".to_string(),
+            expected: Object::Integer(5),
+        },
+        VmTestCase {
+            input: "(10..20)[-1]".to_string(),
+            expected: Object::Integer(19),
+        },
+        VmTestCase {
+            input:
+                "$r = 1..=4; $sum = 0; for ($i = 0; $i < len($r); $i++) { $sum += $r[$i] }; $sum"
+                    .to_string(),
+            expected: Object::Integer(10),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    let program = Parser::new(Lexer::new("0..\"5\"")).parse_program()?;
+    let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+    match Vm::new(bytecode).run() {
+        Ok(_) => panic!("expected a range error"),
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "unsupported types for range: INTEGER..STRING"
+        ),
+    }
+
+    Ok(())
+}
+
 #[test]
 fn test_string_expressions() -> Result<(), Error> {
     let tests = vec![
@@ -403,6 +1126,32 @@ fn test_string_expressions() -> Result<(), Error> {
 
     run_vm_tests(tests)?;
 
+    let errors = vec![
+        (
+            r#""hello" + 1"#,
+            "unsupported types for addition: STRING + INTEGER",
+        ),
+        (
+            r#"1 + "hello""#,
+            "unsupported types for addition: INTEGER + STRING",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected),
+        }
+    }
+
     Ok(())
 }
 
@@ -444,3 +1193,366 @@ fn assert_constants(expected: &Vec<Object>, actual: &Vec<Rc<Object>>) {
         assert_eq!(exp, got);
     }
 }
+
+#[test]
+fn test_while_loops() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$x = 0; while ($x < 3) { $x = $x + 1; }; $x".to_string(),
+            expected: Object::Integer(3),
+        },
+        VmTestCase {
+            input: "$x = 10; while ($x > 1) { $x /= 2; $x -= 1; }; $x *= 3; $x += 1; $x"
+                .to_string(),
+            expected: Object::Integer(4),
+        },
+        VmTestCase {
+            input: "while (false) { 1 }".to_string(),
+            expected: Object::Null,
+        },
+        VmTestCase {
+            input: "
+            $sum = function ($n) {
+                $total = 0;
+                $i = 1;
+                while ($i <= $n) { $total = $total + $i; $i = $i + 1; };
+                $total;
+            };
+            $sum(10)"
+                .to_string(),
+            expected: Object::Integer(55),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_break_and_continue() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$x = 0; while (true) { $x += 1; if ($x == 5) { break; } }; $x".to_string(),
+            expected: Object::Integer(5),
+        },
+        VmTestCase {
+            input: "
+            $sum = 0;
+            for ($i = 0; $i < 10; $i += 1) {
+                if ($i % 2 == 0) { continue; }
+                if ($i > 7) { break; }
+                $sum += $i;
+            };
+            $sum"
+                .to_string(),
+            expected: Object::Integer(16),
+        },
+        VmTestCase {
+            input: "
+            $count = 0;
+            for ($i = 0; $i < 3; $i += 1) {
+                $j = 0;
+                while (true) { $j += 1; $count += 1; if ($j == 2) { break; } };
+            };
+            $count"
+                .to_string(),
+            expected: Object::Integer(6),
+        },
+        VmTestCase {
+            input: "
+            $find = function ($arr, $target) {
+                $i = 0;
+                $found = -1;
+                while ($i < len($arr)) {
+                    if ($arr[$i] == $target) { $found = $i; break; }
+                    $i += 1;
+                };
+                $found;
+            };
+            $find([4, 8, 15, 16], 15)"
+                .to_string(),
+            expected: Object::Integer(2),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_postfix_expressions() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$x = 1; $y = $x++; [$x, $y]".to_string(),
+            expected: Object::Array(vec![
+                Rc::new(Object::Integer(2)),
+                Rc::new(Object::Integer(1)),
+            ]),
+        },
+        VmTestCase {
+            input: "$x = 5; $x--; $x".to_string(),
+            expected: Object::Integer(4),
+        },
+        VmTestCase {
+            input: "$sum = 0; for ($i = 0; $i < 4; $i++) { $sum += $i; }; $sum".to_string(),
+            expected: Object::Integer(6),
+        },
+        VmTestCase {
+            input: "$countdown = function ($n) { while ($n > 0) { $n--; }; $n }; $countdown(3)"
+                .to_string(),
+            expected: Object::Integer(0),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_for_loops() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$sum = 0; for ($i = 1; $i <= 10; $i = $i + 1) { $sum = $sum + $i; }; $sum"
+                .to_string(),
+            expected: Object::Integer(55),
+        },
+        VmTestCase {
+            input: "$i = 5; for (; $i > 0;) { $i = $i - 1; }; $i".to_string(),
+            expected: Object::Integer(0),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_stack_top_with_kept_last_value() -> Result<(), Error> {
+    let mut parser = Parser::new(Lexer::new("1; $a = 2; $a * 3"));
+
+    let program = parser.parse_program()?;
+
+    let bytecode = Compiler::new()
+        .with_keep_last_value()
+        .compile(&Node::Program(program))?;
+
+    let mut vm = Vm::new(bytecode);
+    vm.run()?;
+
+    assert_eq!(vm.stack_top().as_deref(), Some(&Object::Integer(6)));
+
+    Ok(())
+}
+
+#[test]
+fn test_integer_overflow() -> Result<(), Error> {
+    let max = i64::MAX;
+    let min = i64::MIN;
+
+    let tests = [
+        (
+            format!("{} + 1", max),
+            Err(format!("integer overflow: {} + 1", max)),
+            Object::Integer(min),
+        ),
+        (
+            format!("$a = {}; $a * 2", max),
+            Err(format!("integer overflow: {} * 2", max)),
+            Object::Integer(-2),
+        ),
+        (
+            format!("$a = -{}; $a - 2", max),
+            Err(format!("integer overflow: -{} - 2", max)),
+            Object::Integer(max),
+        ),
+        (
+            format!("$a = {}; $a++; $a", max),
+            Err(format!("integer overflow: {} + 1", max)),
+            Object::Integer(min),
+        ),
+        (
+            format!("$a = -{} - 1; -$a", max),
+            Err(format!("integer overflow: -({})", min)),
+            Object::Integer(min),
+        ),
+        (
+            format!("(-{} - 1) / -1", max),
+            Err(format!("integer overflow: {} / -1", min)),
+            Object::Integer(min),
+        ),
+        (
+            format!("(-{} - 1) % -1", max),
+            Err(format!("integer overflow: {} % -1", min)),
+            Object::Integer(0),
+        ),
+        (
+            format!("{} - 1", max),
+            Ok(Object::Integer(max - 1)),
+            Object::Integer(max - 1),
+        ),
+    ];
+
+    for (input, checked, wrapped) in tests {
+        for wrapping_mode in [WrappingMode::Checked, WrappingMode::Wrapping] {
+            let mut parser = Parser::new(Lexer::new(&input));
+
+            let program = parser.parse_program()?;
+
+            let bytecode = Compiler::new()
+                .with_optimizations()
+                .with_wrapping_mode(wrapping_mode)
+                .compile(&Node::Program(program))?;
+
+            let mut vm = Vm::new(bytecode).with_wrapping_mode(wrapping_mode);
+
+            let result = vm
+                .run()
+                .map(|_| vm.last_popped_stack_elem().as_ref().clone())
+                .map_err(|err| err.to_string());
+
+            match wrapping_mode {
+                WrappingMode::Checked => assert_eq!(result, checked, "{}", input),
+                WrappingMode::Wrapping => assert_eq!(result, Ok(wrapped.clone()), "{}", input),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_recursive_functions() -> Result<(), Error> {
+    let tests = vec![
+        VmTestCase {
+            input: "$countDown = function ($x) { if ($x == 0) { return 0; } else { $countDown($x - 1); } }; $countDown(5);".to_string(),
+            expected: Object::Integer(0),
+        },
+        VmTestCase {
+            input: "$f = function () { $g = function ($n) { if ($n > 0) { $g($n - 1) } else { 0 } }; $g(3) }; $f();".to_string(),
+            expected: Object::Integer(0),
+        },
+        VmTestCase {
+            input: "$wrapper = function () { $countDown = function ($x) { if ($x == 0) { return 0; } else { function () { $countDown($x - 1) }(); } }; $countDown(5); }; $wrapper();".to_string(),
+            expected: Object::Integer(0),
+        },
+        VmTestCase {
+            input: "$fibonacci = function ($n) { if ($n < 2) { return $n; }; $fibonacci($n - 1) + $fibonacci($n - 2); }; $fibonacci(15);".to_string(),
+            expected: Object::Integer(610),
+        },
+    ];
+
+    run_vm_tests(tests)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_unbounded_recursion() -> Result<(), Error> {
+    let errors = vec![
+        (
+            "$f = function () { $f() }; $f();",
+            "stack overflow: max call depth exceeded",
+        ),
+        (
+            "$f = function ($n) { $f($n + 1) }; $f(0);",
+            "stack overflow",
+        ),
+        (
+            "$f = function () { $g = function ($n) { $g($n + 1) }; $g(0) }; $f();",
+            "stack overflow",
+        ),
+    ];
+
+    for (input, expected) in errors {
+        let mut parser = Parser::new(Lexer::new(input));
+
+        let program = parser.parse_program()?;
+
+        let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+        let mut vm = Vm::new(bytecode);
+
+        match vm.run() {
+            Ok(_) => panic!("expected VM error but resulted in none"),
+            Err(err) => assert_eq!(err.to_string(), expected, "{}", input),
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_linked_modules() -> Result<(), Error> {
+    let modules = [
+        "$a = 2; $double = function ($x) { $x * $a };",
+        "$a = 5; if ($a > 1) { $a + 1 } else { 0 };",
+        "$a = [1, 2]; $a[0] + 4",
+    ]
+    .iter()
+    .map(|input| {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program()?;
+
+        Compiler::new().compile(&Node::Program(program))
+    })
+    .collect::<Result<Vec<Bytecode>, Error>>()?;
+
+    let mut vm = Vm::new(Bytecode::link(modules)?);
+
+    vm.run()?;
+
+    assert_eq!(*vm.last_popped_stack_elem(), Object::Integer(5));
+
+    // Each module keeps its own `$a`.
+    assert_eq!(*vm.globals()[0], Object::Integer(2));
+    assert_eq!(*vm.globals()[2], Object::Integer(5));
+
+    Ok(())
+}
+
+/// A writer that appends to a buffer the test can still read afterwards.
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_trace() -> Result<(), Error> {
+    let program = Parser::new(Lexer::new("1 + 2")).parse_program()?;
+    let bytecode = Compiler::new().compile(&Node::Program(program))?;
+
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+
+    let mut vm = Vm::new(bytecode.clone())
+        .with_trace(true)
+        .with_trace_output(Box::new(Capture(Rc::clone(&buffer))));
+    vm.run()?;
+
+    assert_eq!(
+        String::from_utf8(buffer.take())?,
+        "0000 OpConst 0    []
+0003 OpConst 1    [1]
+0006 OpAdd    [1, 2]
+0007 OpPop    [3]
+"
+    );
+
+    // Nothing is written unless tracing is on.
+    let mut vm = Vm::new(bytecode).with_trace_output(Box::new(Capture(Rc::clone(&buffer))));
+    vm.run()?;
+
+    assert!(buffer.take().is_empty());
+
+    Ok(())
+}
@@ -72,8 +72,10 @@ fn execute(input: &str, state: &mut ExecutionState) -> Result<String, String> {
 
     match last_popped.as_ref() {
         Object::Integer(i) => return Ok(i.to_string()),
+        Object::Float(f) => return Ok(f.to_string()),
         Object::Boolean(b) => return Ok(b.to_string()),
         Object::String(s) => return Ok(s.to_string()),
+        Object::Char(c) => return Ok(c.to_string()),
         Object::Null => return Ok("null".to_string()),
         _ => {}
     }